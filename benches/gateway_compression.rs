@@ -0,0 +1,160 @@
+//! Compares per-message and streaming gateway compression on a synthetic trace of `ServerMsg` events.
+//!
+//! Each event is deserialized into a [`ServerMsg`] and serialized again, so the trace matches
+//! what the gateway actually sends rather than whatever was written here.
+//!
+//! Run with `cargo bench --bench gateway_compression`, adding `--features zstd` to include zstd.
+
+use std::time::{Duration, Instant};
+
+use client_sdk::api::gateway::{GatewayCompression, GatewayQueryParams};
+use client_sdk::gateway::{Compressor, Decompressor};
+use client_sdk::models::gateway::message::ServerMsg;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::{json, Value};
+
+const EVENTS: usize = 10_000;
+const ROUNDS: u32 = 5;
+
+fn id(rng: &mut StdRng) -> String {
+    rng.gen_range(100_000_000_000_000_000u64..200_000_000_000_000_000).to_string()
+}
+
+fn member(rng: &mut StdRng, user_id: &str) -> Value {
+    json!({
+        "user": {
+            "id": user_id,
+            "username": format!("user{}", rng.gen_range(0..500)),
+            "discriminator": rng.gen_range(0..9999),
+            "flags": 0,
+            "profile": { "bits": rng.gen::<i32>(), "nick": lipsum::lipsum_words(1) },
+        },
+        "roles": [id(rng), id(rng)],
+        "flags": 0,
+        "joined_at": "2024-05-01T12:34:56.789Z",
+    })
+}
+
+/// Builds a trace of events in roughly the proportions a busy bot would see them
+fn trace(rng: &mut StdRng) -> Vec<Vec<u8>> {
+    let party_id = id(rng);
+    let rooms: Vec<String> = (0..8).map(|_| id(rng)).collect();
+    let users: Vec<String> = (0..50).map(|_| id(rng)).collect();
+
+    (0..EVENTS)
+        .map(|_| {
+            let room_id = &rooms[rng.gen_range(0..rooms.len())];
+            let user_id = &users[rng.gen_range(0..users.len())];
+
+            let event = match rng.gen_range(0..10) {
+                // MessageCreate
+                0..=4 => json!({ "o": 19, "p": {
+                    "id": id(rng),
+                    "room_id": room_id,
+                    "party_id": party_id,
+                    "kind": 0,
+                    "author": member(rng, user_id),
+                    "content": lipsum::lipsum_words(rng.gen_range(1..60)),
+                    "flags": 0,
+                }}),
+                // TypingStart
+                5..=7 => json!({ "o": 27, "p": {
+                    "room_id": room_id,
+                    "party_id": party_id,
+                    "user_id": user_id,
+                    "member": member(rng, user_id),
+                }}),
+                // PresenceUpdate
+                8 => json!({ "o": 26, "p": {
+                    "party_id": party_id,
+                    "user": {
+                        "id": user_id,
+                        "username": "someone",
+                        "discriminator": 1234,
+                        "flags": 0,
+                        "presence": { "flags": rng.gen_range(0..8), "last_active": rng.gen::<u16>() },
+                    },
+                }}),
+                // MessageReactionAdd
+                _ => json!({ "o": 22, "p": {
+                    "user_id": user_id,
+                    "room_id": room_id,
+                    "party_id": party_id,
+                    "msg_id": id(rng),
+                    "emote": { "emoji": "👍" },
+                }}),
+            };
+
+            let raw = serde_json::to_vec(&event).unwrap();
+
+            let msg: ServerMsg =
+                serde_json::from_slice(&raw).unwrap_or_else(|e| panic!("trace event is not a valid ServerMsg: {e}\n{event:#}"));
+
+            assert!(
+                !matches!(msg, ServerMsg::Unknown(_)),
+                "trace event has an unknown opcode: {event:#}"
+            );
+
+            serde_json::to_vec(&msg).unwrap()
+        })
+        .collect()
+}
+
+fn bench(trace: &[Vec<u8>], compress: GatewayCompression, level: u8) {
+    let params = GatewayQueryParams {
+        compress,
+        ..GatewayQueryParams::default()
+    };
+
+    let (mut compress_time, mut decompress_time) = (Duration::ZERO, Duration::ZERO);
+    let mut compressed_size = 0;
+
+    for _ in 0..ROUNDS {
+        let mut compressor = Compressor::new(&params, level).unwrap();
+        let mut decompressor = Decompressor::new(&params).unwrap();
+
+        let start = Instant::now();
+        let compressed: Vec<Vec<u8>> = trace.iter().map(|msg| compressor.compress(msg.clone()).unwrap()).collect();
+        compress_time += start.elapsed();
+
+        let start = Instant::now();
+        for msg in &compressed {
+            std::hint::black_box(decompressor.decompress(msg.clone()).unwrap());
+        }
+        decompress_time += start.elapsed();
+
+        compressed_size = compressed.iter().map(Vec::len).sum::<usize>();
+    }
+
+    let raw_size = trace.iter().map(Vec::len).sum::<usize>();
+
+    println!(
+        "{:<11} level {:>2}: {:>9} -> {:>9} bytes ({:>5.1}%), compress {:>8.2?}/round, decompress {:>8.2?}/round",
+        format!("{compress:?}"),
+        level,
+        raw_size,
+        compressed_size,
+        compressed_size as f64 * 100.0 / raw_size as f64,
+        compress_time / ROUNDS,
+        decompress_time / ROUNDS,
+    );
+}
+
+fn main() {
+    let trace = trace(&mut StdRng::seed_from_u64(0x1a17e2));
+
+    println!("{EVENTS} events, {ROUNDS} rounds");
+
+    for compress in [GatewayCompression::Zlib, GatewayCompression::ZlibStream] {
+        for level in [1, 6, 9] {
+            bench(&trace, compress, level);
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    for compress in [GatewayCompression::Zstd, GatewayCompression::ZstdStream] {
+        for level in [1, 3, 9, 19] {
+            bench(&trace, compress, level);
+        }
+    }
+}
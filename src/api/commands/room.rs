@@ -16,7 +16,7 @@ command! { Room;
 
             #[serde(default, skip_serializing_if = "Option::is_none")]
             #[cfg_attr(feature = "typed-builder", builder(default))]
-            pub parent: Option<RoomId>,
+            pub parent: Option<MessageId>,
 
             #[serde(default, skip_serializing_if = "ThinVec::is_empty")]
             #[cfg_attr(feature = "typed-builder", builder(default, setter(into)))]
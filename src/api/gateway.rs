@@ -1,11 +1,124 @@
 pub use crate::driver::Encoding;
 
-const fn default_compress() -> bool {
-    true
+/// Compression method for gateway messages
+///
+/// For compatibility with older servers and clients, no compression and per-message zlib
+/// are serialized as `false` and `true`, respectively, and may be deserialized from either
+/// booleans or strings.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GatewayCompression {
+    /// Messages are not compressed
+    None,
+
+    /// Each message is compressed individually with zlib
+    #[default]
+    Zlib,
+
+    /// A single zlib stream is used for the whole connection, flushed after each message
+    ///
+    /// Streaming compression achieves much better ratios on small messages, but requires
+    /// that every message is processed in order.
+    ZlibStream,
+
+    /// Each message is compressed individually with zstd
+    ///
+    /// Only supported by the gateway connection with the `zstd` feature.
+    Zstd,
+
+    /// A single zstd stream is used for the whole connection, flushed after each message
+    ZstdStream,
 }
 
+impl GatewayCompression {
+    /// Value of the compression method within the query string
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            GatewayCompression::None => "false",
+            GatewayCompression::Zlib => "true",
+            GatewayCompression::ZlibStream => "zlib-stream",
+            GatewayCompression::Zstd => "zstd",
+            GatewayCompression::ZstdStream => "zstd-stream",
+        }
+    }
+
+    /// Whether a single compression context lasts for the whole connection
+    #[must_use]
+    pub const fn is_streaming(self) -> bool {
+        matches!(self, GatewayCompression::ZlibStream | GatewayCompression::ZstdStream)
+    }
+}
+
+mod compression_serde {
+    use super::GatewayCompression;
+
+    use core::fmt;
+
+    use serde::de::{self, Deserialize, Deserializer, Visitor};
+    use serde::ser::{Serialize, Serializer};
+
+    impl Serialize for GatewayCompression {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match *self {
+                GatewayCompression::None => serializer.serialize_bool(false),
+                GatewayCompression::Zlib => serializer.serialize_bool(true),
+                _ => serializer.serialize_str(self.as_str()),
+            }
+        }
+    }
+
+    const VARIANTS: &[&str] = &["false", "true", "zlib-stream", "zstd", "zstd-stream"];
+
+    impl<'de> Deserialize<'de> for GatewayCompression {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct CompressionVisitor;
+
+            impl Visitor<'_> for CompressionVisitor {
+                type Value = GatewayCompression;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a boolean or compression method")
+                }
+
+                fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+                    Ok(if value { GatewayCompression::Zlib } else { GatewayCompression::None })
+                }
+
+                fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                    Ok(match value {
+                        "false" | "none" => GatewayCompression::None,
+                        "true" | "zlib" => GatewayCompression::Zlib,
+                        "zlib-stream" => GatewayCompression::ZlibStream,
+                        "zstd" => GatewayCompression::Zstd,
+                        "zstd-stream" => GatewayCompression::ZstdStream,
+                        _ => return Err(E::unknown_variant(value, VARIANTS)),
+                    })
+                }
+            }
+
+            deserializer.deserialize_any(CompressionVisitor)
+        }
+    }
+}
+
+#[cfg(feature = "ts")]
+const _: () = {
+    use ts_bindgen::{TypeRegistry, TypeScriptDef, TypeScriptType};
+
+    impl TypeScriptDef for GatewayCompression {
+        fn register(_: &mut TypeRegistry) -> TypeScriptType {
+            TypeScriptType::Union(vec![
+                TypeScriptType::Boolean(None),
+                TypeScriptType::String(Some("zlib-stream".into())),
+                TypeScriptType::String(Some("zstd".into())),
+                TypeScriptType::String(Some("zstd-stream".into())),
+            ])
+        }
+    }
+};
+
 /// Query parameters for the gateway, used to configure the connection.
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(ts_bindgen::TypeScriptDef))]
 #[serde(default)]
 pub struct GatewayQueryParams {
@@ -13,16 +126,11 @@ pub struct GatewayQueryParams {
     #[serde(alias = "e")]
     pub encoding: Encoding,
 
-    /// Whether to compress individual messages
+    /// Compression method for messages
     #[serde(alias = "c")]
-    pub compress: bool,
-}
+    pub compress: GatewayCompression,
 
-impl Default for GatewayQueryParams {
-    fn default() -> Self {
-        GatewayQueryParams {
-            encoding: Encoding::default(),
-            compress: default_compress(),
-        }
-    }
+    /// ID of a shared zstd dictionary to compress messages with, known to both client and server
+    #[serde(alias = "d", skip_serializing_if = "Option::is_none")]
+    pub dict: Option<u32>,
 }
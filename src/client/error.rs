@@ -19,6 +19,31 @@ pub enum ClientError {
 
     #[error("Not a file")]
     NotAFile,
+
+    #[error("Invalid Message: {0}")]
+    InvalidMessage(#[from] MessageError),
+}
+
+/// Reasons a message may be rejected before being sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum MessageError {
+    #[error("Message is empty")]
+    Empty,
+
+    #[error("Message content is too long ({len} > {max} characters)")]
+    TooLong { len: usize, max: usize },
+
+    #[error("Message has too many lines ({lines} > {max})")]
+    TooManyLines { lines: usize, max: usize },
+
+    #[error("Message has too many attachments ({count} > {max})")]
+    TooManyAttachments { count: usize, max: usize },
+
+    #[error("Message has too many embeds ({count} > {max})")]
+    TooManyEmbeds { count: usize, max: usize },
+
+    #[error("Attachment is too large ({size} > {max} bytes)")]
+    AttachmentTooLarge { size: u64, max: u64 },
 }
 
 impl From<DriverError> for ClientError {
@@ -4,7 +4,7 @@ use tokio::io::{AsyncRead, AsyncReadExt};
 use super::{Client, ClientError};
 use crate::{
     api::commands::file::{CreateFile, CreateFileBody},
-    models::Snowflake,
+    models::FileId,
 };
 
 impl Client {
@@ -19,7 +19,7 @@ impl Client {
         mime: Option<mime::Mime>,
         file: &mut tokio::fs::File,
         progress: impl FnMut(u64, u64),
-    ) -> Result<Snowflake, ClientError> {
+    ) -> Result<FileId, ClientError> {
         let meta = file.metadata().await?;
 
         if !meta.is_file() {
@@ -47,7 +47,7 @@ impl Client {
         meta: CreateFileBody,
         stream: impl AsyncRead,
         mut progress: impl FnMut(u64, u64),
-    ) -> Result<Snowflake, ClientError> {
+    ) -> Result<FileId, ClientError> {
         let file_size = meta.size as u64;
         let file_id = self.driver().execute(CreateFile { body: meta }).await?;
 
@@ -0,0 +1,456 @@
+//! Lightweight handles to parties, rooms, messages, users and members,
+//! providing convenience methods built on top of the API commands.
+
+use smol_str::SmolStr;
+use thin_vec::ThinVec;
+
+use super::{Client, ClientError};
+use crate::{api::commands::all::*, models::*};
+
+impl Client {
+    /// Construct a handle to the given party
+    pub fn party(&self, party_id: PartyId) -> PartyHandle {
+        PartyHandle {
+            client: self.clone(),
+            id: party_id,
+        }
+    }
+
+    /// Construct a handle to the given room
+    pub fn room(&self, room_id: RoomId) -> RoomHandle {
+        RoomHandle {
+            client: self.clone(),
+            id: room_id,
+        }
+    }
+
+    /// Construct a handle to the given message within a room
+    pub fn message(&self, room_id: RoomId, msg_id: MessageId) -> MessageHandle {
+        MessageHandle {
+            client: self.clone(),
+            room_id,
+            id: msg_id,
+        }
+    }
+
+    /// Construct a handle to the given user
+    pub fn user(&self, user_id: UserId) -> UserHandle {
+        UserHandle {
+            client: self.clone(),
+            id: user_id,
+        }
+    }
+
+    /// Construct a handle to the given member of a party
+    pub fn member(&self, party_id: PartyId, user_id: UserId) -> MemberHandle {
+        MemberHandle {
+            client: self.clone(),
+            party_id,
+            user_id,
+        }
+    }
+}
+
+/// Handle to a party, bound to a [`Client`]
+#[must_use]
+#[derive(Clone)]
+pub struct PartyHandle {
+    client: Client,
+    id: PartyId,
+}
+
+/// Handle to a room, bound to a [`Client`]
+#[must_use]
+#[derive(Clone)]
+pub struct RoomHandle {
+    client: Client,
+    id: RoomId,
+}
+
+/// Handle to a message within a room, bound to a [`Client`]
+#[must_use]
+#[derive(Clone)]
+pub struct MessageHandle {
+    client: Client,
+    room_id: RoomId,
+    id: MessageId,
+}
+
+/// Handle to a user, bound to a [`Client`]
+#[must_use]
+#[derive(Clone)]
+pub struct UserHandle {
+    client: Client,
+    id: UserId,
+}
+
+/// Handle to a member of a party, bound to a [`Client`]
+#[must_use]
+#[derive(Clone)]
+pub struct MemberHandle {
+    client: Client,
+    party_id: PartyId,
+    user_id: UserId,
+}
+
+impl PartyHandle {
+    #[inline]
+    #[must_use]
+    pub fn id(&self) -> PartyId {
+        self.id
+    }
+
+    #[inline]
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Construct a handle to a member of this party
+    pub fn member(&self, user_id: UserId) -> MemberHandle {
+        self.client.member(self.id, user_id)
+    }
+
+    /// Fetch the party
+    pub async fn fetch(&self) -> Result<Party, ClientError> {
+        Ok(self.client.driver().execute(GetParty { party_id: self.id }).await?)
+    }
+
+    /// Fetch all rooms in the party visible to the current user
+    pub async fn rooms(&self) -> Result<Vec<Room>, ClientError> {
+        Ok(self.client.driver().execute(GetPartyRooms { party_id: self.id }).await?)
+    }
+
+    /// Fetch all members of the party
+    pub async fn members(&self) -> Result<Vec<PartyMember>, ClientError> {
+        Ok(self.client.driver().execute(GetPartyMembers { party_id: self.id }).await?)
+    }
+
+    /// Fetch all active invites to the party
+    pub async fn invites(&self) -> Result<Vec<Invite>, ClientError> {
+        Ok(self.client.driver().execute(GetPartyInvites { party_id: self.id }).await?)
+    }
+
+    /// Create a new invite to the party
+    pub async fn create_invite(&self, body: CreatePartyInviteBody) -> Result<Invite, ClientError> {
+        let cmd = CreatePartyInvite { party_id: self.id, body };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Create a new room in the party
+    pub async fn create_room(&self, body: CreateRoomForm) -> Result<Room, ClientError> {
+        let cmd = CreateRoom { party_id: self.id, body };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Create a new role in the party
+    pub async fn create_role(&self, body: CreateRoleForm) -> Result<Role, ClientError> {
+        let cmd = CreateRole { party_id: self.id, body };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Delete a role from the party
+    pub async fn delete_role(&self, role_id: RoleId) -> Result<(), ClientError> {
+        let cmd = DeleteRole {
+            party_id: self.id,
+            role_id,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+}
+
+impl RoomHandle {
+    #[inline]
+    #[must_use]
+    pub fn id(&self) -> RoomId {
+        self.id
+    }
+
+    #[inline]
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Construct a handle to a message in this room
+    pub fn message(&self, msg_id: MessageId) -> MessageHandle {
+        self.client.message(self.id, msg_id)
+    }
+
+    /// Fetch the room, including the current user's permissions within it
+    pub async fn fetch(&self) -> Result<FullRoom, ClientError> {
+        Ok(self.client.driver().execute(GetRoom { room_id: self.id }).await?)
+    }
+
+    /// Send a plain text message to the room
+    pub async fn send(&self, content: impl Into<SmolStr>) -> Result<Message, ClientError> {
+        self.send_body(CreateMessageBody {
+            content: content.into(),
+            parent: None,
+            attachments: ThinVec::new(),
+            embeds: ThinVec::new(),
+            ephemeral: false,
+            tts: false,
+        })
+        .await
+    }
+
+    /// Send a message with the full message body to the room
+    pub async fn send_body(&self, body: CreateMessageBody) -> Result<Message, ClientError> {
+        let cmd = CreateMessage { room_id: self.id, body };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Trigger the typing indicator for the current user in this room
+    pub async fn typing(&self) -> Result<(), ClientError> {
+        let cmd = StartTyping {
+            room_id: self.id,
+            body: StartTypingBody::default(),
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Fetch the most recent messages in the room
+    pub async fn messages(&self) -> Result<Vec<Message>, ClientError> {
+        self.query_messages(GetMessagesQuery::default()).await
+    }
+
+    /// Fetch messages in the room matching the given query
+    pub async fn query_messages(&self, query: GetMessagesQuery) -> Result<Vec<Message>, ClientError> {
+        let cmd = GetMessages {
+            room_id: self.id,
+            body: query,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+}
+
+impl MessageHandle {
+    #[inline]
+    #[must_use]
+    pub fn id(&self) -> MessageId {
+        self.id
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn room_id(&self) -> RoomId {
+        self.room_id
+    }
+
+    #[inline]
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Construct a handle to the room this message is in
+    pub fn room(&self) -> RoomHandle {
+        self.client.room(self.room_id)
+    }
+
+    /// Fetch the message
+    pub async fn fetch(&self) -> Result<Message, ClientError> {
+        let cmd = GetMessage {
+            room_id: self.room_id,
+            msg_id: self.id,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Reply to this message with a plain text message
+    pub async fn reply(&self, content: impl Into<SmolStr>) -> Result<Message, ClientError> {
+        self.room()
+            .send_body(CreateMessageBody {
+                content: content.into(),
+                parent: Some(self.id),
+                attachments: ThinVec::new(),
+                embeds: ThinVec::new(),
+                ephemeral: false,
+                tts: false,
+            })
+            .await
+    }
+
+    /// Replace the content of this message
+    pub async fn edit(&self, content: impl Into<SmolStr>) -> Result<Message, ClientError> {
+        self.edit_body(EditMessageBody {
+            content: content.into(),
+            attachments: ThinVec::new(),
+        })
+        .await
+    }
+
+    /// Edit this message with the full message body
+    pub async fn edit_body(&self, body: EditMessageBody) -> Result<Message, ClientError> {
+        let cmd = EditMessage {
+            room_id: self.room_id,
+            msg_id: self.id,
+            body,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Delete this message
+    pub async fn delete(&self) -> Result<(), ClientError> {
+        let cmd = DeleteMessage {
+            room_id: self.room_id,
+            msg_id: self.id,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Add a reaction from the current user
+    pub async fn react(&self, emote: EmoteOrEmoji) -> Result<(), ClientError> {
+        let cmd = PutReaction {
+            room_id: self.room_id,
+            msg_id: self.id,
+            emote_id: emote,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Remove a reaction from the current user
+    pub async fn unreact(&self, emote: EmoteOrEmoji) -> Result<(), ClientError> {
+        let cmd = DeleteOwnReaction {
+            room_id: self.room_id,
+            msg_id: self.id,
+            emote_id: emote,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Remove all reactions from this message
+    pub async fn clear_reactions(&self) -> Result<(), ClientError> {
+        let cmd = DeleteAllReactions {
+            room_id: self.room_id,
+            msg_id: self.id,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Pin this message to the given pin folder
+    pub async fn pin(&self, folder: FolderId) -> Result<(), ClientError> {
+        let cmd = PinMessage {
+            room_id: self.room_id,
+            msg_id: self.id,
+            pin_tag: folder,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Unpin this message from the given pin folder
+    pub async fn unpin(&self, folder: FolderId) -> Result<(), ClientError> {
+        let cmd = UnpinMessage {
+            room_id: self.room_id,
+            msg_id: self.id,
+            pin_tag: folder,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Star this message for the current user
+    pub async fn star(&self) -> Result<(), ClientError> {
+        let cmd = StarMessage {
+            room_id: self.room_id,
+            msg_id: self.id,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Unstar this message for the current user
+    pub async fn unstar(&self) -> Result<(), ClientError> {
+        let cmd = UnstarMessage {
+            room_id: self.room_id,
+            msg_id: self.id,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+}
+
+impl UserHandle {
+    #[inline]
+    #[must_use]
+    pub fn id(&self) -> UserId {
+        self.id
+    }
+
+    #[inline]
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Fetch the user, including profile data
+    pub async fn fetch(&self) -> Result<User, ClientError> {
+        Ok(self.client.driver().execute(GetUser { user_id: self.id }).await?)
+    }
+}
+
+impl MemberHandle {
+    #[inline]
+    #[must_use]
+    pub fn party_id(&self) -> PartyId {
+        self.party_id
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    #[inline]
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Construct a handle to the party this member belongs to
+    pub fn party(&self) -> PartyHandle {
+        self.client.party(self.party_id)
+    }
+
+    /// Construct a handle to the underlying user
+    pub fn user(&self) -> UserHandle {
+        self.client.user(self.user_id)
+    }
+
+    /// Fetch the party member
+    pub async fn fetch(&self) -> Result<PartyMember, ClientError> {
+        let cmd = GetPartyMember {
+            party_id: self.party_id,
+            member_id: self.user_id,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+
+    /// Fetch the member's party-specific profile
+    pub async fn profile(&self) -> Result<UserProfile, ClientError> {
+        let cmd = GetMemberProfile {
+            party_id: self.party_id,
+            user_id: self.user_id,
+        };
+
+        Ok(self.client.driver().execute(cmd).await?)
+    }
+}
+
+impl From<(&Client, &Message)> for MessageHandle {
+    fn from((client, msg): (&Client, &Message)) -> Self {
+        client.message(msg.room_id, msg.id)
+    }
+}
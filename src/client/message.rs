@@ -0,0 +1,495 @@
+use core::{fmt, pin::Pin};
+
+use smol_str::SmolStr;
+use thin_vec::ThinVec;
+use tokio::io::AsyncRead;
+
+use super::{Client, ClientError, MessageError, RoomHandle};
+use crate::{
+    api::commands::{file::CreateFileBody, room::CreateMessageBody},
+    models::{Embed, EmbedV1, FileId, Message, MessageId, RoleId, RoomId, ServerLimits, UserId},
+};
+
+/// Whether the character has special meaning in markdown and can be escaped with a backslash
+#[inline]
+#[must_use]
+pub const fn is_escapable(c: char) -> bool {
+    matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '<' | '>' | '[' | ']' | '#' | ':')
+}
+
+/// Appends `text` to `out` with any markdown formatting characters escaped, so it's displayed exactly as given
+pub fn escape_into(text: &str, out: &mut String) {
+    for c in text.chars() {
+        if is_escapable(c) {
+            out.push('\\');
+        }
+
+        out.push(c);
+    }
+}
+
+/// Client-side limits checked by [`MessageBuilder`] before anything is uploaded or sent.
+///
+/// Only the upload size is part of the server's [`ServerLimits`], which the limits can be created from.
+/// The server does not report its limits on message content, so those defaults are chosen by the client
+/// to catch obviously oversized messages early, and may be adjusted to match the server's configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageLimits {
+    /// Maximum length of the message content, in characters, defaulting to [`Self::DEFAULT_MAX_LENGTH`]
+    pub max_length: usize,
+    /// Maximum number of lines in the message content, defaulting to [`Self::DEFAULT_MAX_LINES`]
+    pub max_lines: usize,
+    /// Maximum number of attachments, defaulting to [`Self::DEFAULT_MAX_ATTACHMENTS`]
+    pub max_attachments: usize,
+    /// Maximum number of embeds, defaulting to [`Self::DEFAULT_MAX_EMBEDS`]
+    pub max_embeds: usize,
+    /// Maximum size of each attachment to be uploaded, in bytes, as given by [`ServerLimits::max_upload_size`].
+    ///
+    /// Unchecked by default, as only the server knows it.
+    pub max_upload_size: u64,
+}
+
+impl MessageLimits {
+    /// Default maximum content length, client-side only
+    pub const DEFAULT_MAX_LENGTH: usize = 2500;
+    /// Default maximum number of lines, client-side only
+    pub const DEFAULT_MAX_LINES: usize = 80;
+    /// Default maximum number of attachments, client-side only
+    pub const DEFAULT_MAX_ATTACHMENTS: usize = 10;
+    /// Default maximum number of embeds, client-side only
+    pub const DEFAULT_MAX_EMBEDS: usize = 8;
+
+    fn check_upload_size(&self, size: u64) -> Result<(), MessageError> {
+        match size > self.max_upload_size {
+            true => Err(MessageError::AttachmentTooLarge {
+                size,
+                max: self.max_upload_size,
+            }),
+            false => Ok(()),
+        }
+    }
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        MessageLimits {
+            max_length: Self::DEFAULT_MAX_LENGTH,
+            max_lines: Self::DEFAULT_MAX_LINES,
+            max_attachments: Self::DEFAULT_MAX_ATTACHMENTS,
+            max_embeds: Self::DEFAULT_MAX_EMBEDS,
+            max_upload_size: u64::MAX,
+        }
+    }
+}
+
+impl From<&ServerLimits> for MessageLimits {
+    /// The default limits, with the upload size taken from the server's configuration
+    fn from(limits: &ServerLimits) -> Self {
+        MessageLimits {
+            max_upload_size: limits.max_upload_size,
+            ..MessageLimits::default()
+        }
+    }
+}
+
+enum PendingAttachment {
+    Stream {
+        meta: CreateFileBody,
+        stream: Pin<Box<dyn AsyncRead + Send>>,
+    },
+
+    #[cfg(feature = "fs")]
+    Path(std::path::PathBuf),
+}
+
+/// Incrementally builds a message, including mentions, code blocks, embeds and attachments.
+///
+/// ```ignore
+/// let msg = MessageBuilder::new()
+///     .text("Hello, ")
+///     .mention_user(user_id)
+///     .code_block(Some("rust"), "fn main() {}")
+///     .reply_to(msg_id)
+///     .send(&client, room_id)
+///     .await?;
+/// ```
+#[must_use]
+#[derive(Default)]
+pub struct MessageBuilder {
+    content: String,
+    parent: Option<MessageId>,
+    attachments: ThinVec<FileId>,
+    pending: Vec<PendingAttachment>,
+    embeds: ThinVec<Embed>,
+    ephemeral: bool,
+    tts: bool,
+    limits: MessageLimits,
+}
+
+impl MessageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current message content
+    #[inline]
+    #[must_use]
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Appends raw text, which may contain markdown
+    pub fn text(mut self, text: &str) -> Self {
+        self.content.push_str(text);
+        self
+    }
+
+    /// Appends text with any markdown formatting characters escaped
+    pub fn escaped(mut self, text: &str) -> Self {
+        escape_into(text, &mut self.content);
+        self
+    }
+
+    /// Appends a newline
+    pub fn newline(mut self) -> Self {
+        self.content.push('\n');
+        self
+    }
+
+    /// Appends a mention of the given user
+    pub fn mention_user(self, user_id: UserId) -> Self {
+        self.format(format_args!("<@{user_id}>"))
+    }
+
+    /// Appends a mention of the given role
+    pub fn mention_role(self, role_id: RoleId) -> Self {
+        self.format(format_args!("<@&{role_id}>"))
+    }
+
+    /// Appends a mention of the given room
+    pub fn mention_room(self, room_id: RoomId) -> Self {
+        self.format(format_args!("<#{room_id}>"))
+    }
+
+    /// Appends inline code, using enough backticks to contain any within `code`
+    pub fn inline_code(mut self, code: &str) -> Self {
+        let fence = "`".repeat(longest_backtick_run(code) + 1);
+
+        // padding is required if the code itself starts or ends with a backtick
+        let pad = if code.starts_with('`') || code.ends_with('`') { " " } else { "" };
+
+        self.content.extend([&*fence, pad, code, pad, &*fence]);
+        self
+    }
+
+    /// Appends a fenced code block on its own lines, with an optional language tag
+    ///
+    /// The fence is lengthened as needed so `code` cannot terminate the block early.
+    pub fn code_block(mut self, lang: Option<&str>, code: &str) -> Self {
+        let fence = "`".repeat(longest_backtick_run(code).max(2) + 1);
+
+        if !(self.content.is_empty() || self.content.ends_with('\n')) {
+            self.content.push('\n');
+        }
+
+        self.content.extend([&*fence, lang.unwrap_or_default(), "\n", code]);
+
+        if !code.ends_with('\n') {
+            self.content.push('\n');
+        }
+
+        self.content.push_str(&fence);
+        self.content.push('\n');
+        self
+    }
+
+    /// Appends formatted text, such as from [`format_args!`]
+    pub fn format(mut self, args: fmt::Arguments) -> Self {
+        // writing to a String cannot fail
+        _ = fmt::Write::write_fmt(&mut self.content, args);
+        self
+    }
+
+    /// Adds an embed to the message
+    pub fn embed(mut self, embed: EmbedV1) -> Self {
+        self.embeds.push(Embed::V1(embed));
+        self
+    }
+
+    /// Sets the message this is in reply to
+    pub fn reply_to(mut self, parent: MessageId) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Sets or clears the parent message
+    pub fn parent(mut self, parent: Option<MessageId>) -> Self {
+        self.parent = parent;
+        self
+    }
+
+    /// Sets whether the message is ephemeral
+    pub fn ephemeral(mut self, ephemeral: bool) -> Self {
+        self.ephemeral = ephemeral;
+        self
+    }
+
+    /// Sets whether the message should be read aloud with text-to-speech
+    pub fn tts(mut self, tts: bool) -> Self {
+        self.tts = tts;
+        self
+    }
+
+    /// Overrides the limits checked before sending
+    pub fn limits(mut self, limits: MessageLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Attaches an already-uploaded file
+    pub fn attachment(mut self, file_id: FileId) -> Self {
+        self.attachments.push(file_id);
+        self
+    }
+
+    /// Attaches a file stream, to be uploaded with [`Client::upload_stream`] when the message is built
+    pub fn attach(mut self, meta: CreateFileBody, stream: impl AsyncRead + Send + 'static) -> Self {
+        self.pending.push(PendingAttachment::Stream {
+            meta,
+            stream: Box::pin(stream),
+        });
+        self
+    }
+
+    /// Attaches a local file, to be uploaded with [`Client::upload_plain_file`] when the message is built
+    #[cfg(feature = "fs")]
+    pub fn attach_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.pending.push(PendingAttachment::Path(path.into()));
+        self
+    }
+
+    /// Checks the message against the configured [`MessageLimits`]
+    pub fn validate(&self) -> Result<(), MessageError> {
+        let limits = &self.limits;
+
+        let attachments = self.attachments.len() + self.pending.len();
+
+        if self.content.trim().is_empty() && attachments == 0 && self.embeds.is_empty() {
+            return Err(MessageError::Empty);
+        }
+
+        let len = self.content.chars().count();
+
+        if len > limits.max_length {
+            return Err(MessageError::TooLong {
+                len,
+                max: limits.max_length,
+            });
+        }
+
+        let lines = self.content.lines().count();
+
+        if lines > limits.max_lines {
+            return Err(MessageError::TooManyLines {
+                lines,
+                max: limits.max_lines,
+            });
+        }
+
+        if attachments > limits.max_attachments {
+            return Err(MessageError::TooManyAttachments {
+                count: attachments,
+                max: limits.max_attachments,
+            });
+        }
+
+        if self.embeds.len() > limits.max_embeds {
+            return Err(MessageError::TooManyEmbeds {
+                count: self.embeds.len(),
+                max: limits.max_embeds,
+            });
+        }
+
+        for attachment in &self.pending {
+            // the size of local files is only known once they're opened, see `build`
+            if let PendingAttachment::Stream { meta, .. } = attachment {
+                limits.check_upload_size(meta.size.max(0) as u64)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates the message and uploads any pending attachments, producing the final message body
+    pub async fn build(self, client: &Client) -> Result<CreateMessageBody, ClientError> {
+        self.validate()?;
+
+        // check every local file before uploading any of them
+        #[cfg(feature = "fs")]
+        for attachment in &self.pending {
+            if let PendingAttachment::Path(path) = attachment {
+                self.limits.check_upload_size(tokio::fs::metadata(path).await?.len())?;
+            }
+        }
+
+        let MessageBuilder {
+            content,
+            parent,
+            mut attachments,
+            pending,
+            embeds,
+            ephemeral,
+            tts,
+            ..
+        } = self;
+
+        for attachment in pending {
+            attachments.push(match attachment {
+                PendingAttachment::Stream { meta, stream } => client.upload_stream(meta, stream, |_, _| {}).await?,
+
+                #[cfg(feature = "fs")]
+                PendingAttachment::Path(path) => {
+                    let filename = match path.file_name() {
+                        Some(name) => SmolStr::new(name.to_string_lossy()),
+                        None => return Err(ClientError::NotAFile),
+                    };
+
+                    #[cfg(feature = "mime_guess")]
+                    let mime = mime_guess::from_path(&path).first();
+
+                    #[cfg(not(feature = "mime_guess"))]
+                    let mime = None;
+
+                    let mut file = tokio::fs::File::open(&path).await?;
+
+                    client.upload_plain_file(filename, mime, &mut file, |_, _| {}).await?
+                }
+            });
+        }
+
+        Ok(CreateMessageBody {
+            content: SmolStr::from(content),
+            parent,
+            attachments,
+            embeds,
+            ephemeral,
+            tts,
+        })
+    }
+
+    /// Builds the message and sends it to the given room
+    pub async fn send(self, client: &Client, room_id: RoomId) -> Result<Message, ClientError> {
+        client.room(room_id).send_message(self).await
+    }
+}
+
+impl RoomHandle {
+    /// Build and send a message to the room
+    pub async fn send_message(&self, builder: MessageBuilder) -> Result<Message, ClientError> {
+        let body = builder.build(self.client()).await?;
+
+        self.send_body(body).await
+    }
+}
+
+fn longest_backtick_run(s: &str) -> usize {
+    let (mut longest, mut current) = (0, 0);
+
+    for c in s.chars() {
+        if c == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::cast_id;
+
+    #[test]
+    fn test_mentions() {
+        let id: crate::models::Snowflake = "1234".parse().unwrap();
+
+        let msg = MessageBuilder::new()
+            .mention_user(cast_id(id))
+            .text(" ")
+            .mention_role(cast_id(id))
+            .text(" ")
+            .mention_room(cast_id(id));
+
+        assert_eq!(msg.content(), "<@1234> <@&1234> <#1234>");
+    }
+
+    #[test]
+    fn test_escaped() {
+        let msg = MessageBuilder::new().text("**").escaped(r"a_b\").text("**");
+
+        assert_eq!(msg.content(), r"**a\_b\\**");
+    }
+
+    #[test]
+    fn test_code() {
+        let msg = MessageBuilder::new().text("a").code_block(Some("rs"), "let x = \"```\";");
+        assert_eq!(msg.content(), "a\n````rs\nlet x = \"```\";\n````\n");
+
+        let msg = MessageBuilder::new().inline_code("`a`");
+        assert_eq!(msg.content(), "`` `a` ``");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(MessageBuilder::new().text("  ").validate(), Err(MessageError::Empty));
+
+        let limits = MessageLimits {
+            max_length: 4,
+            ..MessageLimits::default()
+        };
+
+        let msg = MessageBuilder::new().limits(limits).text("hello");
+        assert_eq!(msg.validate(), Err(MessageError::TooLong { len: 5, max: 4 }));
+
+        let msg = MessageBuilder::new().limits(limits).text("héé");
+        assert_eq!(msg.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_upload_size() {
+        let limits = MessageLimits::from(&ServerLimits {
+            max_upload_size: 4,
+            max_avatar_size: 0,
+            max_banner_size: 0,
+            max_avatar_pixels: 0,
+            max_banner_pixels: 0,
+            avatar_width: 0,
+            banner_width: 0,
+            banner_height: 0,
+        });
+
+        assert_eq!(limits.max_length, MessageLimits::DEFAULT_MAX_LENGTH);
+
+        let meta = |size| CreateFileBody {
+            filename: SmolStr::new_static("a.txt"),
+            size,
+            mime: None,
+            width: None,
+            height: None,
+            preview: None,
+        };
+
+        let msg = MessageBuilder::new().limits(limits).attach(meta(4), tokio::io::empty());
+        assert_eq!(msg.validate(), Ok(()));
+
+        let msg = MessageBuilder::new().limits(limits).attach(meta(5), tokio::io::empty());
+        assert_eq!(msg.validate(), Err(MessageError::AttachmentTooLarge { size: 5, max: 4 }));
+
+        // unchecked without the server's limits
+        let msg = MessageBuilder::new().attach(meta(5), tokio::io::empty());
+        assert_eq!(msg.validate(), Ok(()));
+    }
+}
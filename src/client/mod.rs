@@ -8,9 +8,14 @@ use crate::{
 };
 
 mod error;
-pub use error::ClientError;
+pub use error::{ClientError, MessageError};
 
 mod file;
+mod handle;
+mod message;
+
+pub use handle::{MemberHandle, MessageHandle, PartyHandle, RoomHandle, UserHandle};
+pub use message::{escape_into, is_escapable, MessageBuilder, MessageLimits};
 
 struct ClientInner {
     inner: reqwest::Client,
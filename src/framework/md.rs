@@ -1 +1,925 @@
+//! Utilities for Lantern's flavour of markdown
+//!
+//! Supported formatting:
+//! - `**bold**`, `*italic*` or `_italic_`, `__underline__`, `~~strikethrough~~` and `||spoilers||`
+//! - `` `inline code` `` and fenced code blocks with an optional language, ```` ```rust ... ``` ````
+//! - `> quotes`, at the start of a line
+//! - `[links](https://example.com)` and `<https://example.com>`
+//! - mentions such as `<@id>`, `<@&id>` and `<#id>`, and custom emotes as `<:name:id>`
+//!
+//! Any formatting character can be escaped with a backslash, except within code.
+//!
+//! ```rust,ignore
+//! // safely embed user input, which cannot inject formatting or mentions
+//! let text = format!("**{}** said hello", md::escape(&name));
+//!
+//! // strip formatting for logs
+//! assert_eq!(md::strip("**bold** and ||secret||"), "bold and secret");
+//! ```
 
+use alloc::borrow::Cow;
+use core::ops::Range;
+
+pub use crate::client::{escape_into, is_escapable};
+
+use crate::framework_utils::parse::{parse_tag, Mention, Tag};
+use crate::models::EmoteId;
+
+/// Escapes any formatting characters in `text`, so it's displayed exactly as given
+#[must_use]
+pub fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(is_escapable) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len() + 8);
+    escape_into(text, &mut out);
+    Cow::Owned(out)
+}
+
+/// Removes backslashes escaping formatting characters, the inverse of [`escape`]
+///
+/// This treats the entire text as plain, including any code. Use [`parse`] to unescape only where needed.
+#[must_use]
+pub fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.clone().next() {
+                Some(next) if is_escapable(next) => {
+                    out.push(next);
+                    chars.next();
+                }
+                _ => out.push(c),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+
+    Cow::Owned(out)
+}
+
+/// Inline code or a fenced code block found by [`code_spans`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeSpan<'a> {
+    /// Byte range of the code, including the backticks
+    pub range: Range<usize>,
+
+    /// The code itself, without backticks, padding or language
+    pub code: &'a str,
+
+    /// Language of a code block, if given
+    pub lang: Option<&'a str>,
+
+    /// Whether this is a fenced code block, rather than inline code
+    pub block: bool,
+}
+
+/// Finds all inline code and code blocks in the text
+///
+/// Code is delimited by a run of backticks and ends at the next run of exactly the same length.
+/// Runs of three or more backticks delimit code blocks. Unclosed or escaped backticks are plain text.
+#[must_use]
+pub fn code_spans(text: &str) -> Vec<CodeSpan<'_>> {
+    let b = text.as_bytes();
+    let mut spans = Vec::new();
+    let mut i = 0;
+
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += escape_len(b, i),
+            b'`' => {
+                let n = run_len(b, i);
+
+                match find_code_close(b, i + n, n) {
+                    Some(close) => {
+                        spans.push(code_span(text, i, close, n));
+                        i = close + n;
+                    }
+                    None => i += n,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    spans
+}
+
+/// Whether the byte at `idx` is within inline code or a code block, including the backticks
+#[must_use]
+pub fn is_in_code(text: &str, idx: usize) -> bool {
+    code_spans(text).iter().any(|span| span.range.contains(&idx))
+}
+
+/// Markdown syntax tree, see [`parse`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node<'a> {
+    /// Plain text, with any escapes removed
+    Text(Cow<'a, str>),
+    Code(&'a str),
+    CodeBlock {
+        lang: Option<&'a str>,
+        code: &'a str,
+    },
+    Bold(Vec<Node<'a>>),
+    Italic(Vec<Node<'a>>),
+    Underline(Vec<Node<'a>>),
+    Strikethrough(Vec<Node<'a>>),
+    Spoiler(Vec<Node<'a>>),
+    /// Quoted lines, which may only contain inline formatting
+    Quote(Vec<Node<'a>>),
+    Link {
+        text: Vec<Node<'a>>,
+        url: &'a str,
+    },
+    Mention(Mention),
+    Emote {
+        name: &'a str,
+        id: EmoteId,
+    },
+}
+
+impl<'a> Node<'a> {
+    #[must_use]
+    pub fn text(text: impl Into<Cow<'a, str>>) -> Self {
+        Node::Text(text.into())
+    }
+}
+
+/// Parses markdown into a syntax tree
+///
+/// Anything that isn't valid formatting, such as an unclosed `**`, is kept as plain text,
+/// so rendering the tree as [plain text](render_plain) never loses any content.
+#[must_use]
+pub fn parse(text: &str) -> Vec<Node<'_>> {
+    let spans = code_spans(text);
+
+    // whether a line starts within a code span, so it can't be a quote
+    let in_code = |line_start: usize| spans.iter().any(|s| s.range.start < line_start && line_start < s.range.end);
+
+    let mut nodes = Vec::new();
+    let mut quote: Option<Vec<Node<'_>>> = None;
+    let mut plain_start = 0;
+    let mut line_start = 0;
+
+    while line_start <= text.len() {
+        let line_end = text[line_start..].find('\n').map_or(text.len(), |i| line_start + i);
+        let line = &text[line_start..line_end];
+
+        let content = match line.strip_prefix('>') {
+            Some(content) if !in_code(line_start) && !content.starts_with('>') => match content.strip_prefix(' ') {
+                Some(content) => Some(content),
+                None if content.is_empty() => Some(content),
+                None => None,
+            },
+            _ => None,
+        };
+
+        match content {
+            Some(content) => {
+                let children = match quote {
+                    Some(ref mut children) => {
+                        push_text(children, "\n");
+                        children
+                    }
+                    None => {
+                        nodes.extend(parse_inline(&text[plain_start..line_start]));
+                        quote.insert(Vec::new())
+                    }
+                };
+
+                children.extend(parse_inline(content));
+                plain_start = line_end;
+            }
+            None => {
+                if let Some(children) = quote.take() {
+                    nodes.push(Node::Quote(merge_text(children)));
+                }
+            }
+        }
+
+        line_start = line_end + 1;
+    }
+
+    if let Some(children) = quote.take() {
+        nodes.push(Node::Quote(merge_text(children)));
+    }
+
+    nodes.extend(parse_inline(&text[plain_start.min(text.len())..]));
+
+    merge_text(nodes)
+}
+
+/// Parses markdown and renders it as plain text, see [`render_plain`]
+#[must_use]
+pub fn strip(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    render_plain(&parse(text), &mut out);
+    out
+}
+
+/// Renders nodes as plain text, without any formatting
+///
+/// Links are followed by their URL in parentheses, unless the text is the URL itself.
+/// Mentions become `@id`, `@&id` or `#id`, and emotes become `:name:`.
+pub fn render_plain(nodes: &[Node<'_>], out: &mut String) {
+    use core::fmt::Write;
+
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Code(code) | Node::CodeBlock { code, .. } => out.push_str(code),
+            Node::Bold(children)
+            | Node::Italic(children)
+            | Node::Underline(children)
+            | Node::Strikethrough(children)
+            | Node::Spoiler(children)
+            | Node::Quote(children) => render_plain(children, out),
+            Node::Link { text, url } => {
+                let start = out.len();
+                render_plain(text, out);
+
+                if out[start..] != **url {
+                    let _ = write!(out, " ({url})");
+                }
+            }
+            Node::Mention(Mention::User(id)) => _ = write!(out, "@{id}"),
+            Node::Mention(Mention::Role(id)) => _ = write!(out, "@&{id}"),
+            Node::Mention(Mention::Room(id)) => _ = write!(out, "#{id}"),
+            Node::Emote { name, .. } => _ = write!(out, ":{name}:"),
+        }
+    }
+}
+
+/// Renders nodes back to markdown, escaping all text so it's displayed exactly as given
+///
+/// This can be used to safely build formatted messages around untrusted text. Note that inline code
+/// containing two or more consecutive backticks needs a longer fence, so it becomes a code block.
+///
+/// ```rust,ignore
+/// let mut out = String::new();
+///
+/// md::render(&[Node::Bold(vec![Node::text(user_input)]), Node::text(" joined!")], &mut out);
+/// ```
+pub fn render(nodes: &[Node<'_>], out: &mut String) {
+    use core::fmt::Write;
+
+    let mut after_quote = false;
+
+    for node in nodes {
+        // a quote lasts until the end of the line, so anything following it must start on a new line
+        if after_quote && !matches!(node, Node::Text(text) if text.starts_with('\n')) {
+            out.push('\n');
+        }
+
+        after_quote = matches!(node, Node::Quote(_));
+
+        match node {
+            Node::Text(text) => escape_into(text, out),
+            Node::Code(code) => {
+                if code.is_empty() {
+                    continue;
+                }
+
+                let fence = "`".repeat(longest_backtick_run(code) + 1);
+
+                // padding is stripped when parsing, and is required around backticks
+                let pad = match code.trim_matches(' ').is_empty() {
+                    false if code.starts_with([' ', '`']) || code.ends_with([' ', '`']) => " ",
+                    _ => "",
+                };
+
+                out.extend([&*fence, pad, code, pad, &*fence]);
+            }
+            Node::CodeBlock { lang, code } => {
+                let fence = "`".repeat(longest_backtick_run(code).max(2) + 1);
+
+                out.extend([&*fence, lang.unwrap_or_default(), "\n", code, "\n", &*fence]);
+            }
+            Node::Bold(children) => wrap(out, "**", children),
+            Node::Italic(children) => wrap(out, "*", children),
+            Node::Underline(children) => wrap(out, "__", children),
+            Node::Strikethrough(children) => wrap(out, "~~", children),
+            Node::Spoiler(children) => wrap(out, "||", children),
+            Node::Quote(children) => {
+                if !(out.is_empty() || out.ends_with('\n')) {
+                    out.push('\n');
+                }
+
+                let mut quoted = String::new();
+                render(children, &mut quoted);
+
+                for (i, line) in quoted.split('\n').enumerate() {
+                    if i > 0 {
+                        out.push('\n');
+                    }
+
+                    out.push_str("> ");
+                    out.push_str(line);
+                }
+            }
+            Node::Link { text, url } => {
+                out.push('[');
+                render(text, out);
+                out.push_str("](");
+                push_url(out, url);
+                out.push(')');
+            }
+            Node::Mention(Mention::User(id)) => _ = write!(out, "<@{id}>"),
+            Node::Mention(Mention::Role(id)) => _ = write!(out, "<@&{id}>"),
+            Node::Mention(Mention::Room(id)) => _ = write!(out, "<#{id}>"),
+            Node::Emote { name, id } => _ = write!(out, "<:{name}:{id}>"),
+        }
+    }
+}
+
+/// Appends a link's URL, percent-encoding the characters that would end it early
+fn push_url(out: &mut String, url: &str) {
+    use core::fmt::Write;
+
+    for c in url.chars() {
+        match c {
+            ')' => out.push_str("%29"),
+            c if c.is_ascii_whitespace() => _ = write!(out, "%{:02X}", c as u32),
+            c => out.push(c),
+        }
+    }
+}
+
+fn wrap(out: &mut String, delim: &str, children: &[Node<'_>]) {
+    out.push_str(delim);
+    render(children, out);
+    out.push_str(delim);
+}
+
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+/// Length of a run of the byte at `i`
+fn run_len(b: &[u8], i: usize) -> usize {
+    b[i..].iter().take_while(|&&c| c == b[i]).count()
+}
+
+/// Number of bytes to skip for a backslash at `i`, which escapes the next character if possible
+fn escape_len(b: &[u8], i: usize) -> usize {
+    match b.get(i + 1) {
+        Some(&c) if is_escapable(c as char) => 2,
+        _ => 1,
+    }
+}
+
+/// Finds the start of the next run of exactly `n` backticks, where escapes do not apply
+fn find_code_close(b: &[u8], mut i: usize, n: usize) -> Option<usize> {
+    while i < b.len() {
+        if b[i] != b'`' {
+            i += 1;
+            continue;
+        }
+
+        let r = run_len(b, i);
+
+        if r == n {
+            return Some(i);
+        }
+
+        i += r;
+    }
+
+    None
+}
+
+fn code_span(text: &str, open: usize, close: usize, n: usize) -> CodeSpan<'_> {
+    let inner = &text[open + n..close];
+    let range = open..close + n;
+
+    if n < 3 {
+        // a single space of padding on both sides is removed, unless it's all spaces
+        let code = match inner.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+            Some(code) if !inner.trim_matches(' ').is_empty() => code,
+            _ => inner,
+        };
+
+        return CodeSpan {
+            range,
+            code,
+            lang: None,
+            block: false,
+        };
+    }
+
+    let (lang, code) = match inner.split_once('\n') {
+        Some(("", code)) => (None, code),
+        Some((lang, code)) if !lang.contains(char::is_whitespace) => (Some(lang), code),
+        _ => (None, inner),
+    };
+
+    CodeSpan {
+        range,
+        code: code.strip_suffix('\n').unwrap_or(code),
+        lang,
+        block: true,
+    }
+}
+
+/// Appends text, merging it with any preceding text node
+fn push_text<'a>(nodes: &mut Vec<Node<'a>>, text: &'a str) {
+    if text.is_empty() {
+        return;
+    }
+
+    match nodes.last_mut() {
+        Some(Node::Text(last)) => last.to_mut().push_str(text),
+        _ => nodes.push(Node::Text(Cow::Borrowed(text))),
+    }
+}
+
+/// Merges adjacent text nodes, such as at the boundaries of quotes
+fn merge_text(nodes: Vec<Node<'_>>) -> Vec<Node<'_>> {
+    let mut merged = Vec::with_capacity(nodes.len());
+
+    for node in nodes {
+        match (merged.last_mut(), node) {
+            (Some(Node::Text(last)), Node::Text(text)) => last.to_mut().push_str(&text),
+            (_, node) => merged.push(node),
+        }
+    }
+
+    merged
+}
+
+/// Node constructor for a run of delimiters, if the run is valid formatting
+fn delimiter<'a>(c: u8, n: usize) -> Option<fn(Vec<Node<'a>>) -> Node<'a>> {
+    Some(match (c, n) {
+        (b'*' | b'_', 1) => Node::Italic,
+        (b'*', 2) => Node::Bold,
+        (b'*', 3) => |children| Node::Bold(vec![Node::Italic(children)]),
+        (b'_', 2) => Node::Underline,
+        (b'_', 3) => |children| Node::Underline(vec![Node::Italic(children)]),
+        (b'~', 2) => Node::Strikethrough,
+        (b'|', 2) => Node::Spoiler,
+        _ => return None,
+    })
+}
+
+/// Finds the start of a closing run of exactly `n` of the delimiter `c`, skipping escapes and code
+fn find_closer(b: &[u8], from: usize, c: u8, n: usize) -> Option<usize> {
+    let mut i = from;
+
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += escape_len(b, i),
+            b'`' => {
+                let r = run_len(b, i);
+
+                i = match find_code_close(b, i + r, r) {
+                    Some(close) => close + r,
+                    None => i + r,
+                };
+            }
+            x if x == c => {
+                let r = run_len(b, i);
+
+                // must directly follow content, and underscores may not close within a word
+                if r == n
+                    && i > from
+                    && !b[i - 1].is_ascii_whitespace()
+                    && !(c == b'_' && b.get(i + r).is_some_and(u8::is_ascii_alphanumeric))
+                {
+                    return Some(i);
+                }
+
+                i += r;
+            }
+            _ => i += 1,
+        }
+    }
+
+    None
+}
+
+/// Parses `[text](url)` at `i`, returning the text and URL ranges and the end of the link
+fn find_link(b: &[u8], i: usize) -> Option<(Range<usize>, Range<usize>, usize)> {
+    let mut depth = 0;
+    let mut j = i + 1;
+
+    let text_end = loop {
+        match *b.get(j)? {
+            b'\\' => j += escape_len(b, j),
+            b'`' => {
+                let r = run_len(b, j);
+
+                j = match find_code_close(b, j + r, r) {
+                    Some(close) => close + r,
+                    None => j + r,
+                };
+            }
+            b'[' => {
+                depth += 1;
+                j += 1;
+            }
+            b']' if depth == 0 => break j,
+            b']' => {
+                depth -= 1;
+                j += 1;
+            }
+            _ => j += 1,
+        }
+    };
+
+    if b.get(text_end + 1) != Some(&b'(') {
+        return None;
+    }
+
+    let url_start = text_end + 2;
+    let url_len = b[url_start..].iter().position(|&c| c == b')' || c.is_ascii_whitespace())?;
+
+    if url_len == 0 || b[url_start + url_len] != b')' {
+        return None;
+    }
+
+    Some((i + 1..text_end, url_start..url_start + url_len, url_start + url_len + 1))
+}
+
+/// Parses a mention, emote or autolink enclosed in angle brackets
+fn parse_angle(inner: &str) -> Option<Node<'_>> {
+    match parse_tag(inner) {
+        Some(Tag::Mention(mention)) => return Some(Node::Mention(mention)),
+        Some(Tag::Emote { name, id }) => return Some(Node::Emote { name, id }),
+        None => {}
+    }
+
+    if inner.starts_with("https://") || inner.starts_with("http://") {
+        return Some(Node::Link {
+            text: vec![Node::text(inner)],
+            url: inner,
+        });
+    }
+
+    None
+}
+
+/// Parses inline formatting, without quotes
+fn parse_inline(s: &str) -> Vec<Node<'_>> {
+    let b = s.as_bytes();
+    let mut nodes = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+
+    while i < b.len() {
+        let (node, end) = match b[i] {
+            b'\\' => {
+                if escape_len(b, i) == 2 {
+                    // drop the backslash, keeping the escaped character as text
+                    push_text(&mut nodes, &s[text_start..i]);
+                    text_start = i + 1;
+                }
+
+                i += escape_len(b, i);
+                continue;
+            }
+            b'`' => {
+                let n = run_len(b, i);
+
+                let Some(close) = find_code_close(b, i + n, n) else {
+                    i += n;
+                    continue;
+                };
+
+                let span = code_span(s, i, close, n);
+
+                let node = match span.block {
+                    true => Node::CodeBlock {
+                        lang: span.lang,
+                        code: span.code,
+                    },
+                    false => Node::Code(span.code),
+                };
+
+                (node, close + n)
+            }
+            c @ (b'*' | b'_' | b'~' | b'|') => {
+                let n = run_len(b, i);
+
+                // openers must be followed by content, and underscores may not open within a word
+                let can_open = b.get(i + n).is_some_and(|c| !c.is_ascii_whitespace())
+                    && !(c == b'_' && i > 0 && b[i - 1].is_ascii_alphanumeric());
+
+                let found = match delimiter(c, n) {
+                    Some(node) if can_open => find_closer(b, i + n, c, n).map(|close| (node, close)),
+                    _ => None,
+                };
+
+                let Some((node, close)) = found else {
+                    i += n;
+                    continue;
+                };
+
+                (node(parse_inline(&s[i + n..close])), close + n)
+            }
+            b'[' => {
+                let Some((text, url, end)) = find_link(b, i) else {
+                    i += 1;
+                    continue;
+                };
+
+                let node = Node::Link {
+                    text: parse_inline(&s[text]),
+                    url: &s[url],
+                };
+
+                (node, end)
+            }
+            b'<' => {
+                let node = s[i + 1..]
+                    .find(|c: char| c == '>' || c.is_whitespace())
+                    .filter(|&len| b[i + 1 + len] == b'>')
+                    .and_then(|len| Some((parse_angle(&s[i + 1..i + 1 + len])?, i + len + 2)));
+
+                let Some(node) = node else {
+                    i += 1;
+                    continue;
+                };
+
+                node
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+
+        push_text(&mut nodes, &s[text_start..i]);
+        nodes.push(node);
+
+        i = end;
+        text_start = end;
+    }
+
+    push_text(&mut nodes, &s[text_start..]);
+
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Node<'_> {
+        Node::text(text)
+    }
+
+    fn render_md(nodes: &[Node<'_>]) -> String {
+        let mut out = String::new();
+        render(nodes, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_escape() {
+        assert!(matches!(escape("hello world"), Cow::Borrowed("hello world")));
+        assert_eq!(escape("~~[a](b)~~ #c"), r"\~\~\[a\](b)\~\~ \#c");
+        assert_eq!(escape(r"a\b"), r"a\\b");
+
+        for s in ["", "plain", r"\\*", r"**bold** \ <@1> [x](y) > quote", "ünïcödé_~|:#"] {
+            assert_eq!(unescape(&escape(s)), s);
+        }
+
+        // backslashes before other characters are kept
+        assert_eq!(unescape(r"\a\*\"), r"\a*\");
+    }
+
+    #[test]
+    fn test_code_spans() {
+        let spans = code_spans("a `b` ``c ` d`` ```rust\nfn main() {}\n``` `unclosed");
+
+        assert_eq!(spans.len(), 3);
+        assert_eq!((spans[0].code, spans[0].range.clone(), spans[0].block), ("b", 2..5, false));
+        assert_eq!(spans[1].code, "c ` d");
+        assert_eq!(
+            (spans[2].code, spans[2].lang, spans[2].block),
+            ("fn main() {}", Some("rust"), true)
+        );
+
+        // escaped backticks aren't code
+        assert!(!is_in_code("\\`e\\`", 2));
+        assert!(is_in_code("\\``e`", 3));
+
+        // padding is only stripped from both sides, and not from all-space code
+        assert_eq!(code_spans("`` `a` ``")[0].code, "`a`");
+        assert_eq!(code_spans("` a`")[0].code, " a");
+        assert_eq!(code_spans("`  `")[0].code, "  ");
+
+        // escapes don't apply within code
+        assert_eq!(code_spans(r"`a\`")[0].code, r"a\");
+
+        // a block's first line is only a language if there's no whitespace
+        let spans = code_spans("```\nplain\n``` ```not a lang\nx```");
+        assert_eq!((spans[0].code, spans[0].lang), ("plain", None));
+        assert_eq!((spans[1].code, spans[1].lang), ("not a lang\nx", None));
+    }
+
+    #[test]
+    fn test_parse_emphasis() {
+        assert_eq!(
+            parse("**bold** *it* _it_ __under__ ~~strike~~ ||spoiler||"),
+            vec![
+                Node::Bold(vec![text("bold")]),
+                text(" "),
+                Node::Italic(vec![text("it")]),
+                text(" "),
+                Node::Italic(vec![text("it")]),
+                text(" "),
+                Node::Underline(vec![text("under")]),
+                text(" "),
+                Node::Strikethrough(vec![text("strike")]),
+                text(" "),
+                Node::Spoiler(vec![text("spoiler")]),
+            ]
+        );
+
+        assert_eq!(
+            parse("*a **b** c*"),
+            vec![Node::Italic(vec![text("a "), Node::Bold(vec![text("b")]), text(" c")])]
+        );
+
+        assert_eq!(parse("***both***"), vec![Node::Bold(vec![Node::Italic(vec![text("both")])])]);
+
+        // unclosed, whitespace-adjacent, intraword and single delimiters stay as text
+        for s in [
+            "**unclosed",
+            "** spaced**",
+            "*a *",
+            "snake_case_name",
+            "~single~",
+            "|single|",
+            "****",
+            "a * b * c",
+        ] {
+            assert_eq!(parse(s), vec![text(s)], "{s}");
+        }
+
+        // escaped delimiters
+        assert_eq!(parse(r"\*\*not bold\*\*"), vec![text("**not bold**")]);
+        assert_eq!(parse(r"**a\*\*b**"), vec![Node::Bold(vec![text("a**b")])]);
+
+        // delimiters within code don't close formatting
+        assert_eq!(
+            parse("**a `**` b**"),
+            vec![Node::Bold(vec![text("a "), Node::Code("**"), text(" b")])]
+        );
+    }
+
+    #[test]
+    fn test_parse_links_and_mentions() {
+        let nodes = parse("see [the **docs**](https://example.com) or <https://lantern.chat>, <@12> <@&34> <#56> <:crab:78>");
+
+        assert_eq!(
+            nodes[1],
+            Node::Link {
+                text: vec![text("the "), Node::Bold(vec![text("docs")])],
+                url: "https://example.com"
+            }
+        );
+        assert!(matches!(
+            nodes[3],
+            Node::Link {
+                url: "https://lantern.chat",
+                ..
+            }
+        ));
+        assert!(matches!(nodes[5], Node::Mention(Mention::User(_))));
+        assert!(matches!(nodes[7], Node::Mention(Mention::Role(_))));
+        assert!(matches!(nodes[9], Node::Mention(Mention::Room(_))));
+        assert!(matches!(nodes[11], Node::Emote { name: "crab", .. }));
+
+        assert_eq!(
+            parse("[a [nested] link](u)"),
+            vec![Node::Link {
+                text: vec![text("a [nested] link")],
+                url: "u"
+            }]
+        );
+
+        // invalid links and mentions stay as text
+        for s in [
+            "[no url]",
+            "[empty]()",
+            "[space](a b)",
+            "<@abc>",
+            "<@ 1>",
+            "<@>",
+            "<:bad name:1>",
+            "<ftp://x>",
+            "a < b > c",
+        ] {
+            assert_eq!(parse(s), vec![text(s)], "{s}");
+        }
+
+        // escaped mentions can't ping anyone
+        assert_eq!(parse(r"\<@12>"), vec![text("<@12>")]);
+    }
+
+    #[test]
+    fn test_parse_quotes() {
+        assert_eq!(
+            parse("before\n> quoted **text**\n> second\nafter"),
+            vec![
+                text("before\n"),
+                Node::Quote(vec![text("quoted "), Node::Bold(vec![text("text")]), text("\nsecond")]),
+                text("\nafter"),
+            ]
+        );
+
+        assert_eq!(parse(">no space"), vec![text(">no space")]);
+        assert_eq!(parse(r"\> escaped"), vec![text("> escaped")]);
+        assert_eq!(parse("a > b"), vec![text("a > b")]);
+
+        // code within a quote doesn't stop it from being a quote
+        assert_eq!(
+            parse("> run `cargo build`\nafter"),
+            vec![Node::Quote(vec![text("run "), Node::Code("cargo build")]), text("\nafter")]
+        );
+
+        // quotes within code blocks are code
+        assert_eq!(
+            parse("```\n> not a quote\n```"),
+            vec![Node::CodeBlock {
+                lang: None,
+                code: "> not a quote"
+            }]
+        );
+    }
+
+    #[test]
+    fn test_render_plain() {
+        assert_eq!(strip("**bold** and ||secret|| `code`"), "bold and secret code");
+        assert_eq!(strip("[docs](https://x.y) <https://x.y>"), "docs (https://x.y) https://x.y");
+        assert_eq!(strip("> quote\n<@1> <:e:2> \\*"), "quote\n@1 :e: *");
+        assert_eq!(strip("```rs\nlet x = 1;\n```"), "let x = 1;");
+        assert_eq!(strip("**unclosed"), "**unclosed");
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let input = "*<@1> **injected**`";
+
+        let nodes = vec![
+            Node::Bold(vec![text(input)]),
+            text(" said "),
+            Node::Code("`tick"),
+            Node::Spoiler(vec![Node::Italic(vec![text("hi")])]),
+        ];
+
+        let md = render_md(&nodes);
+        assert_eq!(md, r"**\*\<@1\> \*\*injected\*\*\`** said `` `tick ``||*hi*||");
+        assert_eq!(parse(&md), nodes);
+
+        let nodes = vec![
+            text("intro"),
+            Node::Quote(vec![text("one\ntwo")]),
+            text("\n"),
+            Node::CodeBlock {
+                lang: Some("rust"),
+                code: "let s = \"```\";",
+            },
+        ];
+
+        let md = render_md(&nodes);
+        assert_eq!(md, "intro\n> one\n> two\n````rust\nlet s = \"```\";\n````");
+        assert_eq!(parse(&md), {
+            let mut nodes = nodes;
+            nodes[0] = text("intro\n");
+            nodes
+        });
+
+        // text following a quote isn't pulled into it
+        let md = render_md(&[Node::Quote(vec![text("quoted")]), text("after")]);
+        assert_eq!(md, "> quoted\nafter");
+        assert_eq!(parse(&md), [Node::Quote(vec![text("quoted")]), text("\nafter")]);
+
+        // URLs can't be closed early
+        let nodes = vec![Node::Link {
+            text: vec![text("x")],
+            url: "https://x.y/a b)c",
+        }];
+
+        let md = render_md(&nodes);
+        assert_eq!(md, "[x](https://x.y/a%20b%29c)");
+        assert_eq!(
+            parse(&md),
+            vec![Node::Link {
+                text: vec![text("x")],
+                url: "https://x.y/a%20b%29c",
+            }]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-pub use crate::models::gateway::message::{ClientMsg, DynamicServerMsgHandlers, ServerMsg, ServerMsgHandlers};
+pub use crate::models::gateway::message::{ClientMsg, DynamicServerMsgHandlers, ServerMsg, ServerMsgHandlers, ServerMsgOpcode};
 
 pub mod md;
 pub mod standard;
@@ -0,0 +1,171 @@
+use crate::models::{Permissions, RoleId};
+
+/// Declarative requirements a command's invocation must meet before it is run, see [`Command::checks`]
+///
+/// Checks of parent groups apply to their sub-commands as well.
+///
+/// [`Command::checks`]: super::Command::checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checks {
+    /// Permissions the invoker needs in the room, resolved from the party's roles and the room's overwrites
+    pub permissions: Permissions,
+
+    /// Permissions the bot needs in the room
+    pub bot_permissions: Permissions,
+
+    /// Roles the invoker must have, all of which are required
+    pub roles: Vec<RoleId>,
+
+    /// Only the owner of the party may run the command
+    pub owner_only: bool,
+
+    /// The command may only be run in direct messages
+    pub dm_only: bool,
+
+    /// The command may only be run within a party
+    pub party_only: bool,
+}
+
+/// Failed [`Checks`] of a command
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CheckError {
+    #[error("Missing permissions: {}", permission_names(.0))]
+    MissingPermissions(Permissions),
+
+    #[error("Bot is missing permissions: {}", permission_names(.0))]
+    BotMissingPermissions(Permissions),
+
+    #[error("Missing required roles")]
+    MissingRoles(Vec<RoleId>),
+
+    #[error("Command may only be used by the party owner")]
+    OwnerOnly,
+
+    #[error("Command may only be used in direct messages")]
+    DirectMessageOnly,
+
+    #[error("Command may only be used within a party")]
+    PartyOnly,
+}
+
+impl Default for Checks {
+    fn default() -> Self {
+        // `Permissions::default()` is the default set granted to members, not empty
+        Checks {
+            permissions: Permissions::empty(),
+            bot_permissions: Permissions::empty(),
+            roles: Vec::new(),
+            owner_only: false,
+            dm_only: false,
+            party_only: false,
+        }
+    }
+}
+
+/// Lists the names of the permissions, e.g. `BAN_MEMBERS, KICK_MEMBERS`
+pub(super) fn permission_names(permissions: &Permissions) -> String {
+    permissions.iter_names().map(|(name, _)| name).collect::<Vec<_>>().join(", ")
+}
+
+impl Checks {
+    /// Whether there is nothing to check
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Checks::default()
+    }
+
+    /// Combines the requirements of both checks, such as a group's with those of its sub-command
+    pub(super) fn merge(&mut self, other: &Checks) {
+        self.permissions |= other.permissions;
+        self.bot_permissions |= other.bot_permissions;
+        self.owner_only |= other.owner_only;
+        self.dm_only |= other.dm_only;
+        self.party_only |= other.party_only;
+
+        for role in &other.roles {
+            if !self.roles.contains(role) {
+                self.roles.push(*role);
+            }
+        }
+    }
+
+    /// Whether the invoker's party membership or permissions need to be resolved
+    pub(super) fn needs_member(&self) -> bool {
+        !self.permissions.is_empty() || !self.roles.is_empty() || self.owner_only
+    }
+
+    /// Checks the location of the invocation, before anything about the invoker is known
+    pub(super) fn check_room(&self, is_dm: bool, bot_permissions: Permissions) -> Result<(), CheckError> {
+        if self.dm_only && !is_dm {
+            return Err(CheckError::DirectMessageOnly);
+        }
+
+        if self.party_only && is_dm {
+            return Err(CheckError::PartyOnly);
+        }
+
+        if !bot_permissions.is_admin() && !bot_permissions.contains(self.bot_permissions) {
+            return Err(CheckError::BotMissingPermissions(self.bot_permissions - bot_permissions));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the invoker, given whether they own the party, their roles and their permissions in the room
+    pub(super) fn check_member(&self, is_owner: bool, roles: &[RoleId], permissions: Permissions) -> Result<(), CheckError> {
+        if self.owner_only && !is_owner {
+            return Err(CheckError::OwnerOnly);
+        }
+
+        let missing: Vec<_> = self.roles.iter().filter(|role| !roles.contains(role)).copied().collect();
+
+        if !missing.is_empty() {
+            return Err(CheckError::MissingRoles(missing));
+        }
+
+        if !permissions.is_admin() && !permissions.contains(self.permissions) {
+            return Err(CheckError::MissingPermissions(self.permissions - permissions));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks() {
+        let role: RoleId = "1234".parse().unwrap();
+
+        let mut checks = Checks {
+            permissions: Permissions::BAN_MEMBERS,
+            party_only: true,
+            ..Checks::default()
+        };
+
+        checks.merge(&Checks {
+            permissions: Permissions::KICK_MEMBERS,
+            roles: vec![role],
+            ..Checks::default()
+        });
+
+        assert_eq!(checks.check_room(true, Permissions::empty()), Err(CheckError::PartyOnly));
+        assert_eq!(checks.check_room(false, Permissions::empty()), Ok(()));
+
+        assert_eq!(
+            checks.check_member(false, &[], Permissions::all()),
+            Err(CheckError::MissingRoles(vec![role]))
+        );
+        assert_eq!(
+            checks.check_member(false, &[role], Permissions::BAN_MEMBERS),
+            Err(CheckError::MissingPermissions(Permissions::KICK_MEMBERS))
+        );
+        assert_eq!(
+            CheckError::MissingPermissions(Permissions::BAN_MEMBERS | Permissions::KICK_MEMBERS).to_string(),
+            "Missing permissions: KICK_MEMBERS, BAN_MEMBERS"
+        );
+        assert_eq!(checks.check_member(false, &[role], Permissions::ADMINISTRATOR), Ok(()));
+    }
+}
@@ -0,0 +1,467 @@
+use core::any::{Any, TypeId};
+use core::fmt;
+use core::ops::ControlFlow;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use futures::Future;
+use smol_str::SmolStr;
+
+use crate::client::ClientError;
+use crate::framework_utils::{
+    args::ArgumentSplitter,
+    parse::{Arguments, FromArgument},
+};
+use crate::models::{Arc as ModelArc, FxRandomState2, Message, Permissions, RoleId};
+
+use super::super::StandardContext;
+use super::hooks::Hooks;
+use super::{Checks, Cooldown};
+
+/// Boxed command handler, see [`Command::handler`]
+pub type CommandFn<S, E> = Arc<dyn Fn(CommandContext<S>) -> BoxFuture<'static, Result<(), E>> + Send + Sync>;
+
+/// How a declared argument is consumed, see [`CommandArg`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    Required,
+    Optional,
+    /// One or more values
+    Repeated,
+    /// The rest of the message, as written
+    Rest,
+}
+
+/// Argument declared on a [`Command`], documenting its signature for help
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandArg {
+    pub name: SmolStr,
+
+    /// What the argument is expected to be, see [`FromArgument::EXPECTED`]
+    pub expected: &'static str,
+
+    pub kind: ArgKind,
+}
+
+impl fmt::Display for CommandArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let CommandArg { name, expected, kind } = self;
+
+        match kind {
+            ArgKind::Required => write!(f, "<{name}: {expected}>"),
+            ArgKind::Optional => write!(f, "[{name}: {expected}]"),
+            ArgKind::Repeated => write!(f, "<{name}: {expected}...>"),
+            ArgKind::Rest => write!(f, "[{name}...]"),
+        }
+    }
+}
+
+/// A named command with optional aliases and sub-commands
+///
+/// A command without a handler acts as a group, only dispatching to its sub-commands. If a command has
+/// both, the handler is invoked whenever no sub-command matches.
+///
+/// ```rust,ignore
+/// let config = Command::new("config")
+///     .alias("cfg")
+///     .description("Bot configuration")
+///     .subcommand(Command::new("get").handler(|cmd| async move { /* ... */ Ok(()) }))
+///     .subcommand(Command::new("set").handler(|cmd| async move { /* ... */ Ok(()) }));
+/// ```
+#[must_use]
+pub struct Command<S, E> {
+    pub(super) name: SmolStr,
+    pub(super) aliases: Vec<SmolStr>,
+    pub(super) description: Option<SmolStr>,
+    pub(super) usage: Option<SmolStr>,
+    pub(super) args: Vec<CommandArg>,
+    pub(super) checks: Checks,
+    pub(super) cooldown: Option<Cooldown>,
+    pub(super) hooks: Hooks<S, E>,
+    pub(super) handler: Option<CommandFn<S, E>>,
+    pub(super) subcommands: Vec<Command<S, E>>,
+}
+
+impl<S, E> Command<S, E> {
+    pub fn new(name: impl Into<SmolStr>) -> Self {
+        Command {
+            name: name.into(),
+            aliases: Vec::new(),
+            description: None,
+            usage: None,
+            args: Vec::new(),
+            checks: Checks::default(),
+            cooldown: None,
+            hooks: Hooks::default(),
+            handler: None,
+            subcommands: Vec::new(),
+        }
+    }
+
+    /// Adds an alternative name for the command
+    pub fn alias(mut self, alias: impl Into<SmolStr>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    /// Sets a short description of the command
+    pub fn description(mut self, description: impl Into<SmolStr>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Sets how the command is used, shown by help instead of the [signature](Self::signature), e.g. `<user> [reason...]`
+    pub fn usage(mut self, usage: impl Into<SmolStr>) -> Self {
+        self.usage = Some(usage.into());
+        self
+    }
+
+    fn push_arg<'a, T: FromArgument<'a>>(mut self, name: impl Into<SmolStr>, kind: ArgKind) -> Self {
+        self.args.push(CommandArg {
+            name: name.into(),
+            expected: T::EXPECTED,
+            kind,
+        });
+        self
+    }
+
+    /// Declares a required argument, for help. Arguments are parsed by the handler, see [`CommandContext::arguments`].
+    pub fn arg<T: FromArgument<'static>>(self, name: impl Into<SmolStr>) -> Self {
+        self.push_arg::<T>(name, ArgKind::Required)
+    }
+
+    /// Declares an optional argument, for help
+    pub fn optional_arg<T: FromArgument<'static>>(self, name: impl Into<SmolStr>) -> Self {
+        self.push_arg::<T>(name, ArgKind::Optional)
+    }
+
+    /// Declares an argument taking one or more values, for help
+    pub fn repeated_arg<T: FromArgument<'static>>(self, name: impl Into<SmolStr>) -> Self {
+        self.push_arg::<T>(name, ArgKind::Repeated)
+    }
+
+    /// Declares a final argument taking the rest of the message, for help
+    pub fn rest_arg(self, name: impl Into<SmolStr>) -> Self {
+        self.push_arg::<&str>(name, ArgKind::Rest)
+    }
+
+    /// Sets all checks the invocation must pass, see [`Checks`]
+    pub fn checks(mut self, checks: Checks) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// Sets the permissions the invoker needs in the room. Help only lists commands the invoker may run.
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.checks.permissions = permissions;
+        self
+    }
+
+    /// Sets the permissions the bot needs in the room to run the command
+    pub fn bot_permissions(mut self, permissions: Permissions) -> Self {
+        self.checks.bot_permissions = permissions;
+        self
+    }
+
+    /// Adds a role the invoker must have
+    pub fn role(mut self, role: RoleId) -> Self {
+        self.checks.roles.push(role);
+        self
+    }
+
+    /// Only allows the owner of the party to run the command
+    pub fn owner_only(mut self) -> Self {
+        self.checks.owner_only = true;
+        self
+    }
+
+    /// Only allows the command to be run in direct messages
+    pub fn dm_only(mut self) -> Self {
+        self.checks.dm_only = true;
+        self
+    }
+
+    /// Only allows the command to be run within a party
+    pub fn party_only(mut self) -> Self {
+        self.checks.party_only = true;
+        self
+    }
+
+    /// Limits how often the command may be used. Cooldowns apply to this command alone, not its sub-commands.
+    pub fn cooldown(mut self, cooldown: Cooldown) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    /// Sets the handler invoked when the command matches
+    pub fn handler<F, R>(mut self, handler: F) -> Self
+    where
+        F: Fn(CommandContext<S>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        self.handler = Some(Arc::new(move |cmd| Box::pin(handler(cmd))));
+        self
+    }
+
+    /// Adds a hook run before this command or any of its sub-commands, after the router's own hooks.
+    /// Returning [`ControlFlow::Break`] cancels the command, see [`CommandRouter::before`].
+    ///
+    /// [`CommandRouter::before`]: super::CommandRouter::before
+    pub fn before<F, R>(mut self, hook: F) -> Self
+    where
+        F: Fn(CommandContext<S>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<ControlFlow<()>, E>> + Send + 'static,
+    {
+        self.hooks.before(hook);
+        self
+    }
+
+    /// Adds a hook run after this command or any of its sub-commands, before the router's own hooks,
+    /// see [`CommandRouter::after`]
+    ///
+    /// [`CommandRouter::after`]: super::CommandRouter::after
+    pub fn after<F, R>(mut self, hook: F) -> Self
+    where
+        F: Fn(CommandContext<S>, Result<(), E>, Duration) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        self.hooks.after(hook);
+        self
+    }
+
+    /// Adds a hook run when this command or any of its sub-commands fail, before the router's own hooks,
+    /// see [`CommandRouter::on_error`]
+    ///
+    /// [`CommandRouter::on_error`]: super::CommandRouter::on_error
+    pub fn on_error<F, R>(mut self, hook: F) -> Self
+    where
+        F: Fn(CommandContext<S>, E) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        self.hooks.on_error(hook);
+        self
+    }
+
+    /// Adds a sub-command, matched by the next word after this command's name
+    pub fn subcommand(mut self, command: Command<S, E>) -> Self {
+        self.subcommands.push(command);
+        self
+    }
+
+    /// Primary name of the command
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Alternative names of the command
+    #[must_use]
+    pub fn aliases(&self) -> &[SmolStr] {
+        &self.aliases
+    }
+
+    /// Short description of the command, if any
+    #[must_use]
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Custom usage of the command, if any
+    #[must_use]
+    pub fn get_usage(&self) -> Option<&str> {
+        self.usage.as_deref()
+    }
+
+    /// Declared arguments of the command
+    #[must_use]
+    pub fn args(&self) -> &[CommandArg] {
+        &self.args
+    }
+
+    /// Declared arguments formatted as a signature, e.g. `<user: user> [reason...]`
+    #[must_use]
+    pub fn signature(&self) -> String {
+        use fmt::Write;
+
+        let mut signature = String::new();
+
+        for arg in &self.args {
+            if !signature.is_empty() {
+                signature.push(' ');
+            }
+
+            let _ = write!(signature, "{arg}");
+        }
+
+        signature
+    }
+
+    /// Permissions the invoker needs in the room
+    #[must_use]
+    pub fn required_permissions(&self) -> Permissions {
+        self.checks.permissions
+    }
+
+    /// Checks the invocation must pass, not including those of parent groups
+    #[must_use]
+    pub fn get_checks(&self) -> &Checks {
+        &self.checks
+    }
+
+    /// Cooldown of the command, if any
+    #[must_use]
+    pub fn get_cooldown(&self) -> Option<&Cooldown> {
+        self.cooldown.as_ref()
+    }
+
+    /// Sub-commands of this command
+    pub fn subcommands(&self) -> &[Command<S, E>] {
+        &self.subcommands
+    }
+
+    /// Whether the command has a handler, rather than only sub-commands
+    #[must_use]
+    pub fn has_handler(&self) -> bool {
+        self.handler.is_some()
+    }
+
+    /// Whether the given word is this command's name or one of its aliases
+    pub(super) fn is_named(&self, word: &str, case_insensitive: bool) -> bool {
+        let eq = |name: &SmolStr| match case_insensitive {
+            true => eq_ignore_case(name, word),
+            false => name == word,
+        };
+
+        eq(&self.name) || self.aliases.iter().any(eq)
+    }
+}
+
+/// Compares two strings for equality, ignoring case according to Unicode lowercase mappings
+pub(super) fn eq_ignore_case(a: &str, b: &str) -> bool {
+    a.chars().flat_map(char::to_lowercase).eq(b.chars().flat_map(char::to_lowercase))
+}
+
+/// Values stored for a single invocation, keyed by their type, see [`CommandContext::insert`]
+type ExtensionMap = HashMap<TypeId, Box<dyn Any + Send + Sync>, FxRandomState2>;
+
+/// Everything a command handler needs to know about its invocation
+pub struct CommandContext<S> {
+    pub(super) ctx: StandardContext,
+    pub(super) state: Arc<S>,
+    pub(super) msg: ModelArc<Message>,
+    pub(super) prefix: SmolStr,
+    pub(super) command: SmolStr,
+    pub(super) args_start: usize,
+    pub(super) extensions: Arc<Mutex<ExtensionMap>>,
+}
+
+impl<S> CommandContext<S> {
+    /// The framework context, to send messages or use the client
+    #[must_use]
+    pub fn ctx(&self) -> &StandardContext {
+        &self.ctx
+    }
+
+    /// State shared between all commands of the router
+    #[must_use]
+    pub fn state(&self) -> &Arc<S> {
+        &self.state
+    }
+
+    /// The message that invoked the command
+    #[must_use]
+    pub fn message(&self) -> &ModelArc<Message> {
+        &self.msg
+    }
+
+    /// The prefix the command was invoked with, which may be a mention of the bot
+    #[must_use]
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Primary names of the invoked command and its parent groups, separated by spaces, e.g. `config set`
+    #[must_use]
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Everything after the command name, with leading whitespace removed
+    #[must_use]
+    pub fn rest(&self) -> &str {
+        match self.msg.content {
+            Some(ref content) => &content[self.args_start..],
+            None => "",
+        }
+    }
+
+    /// Splits the [rest](Self::rest) of the message into arguments
+    #[must_use]
+    pub fn args(&self) -> ArgumentSplitter<'_> {
+        ArgumentSplitter::split(self.rest())
+    }
+
+    /// Resolves the permissions of the invoker in the room the command was sent in,
+    /// from the party's roles and the room's overwrites. This fetches the party and room.
+    ///
+    /// Permissions do not apply within direct messages, where this is always empty.
+    pub async fn author_permissions(&self) -> Result<Permissions, ClientError> {
+        super::perms::author_permissions(self.ctx.client(), &self.msg).await
+    }
+
+    /// Parses the [rest](Self::rest) of the message into typed arguments
+    ///
+    /// ```rust,ignore
+    /// let mut args = cmd.arguments();
+    ///
+    /// let UserMention(user) = args.next()?;
+    /// let reason = args.rest();
+    /// ```
+    #[must_use]
+    pub fn arguments(&self) -> Arguments<'_> {
+        Arguments::new(self.rest())
+    }
+
+    /// Stores a value for the rest of this invocation, returning any previous value of the same type
+    ///
+    /// Values are shared between the hooks and handler of a single invocation, such as to pass a locale
+    /// resolved in a [before hook](Command::before) on to the handler, which retrieves it with [`get`](Self::get).
+    pub fn insert<T: Send + Sync + 'static>(&self, value: T) -> Option<T> {
+        let previous = self.extensions().insert(TypeId::of::<T>(), Box::new(value))?;
+
+        previous.downcast().ok().map(|value| *value)
+    }
+
+    /// Clones the value of the given type stored for this invocation, see [`insert`](Self::insert)
+    #[must_use]
+    pub fn get<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+        self.extensions().get(&TypeId::of::<T>())?.downcast_ref().cloned()
+    }
+
+    /// Removes the value of the given type stored for this invocation, see [`insert`](Self::insert)
+    pub fn remove<T: Send + Sync + 'static>(&self) -> Option<T> {
+        let value = self.extensions().remove(&TypeId::of::<T>())?;
+
+        value.downcast().ok().map(|value| *value)
+    }
+
+    fn extensions(&self) -> std::sync::MutexGuard<'_, ExtensionMap> {
+        self.extensions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<S> Clone for CommandContext<S> {
+    fn clone(&self) -> Self {
+        CommandContext {
+            ctx: self.ctx.clone(),
+            state: self.state.clone(),
+            msg: self.msg.clone(),
+            prefix: self.prefix.clone(),
+            command: self.command.clone(),
+            args_start: self.args_start,
+            extensions: self.extensions.clone(),
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::api::RateLimit;
+use crate::client::{Client, ClientError};
+use crate::models::{cast_id, FxRandomState2, Message, RoleId, Snowflake, UserId};
+
+use super::super::util::Gcra;
+use super::perms::{is_direct_message, Invoker};
+use super::CommandError;
+
+/// Number of buckets kept before expired buckets are pruned
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// What a [`Cooldown`] bucket is shared between
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CooldownScope {
+    /// Each user has their own bucket
+    User,
+    /// Each room has its own bucket, shared between everyone in it
+    Room,
+    /// Each party has its own bucket, shared between everyone in it
+    Party,
+    /// A single bucket shared between everyone
+    Global,
+}
+
+/// How uses of a command are limited within a [`Cooldown`] bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CooldownKind {
+    /// At most `uses` within `window`, starting from the first use
+    FixedWindow { uses: u32, window: Duration },
+    /// Generic Cell Rate Algorithm, as used for [`RateLimit`]s elsewhere
+    Gcra(RateLimit),
+}
+
+/// Command was used too often, see [`Cooldown`]
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Command is on cooldown, try again in {}s", remaining_secs(.remaining))]
+pub struct CooldownError {
+    /// Scope of the exhausted bucket
+    pub scope: CooldownScope,
+
+    /// How long until the command may be used again
+    pub remaining: Duration,
+}
+
+/// Rounds up to whole seconds, so "try again in 0s" is never shown
+fn remaining_secs(remaining: &Duration) -> u64 {
+    remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)
+}
+
+/// Cooldown of a single command, with a bucket per [`CooldownScope`], see [`Command::cooldown`]
+///
+/// ```rust,ignore
+/// // 3 uses every 10 seconds per user
+/// Command::new("roll").cooldown(Cooldown::fixed(CooldownScope::User, 3, Duration::from_secs(10)))
+/// ```
+///
+/// [`Command::cooldown`]: super::Command::cooldown
+#[derive(Debug)]
+pub struct Cooldown {
+    scope: CooldownScope,
+    kind: CooldownKind,
+    bypass_roles: Vec<RoleId>,
+    buckets: Mutex<HashMap<Option<Snowflake>, Bucket, FxRandomState2>>,
+    /// Whether authors whose roles had to be fetched may bypass the cooldown, and until when that's kept
+    bypassing: Mutex<HashMap<UserId, (bool, Instant), FxRandomState2>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Bucket {
+    Window { start: Instant, uses: u32 },
+    Gcra(Gcra),
+}
+
+impl Cooldown {
+    #[must_use]
+    pub fn new(scope: CooldownScope, kind: CooldownKind) -> Self {
+        Cooldown {
+            scope,
+            kind,
+            bypass_roles: Vec::new(),
+            buckets: Mutex::default(),
+            bypassing: Mutex::default(),
+        }
+    }
+
+    /// Allows `uses` uses within each `window`, starting from the first use
+    #[must_use]
+    pub fn fixed(scope: CooldownScope, uses: u32, window: Duration) -> Self {
+        Self::new(scope, CooldownKind::FixedWindow { uses, window })
+    }
+
+    /// Limits uses using the same model as [`RateLimit`], allowing bursts
+    #[must_use]
+    pub fn gcra(scope: CooldownScope, limit: RateLimit) -> Self {
+        Self::new(scope, CooldownKind::Gcra(limit))
+    }
+
+    /// Adds a role whose members are not subject to the cooldown
+    #[must_use]
+    pub fn bypass_role(mut self, role: RoleId) -> Self {
+        self.bypass_roles.push(role);
+        self
+    }
+
+    #[must_use]
+    pub fn scope(&self) -> CooldownScope {
+        self.scope
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> CooldownKind {
+        self.kind
+    }
+
+    /// Consumes a use for the message's author, unless they may bypass the cooldown.
+    ///
+    /// Bypass roles are checked against the roles included with the message, or those already
+    /// fetched for the command's checks. Otherwise, the member is only fetched if the cooldown
+    /// would be hit, and the outcome is kept until the cooldown ends, so repeated uses don't fetch
+    /// it again. There are no roles in direct messages, so nothing can bypass the cooldown there.
+    pub(super) async fn check<E>(&self, client: &Client, msg: &Message, invoker: Option<&Invoker>) -> Result<(), E>
+    where
+        E: From<ClientError> + From<CommandError>,
+    {
+        if self.bypassed(&msg.author.roles) {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+
+        let Err(remaining) = self.check_at(self.key(msg), now) else {
+            return Ok(());
+        };
+
+        if !self.bypass_roles.is_empty() && msg.author.roles.is_empty() {
+            let bypassed = match invoker {
+                Some(invoker) if invoker.is_dm => false,
+                Some(Invoker { roles: Some(roles), .. }) => self.bypassed(roles),
+                _ => self.fetch_bypassed(client, msg, now, now + remaining).await?,
+            };
+
+            if bypassed {
+                return Ok(());
+            }
+        }
+
+        Err(CommandError::Cooldown(CooldownError {
+            scope: self.scope,
+            remaining,
+        })
+        .into())
+    }
+
+    fn bypassed(&self, roles: &[RoleId]) -> bool {
+        roles.iter().any(|role| self.bypass_roles.contains(role))
+    }
+
+    /// Fetches whether the author may bypass the cooldown, unless it's already known at `now`,
+    /// keeping the outcome until `until`
+    async fn fetch_bypassed(&self, client: &Client, msg: &Message, now: Instant, until: Instant) -> Result<bool, ClientError> {
+        let user_id = msg.author.user.id;
+
+        if let Some(&(bypassed, _)) = self.bypassing().get(&user_id).filter(|(_, until)| now < *until) {
+            return Ok(bypassed);
+        }
+
+        let bypassed = match is_direct_message(client, msg).await? {
+            true => false,
+            false => self.bypassed(&client.member(msg.party_id, user_id).fetch().await?.roles),
+        };
+
+        let mut bypassing = self.bypassing();
+
+        if bypassing.len() >= PRUNE_THRESHOLD {
+            bypassing.retain(|_, (_, until)| now < *until);
+        }
+
+        bypassing.insert(user_id, (bypassed, until));
+
+        Ok(bypassed)
+    }
+
+    fn bypassing(&self) -> std::sync::MutexGuard<'_, HashMap<UserId, (bool, Instant), FxRandomState2>> {
+        self.bypassing.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn key(&self, msg: &Message) -> Option<Snowflake> {
+        match self.scope {
+            CooldownScope::User => Some(cast_id(msg.author.user.id)),
+            CooldownScope::Room => Some(cast_id(msg.room_id)),
+            CooldownScope::Party => Some(cast_id(msg.party_id)),
+            CooldownScope::Global => None,
+        }
+    }
+
+    /// Consumes a use of the bucket at `now`, otherwise returns how long until the next use
+    fn check_at(&self, key: Option<Snowflake>, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| !bucket.is_expired(&self.kind, now));
+        }
+
+        let bucket = buckets.entry(key).or_insert_with(|| match self.kind {
+            CooldownKind::FixedWindow { .. } => Bucket::Window { start: now, uses: 0 },
+            CooldownKind::Gcra(_) => Bucket::Gcra(Gcra::default()),
+        });
+
+        match (bucket, &self.kind) {
+            (Bucket::Window { start, uses }, &CooldownKind::FixedWindow { uses: max, window }) => {
+                if now.duration_since(*start) >= window {
+                    *start = now;
+                    *uses = 0;
+                }
+
+                if *uses >= max {
+                    return Err(window - now.duration_since(*start));
+                }
+
+                *uses += 1;
+
+                Ok(())
+            }
+            (Bucket::Gcra(gcra), CooldownKind::Gcra(limit)) => gcra.check(limit, now),
+            _ => unreachable!("bucket does not match cooldown kind"),
+        }
+    }
+}
+
+impl Bucket {
+    fn is_expired(&self, kind: &CooldownKind, now: Instant) -> bool {
+        match (self, kind) {
+            (Bucket::Window { start, .. }, CooldownKind::FixedWindow { window, .. }) => now.duration_since(*start) >= *window,
+            (Bucket::Gcra(gcra), _) => gcra.is_idle(now),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_window() {
+        let cooldown = Cooldown::fixed(CooldownScope::User, 2, Duration::from_secs(10));
+        let (a, b) = (Some("1234".parse().unwrap()), None);
+        let now = Instant::now();
+
+        assert!(cooldown.check_at(a, now).is_ok());
+        assert!(cooldown.check_at(a, now + Duration::from_secs(1)).is_ok());
+        assert_eq!(
+            cooldown.check_at(a, now + Duration::from_secs(3)),
+            Err(Duration::from_secs(7))
+        );
+
+        // buckets are independent
+        assert!(cooldown.check_at(b, now + Duration::from_secs(3)).is_ok());
+
+        // window resets after it elapses
+        assert!(cooldown.check_at(a, now + Duration::from_secs(10)).is_ok());
+    }
+
+    #[test]
+    fn test_gcra() {
+        let limit = RateLimit {
+            emission_interval: Duration::from_secs(5),
+            burst_size: core::num::NonZeroU64::new(2).unwrap(),
+        };
+
+        let cooldown = Cooldown::gcra(CooldownScope::Global, limit);
+        let now = Instant::now();
+
+        // bursts, then allows a use every interval
+        assert!(cooldown.check_at(None, now).is_ok());
+        assert!(cooldown.check_at(None, now).is_ok());
+        assert_eq!(
+            cooldown.check_at(None, now + Duration::from_secs(1)),
+            Err(Duration::from_secs(4))
+        );
+        assert!(cooldown.check_at(None, now + Duration::from_secs(5)).is_ok());
+        assert!(cooldown.check_at(None, now + Duration::from_secs(6)).is_err());
+    }
+
+    #[test]
+    fn test_bypass_role() {
+        let (role, other): (RoleId, RoleId) = ("1".parse().unwrap(), "2".parse().unwrap());
+
+        let cooldown = Cooldown::fixed(CooldownScope::User, 1, Duration::from_secs(10)).bypass_role(role);
+
+        assert!(cooldown.bypassed(&[other, role]));
+        assert!(!cooldown.bypassed(&[other]));
+        assert!(!cooldown.bypassed(&[]));
+    }
+
+    #[tokio::test]
+    async fn test_bypass_without_fetching() {
+        use crate::framework::standard::StandardError;
+
+        let role: RoleId = "5".parse().unwrap();
+        let cooldown = Cooldown::fixed(CooldownScope::User, 1, Duration::from_secs(60)).bypass_role(role);
+
+        // nothing is listening, so fetching anything fails with a client error
+        let client = Client::new("http://localhost:1").unwrap();
+
+        let msg: Message = serde_json::from_value(serde_json::json!({
+            "id": "3",
+            "room_id": "2",
+            "party_id": "1",
+            "author": {
+                "user": { "id": "4", "username": "someone", "discriminator": 1, "flags": 0 },
+                "joined_at": null,
+            },
+        }))
+        .unwrap();
+
+        let on_cooldown = |res| matches!(res, Err(StandardError::CommandError(CommandError::Cooldown(_))));
+
+        assert!(cooldown.check::<StandardError>(&client, &msg, None).await.is_ok());
+
+        // roles fetched for checks are reused
+        let mut invoker = Invoker {
+            roles: Some(vec![role]),
+            ..Invoker::unknown()
+        };
+
+        assert!(cooldown.check::<StandardError>(&client, &msg, Some(&invoker)).await.is_ok());
+
+        invoker.roles = Some(Vec::new());
+        assert!(on_cooldown(cooldown.check(&client, &msg, Some(&invoker)).await));
+
+        // otherwise, the outcome of fetching the member is kept until the cooldown ends
+        let until = Instant::now() + Duration::from_secs(60);
+        cooldown.bypassing().insert(msg.author.user.id, (false, until));
+
+        assert!(on_cooldown(cooldown.check(&client, &msg, None).await));
+    }
+
+    #[test]
+    fn test_prune_buckets() {
+        let cooldown = Cooldown::fixed(CooldownScope::User, 1, Duration::from_secs(10));
+        let now = Instant::now();
+
+        for id in 1..=PRUNE_THRESHOLD as u64 {
+            assert!(cooldown.check_at(Some(id.to_string().parse().unwrap()), now).is_ok());
+        }
+
+        let recent = Some("1".parse().unwrap());
+        assert!(cooldown.check_at(recent, now + Duration::from_secs(5)).is_err());
+
+        // buckets are only pruned once the threshold is reached, and only if expired
+        assert_eq!(cooldown.buckets.lock().unwrap().len(), PRUNE_THRESHOLD);
+        assert!(cooldown.check_at(None, now + Duration::from_secs(10)).is_ok());
+        assert_eq!(cooldown.buckets.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_remaining_secs() {
+        assert_eq!(remaining_secs(&Duration::from_millis(2001)), 3);
+        assert_eq!(remaining_secs(&Duration::from_secs(3)), 3);
+    }
+}
@@ -0,0 +1,12 @@
+use super::check::CheckError;
+use super::cooldown::CooldownError;
+
+/// Reasons a matched command was not run, passed to the error callback through the user's error type
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum CommandError {
+    #[error(transparent)]
+    Check(#[from] CheckError),
+
+    #[error(transparent)]
+    Cooldown(#[from] CooldownError),
+}
@@ -0,0 +1,402 @@
+use core::fmt::Write;
+
+use smol_str::SmolStr;
+use thin_vec::ThinVec;
+
+use crate::client::MessageBuilder;
+use crate::framework::md;
+use crate::models::{EmbedField, EmbedFooter, EmbedV1};
+
+use super::check::{permission_names, Checks};
+use super::command::{ArgKind, Command};
+use super::perms::Invoker;
+
+/// Default number of commands listed per page of help
+pub const DEFAULT_HELP_PER_PAGE: usize = 10;
+
+/// How help is formatted, see [`HelpCommand::format`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HelpFormat {
+    /// Lantern markdown in the message content
+    #[default]
+    Markdown,
+    /// An embed with a field per command or detail
+    Embed,
+}
+
+/// Built-in help command, generated from the registered commands, see [`CommandRouter::help`]
+///
+/// `help` lists a page of commands, `help 2` lists the second page, and `help config set`
+/// shows the details of a command. Only commands whose [checks] the invoker passes, including those
+/// of parent groups, are shown.
+///
+/// A registered command of the same name takes precedence over the built-in help.
+///
+/// [`CommandRouter::help`]: super::CommandRouter::help
+/// [checks]: Command::checks
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct HelpCommand {
+    pub(super) names: Vec<SmolStr>,
+    per_page: usize,
+    format: HelpFormat,
+    title: SmolStr,
+}
+
+impl Default for HelpCommand {
+    fn default() -> Self {
+        HelpCommand {
+            names: vec![SmolStr::new_static("help")],
+            per_page: DEFAULT_HELP_PER_PAGE,
+            format: HelpFormat::default(),
+            title: SmolStr::new_static("Commands"),
+        }
+    }
+}
+
+/// Formatting-agnostic help output
+struct HelpPage {
+    title: String,
+    description: Option<String>,
+    fields: Vec<(String, String)>,
+    footer: Option<String>,
+}
+
+impl HelpCommand {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an alternative name for the help command
+    pub fn alias(mut self, alias: impl Into<SmolStr>) -> Self {
+        self.names.push(alias.into());
+        self
+    }
+
+    /// Sets how many commands are listed per page, defaulting to [`DEFAULT_HELP_PER_PAGE`]
+    pub fn per_page(mut self, per_page: usize) -> Self {
+        self.per_page = per_page.max(1);
+        self
+    }
+
+    /// Sets how help is formatted, defaulting to [`HelpFormat::Markdown`]
+    pub fn format(mut self, format: HelpFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the title of the command list, defaulting to `Commands`
+    pub fn title(mut self, title: impl Into<SmolStr>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Renders help for the given arguments, which are either empty, a page number or a command path
+    pub(super) fn render<S, E>(
+        &self,
+        commands: &[Command<S, E>],
+        invoker: &Invoker,
+        prefix: &str,
+        args: &str,
+        case_insensitive: bool,
+    ) -> MessageBuilder {
+        let page = match args.parse::<usize>() {
+            _ if args.is_empty() => self.overview(commands, invoker, prefix, 1),
+            Ok(page) => self.overview(commands, invoker, prefix, page),
+            Err(_) => match find(commands, invoker, args, case_insensitive) {
+                Some((path, command, checks)) => self.detail(command, invoker, &checks, &format!("{prefix}{path}")),
+                None => HelpPage {
+                    title: self.title.to_string(),
+                    description: Some(format!("No command named **{}**", md::escape(args))),
+                    fields: Vec::new(),
+                    footer: None,
+                },
+            },
+        };
+
+        match self.format {
+            HelpFormat::Markdown => MessageBuilder::new().text(&page.to_markdown()),
+            HelpFormat::Embed => MessageBuilder::new().embed(page.into_embed()),
+        }
+    }
+
+    fn overview<S, E>(&self, commands: &[Command<S, E>], invoker: &Invoker, prefix: &str, page: usize) -> HelpPage {
+        let visible: Vec<_> = commands.iter().filter(|c| allowed(c, invoker, &Checks::default())).collect();
+
+        let pages = visible.len().div_ceil(self.per_page).max(1);
+        let page = page.clamp(1, pages);
+
+        let fields = visible
+            .iter()
+            .skip((page - 1) * self.per_page)
+            .take(self.per_page)
+            .map(|command| {
+                let mut value = command.get_description().unwrap_or("No description").to_owned();
+
+                let subcommands = subcommand_list(command, invoker, command.get_checks());
+
+                if !subcommands.is_empty() {
+                    let _ = write!(value, " ({subcommands})");
+                }
+
+                (format!("`{prefix}{}`", command.name()), value)
+            })
+            .collect();
+
+        let help = &self.names[0];
+
+        let mut footer = format!("Use `{prefix}{help} <command>` for details");
+
+        if pages > 1 {
+            let _ = write!(footer, ", or `{prefix}{help} <page>` for more. Page {page}/{pages}");
+        }
+
+        HelpPage {
+            title: self.title.to_string(),
+            description: None,
+            fields,
+            footer: Some(footer),
+        }
+    }
+
+    /// Details of a command, given the checks of it and its parent groups
+    fn detail<S, E>(&self, command: &Command<S, E>, invoker: &Invoker, checks: &Checks, invocation: &str) -> HelpPage {
+        let mut fields = Vec::new();
+
+        if !command.aliases().is_empty() {
+            let aliases = command.aliases().iter().map(|a| format!("`{a}`")).collect::<Vec<_>>().join(", ");
+
+            fields.push(("Aliases".to_owned(), aliases));
+        }
+
+        if command.has_handler() {
+            let usage = match command.get_usage() {
+                Some(usage) => usage.to_owned(),
+                None => command.signature(),
+            };
+
+            let usage = format!("{invocation} {usage}");
+
+            fields.push(("Usage".to_owned(), format!("`{}`", usage.trim_end())));
+        }
+
+        if !command.args().is_empty() {
+            let mut args = String::new();
+
+            for arg in command.args() {
+                if !args.is_empty() {
+                    args.push('\n');
+                }
+
+                let _ = write!(args, "`{}`: {}", arg.name, arg.expected);
+
+                let _ = match arg.kind {
+                    ArgKind::Required => Ok(()),
+                    ArgKind::Optional => args.write_str(" (optional)"),
+                    ArgKind::Repeated => args.write_str(" (one or more)"),
+                    ArgKind::Rest => args.write_str(" (rest of message)"),
+                };
+            }
+
+            fields.push(("Arguments".to_owned(), args));
+        }
+
+        if !command.required_permissions().is_empty() {
+            fields.push((
+                "Required permissions".to_owned(),
+                permission_names(&command.required_permissions()),
+            ));
+        }
+
+        let subcommands = subcommand_list(command, invoker, checks);
+
+        if !subcommands.is_empty() {
+            fields.push(("Sub-commands".to_owned(), subcommands));
+        }
+
+        HelpPage {
+            title: invocation.to_owned(),
+            description: command.get_description().map(ToOwned::to_owned),
+            fields,
+            footer: None,
+        }
+    }
+}
+
+impl HelpPage {
+    fn to_markdown(&self) -> String {
+        let mut out = format!("**{}**", md::escape(&self.title));
+
+        if let Some(ref description) = self.description {
+            let _ = write!(out, "\n{description}");
+        }
+
+        for (name, value) in &self.fields {
+            // multi-line values start on their own line
+            let sep = if value.contains('\n') { ":\n" } else { ": " };
+
+            let _ = write!(out, "\n**{name}**{sep}{value}");
+        }
+
+        if let Some(ref footer) = self.footer {
+            let _ = write!(out, "\n\n{footer}");
+        }
+
+        out
+    }
+
+    fn into_embed(self) -> EmbedV1 {
+        EmbedV1 {
+            title: Some(self.title.into()),
+            description: self.description.map(Into::into),
+            fields: self
+                .fields
+                .into_iter()
+                .map(|(name, value)| EmbedField {
+                    name: name.into(),
+                    value: value.into(),
+                    ..EmbedField::default()
+                })
+                .collect::<ThinVec<_>>(),
+            footer: self.footer.map(|text| EmbedFooter {
+                text: text.into(),
+                icon: None,
+            }),
+            ..EmbedV1::default()
+        }
+    }
+}
+
+/// Whether the invoker passes the checks of the command, merged with those of its parent groups
+fn allowed<S, E>(command: &Command<S, E>, invoker: &Invoker, parents: &Checks) -> bool {
+    let mut checks = parents.clone();
+    checks.merge(command.get_checks());
+
+    invoker.check(&checks).is_ok()
+}
+
+/// Lists the sub-commands the invoker may run, e.g. `` `get`, `set` ``, given the checks of the command
+/// and its parent groups
+fn subcommand_list<S, E>(command: &Command<S, E>, invoker: &Invoker, checks: &Checks) -> String {
+    let names: Vec<_> =
+        command.subcommands().iter().filter(|c| allowed(c, invoker, checks)).map(|c| format!("`{}`", c.name())).collect();
+
+    names.join(", ")
+}
+
+/// Finds a visible command by its path of names or aliases, returning its full primary path
+/// and the checks of it and its parent groups
+fn find<'a, S, E>(
+    commands: &'a [Command<S, E>],
+    invoker: &Invoker,
+    path: &str,
+    case_insensitive: bool,
+) -> Option<(String, &'a Command<S, E>, Checks)> {
+    let mut commands = commands;
+    let mut found = None;
+    let mut full_path = String::new();
+    let mut checks = Checks::default();
+
+    for word in path.split_whitespace() {
+        let command = commands.iter().find(|c| allowed(c, invoker, &checks) && c.is_named(word, case_insensitive))?;
+
+        if !full_path.is_empty() {
+            full_path.push(' ');
+        }
+
+        full_path.push_str(command.name());
+        checks.merge(command.get_checks());
+
+        found = Some(command);
+        commands = command.subcommands();
+    }
+
+    found.map(|command| (full_path, command, checks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework_utils::parse::UserMention;
+    use crate::models::Permissions;
+
+    fn member(permissions: Permissions) -> Invoker {
+        Invoker {
+            permissions,
+            ..Invoker::unknown()
+        }
+    }
+
+    fn commands() -> Vec<Command<(), ()>> {
+        vec![
+            Command::new("ping").description("Checks latency").handler(|_| async { Ok(()) }),
+            Command::new("ban")
+                .alias("b")
+                .description("Bans a user")
+                .arg::<UserMention>("user")
+                .rest_arg("reason")
+                .permissions(Permissions::BAN_MEMBERS)
+                .handler(|_| async { Ok(()) }),
+            Command::new("config")
+                .party_only()
+                .subcommand(Command::new("get").handler(|_| async { Ok(()) }))
+                .subcommand(Command::new("set").permissions(Permissions::MANAGE_PARTY).handler(|_| async { Ok(()) })),
+        ]
+    }
+
+    #[test]
+    fn test_help_overview() {
+        let help = HelpCommand::new().per_page(2);
+        let commands = commands();
+
+        let page = help.overview(&commands, &member(Permissions::empty()), "!", 1);
+        assert_eq!(
+            page.to_markdown(),
+            "**Commands**\n**`!ping`**: Checks latency\n**`!config`**: No description (`get`)\n\nUse `!help <command>` for details"
+        );
+
+        let page = help.overview(&commands, &member(Permissions::BAN_MEMBERS), "!", 5);
+        let footer = page.footer.as_deref().unwrap();
+        assert_eq!(page.fields[0].0, "`!config`");
+        assert!(footer.ends_with("Page 2/2"), "{footer}");
+
+        // location checks apply as well
+        let dm = Invoker {
+            is_dm: true,
+            ..member(Permissions::all())
+        };
+
+        let page = help.overview(&commands, &dm, "!", 1);
+        assert!(page.fields.iter().all(|(name, _)| name != "`!config`"));
+    }
+
+    #[test]
+    fn test_help_detail() {
+        let help = HelpCommand::new();
+        let commands = commands();
+
+        let admin = member(Permissions::ADMINISTRATOR);
+
+        assert!(find(&commands, &member(Permissions::empty()), "ban", true).is_none());
+        assert!(find(&commands, &member(Permissions::empty()), "config set", true).is_none());
+
+        // checks of parent groups apply to their sub-commands
+        let dm = Invoker {
+            is_dm: true,
+            ..admin.clone()
+        };
+        assert!(find(&commands, &dm, "config get", true).is_none());
+
+        let (_, _, checks) = find(&commands, &admin, "config set", true).unwrap();
+        assert!(checks.party_only && checks.permissions == Permissions::MANAGE_PARTY);
+
+        let (path, ban, checks) = find(&commands, &admin, "B", true).unwrap();
+        assert_eq!(path, "ban");
+
+        assert_eq!(
+            help.detail(ban, &admin, &checks, "!ban").to_markdown(),
+            "**!ban**\nBans a user\n**Aliases**: `b`\n**Usage**: `!ban <user: user> [reason...]`\n\
+             **Arguments**:\n`user`: user\n`reason`: text (rest of message)\n**Required permissions**: BAN_MEMBERS"
+        );
+    }
+}
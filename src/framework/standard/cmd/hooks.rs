@@ -0,0 +1,61 @@
+use core::ops::ControlFlow;
+use core::time::Duration;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use futures::Future;
+
+use super::CommandContext;
+
+/// Boxed hook run before a command, see [`Command::before`](super::Command::before)
+pub type BeforeFn<S, E> = Arc<dyn Fn(CommandContext<S>) -> BoxFuture<'static, Result<ControlFlow<()>, E>> + Send + Sync>;
+
+/// Boxed hook run after a command, see [`Command::after`](super::Command::after)
+pub type AfterFn<S, E> =
+    Arc<dyn Fn(CommandContext<S>, Result<(), E>, Duration) -> BoxFuture<'static, Result<(), E>> + Send + Sync>;
+
+/// Boxed hook run when a command fails, see [`Command::on_error`](super::Command::on_error)
+pub type ErrorFn<S, E> = Arc<dyn Fn(CommandContext<S>, E) -> BoxFuture<'static, Result<(), E>> + Send + Sync>;
+
+/// Middleware registered on a [`CommandRouter`](super::CommandRouter) or a [`Command`](super::Command)
+pub(super) struct Hooks<S, E> {
+    pub before: Vec<BeforeFn<S, E>>,
+    pub after: Vec<AfterFn<S, E>>,
+    pub on_error: Vec<ErrorFn<S, E>>,
+}
+
+impl<S, E> Default for Hooks<S, E> {
+    fn default() -> Self {
+        Hooks {
+            before: Vec::new(),
+            after: Vec::new(),
+            on_error: Vec::new(),
+        }
+    }
+}
+
+impl<S, E> Hooks<S, E> {
+    pub fn before<F, R>(&mut self, hook: F)
+    where
+        F: Fn(CommandContext<S>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<ControlFlow<()>, E>> + Send + 'static,
+    {
+        self.before.push(Arc::new(move |cmd| Box::pin(hook(cmd))));
+    }
+
+    pub fn after<F, R>(&mut self, hook: F)
+    where
+        F: Fn(CommandContext<S>, Result<(), E>, Duration) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        self.after.push(Arc::new(move |cmd, result, elapsed| Box::pin(hook(cmd, result, elapsed))));
+    }
+
+    pub fn on_error<F, R>(&mut self, hook: F)
+    where
+        F: Fn(CommandContext<S>, E) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        self.on_error.push(Arc::new(move |cmd, err| Box::pin(hook(cmd, err))));
+    }
+}
@@ -1 +1,26 @@
+//! Prefix commands, routed from [`MessageCreate`](crate::models::gateway::message::ServerMsg::MessageCreate) events
+//!
+//! Commands are registered on a [`CommandRouter`], which is then combined with any other event handlers
+//! through [`CommandHandlers`]. Arguments are split with [`ArgumentSplitter`](crate::framework_utils::args::ArgumentSplitter).
+//! A [`HelpCommand`] may be enabled to list commands and their declared arguments. Commands may declare
+//! [`Checks`] and a [`Cooldown`], which fail with a [`CommandError`] before the command is run.
+//!
+//! Hooks run before and after commands, or when they fail, and may be added to the router for all commands
+//! or to a command for it and its sub-commands, such as with [`CommandRouter::before`] and [`Command::before`].
 
+mod check;
+mod command;
+mod cooldown;
+mod error;
+mod help;
+mod hooks;
+mod perms;
+mod router;
+
+pub use check::{CheckError, Checks};
+pub use command::{ArgKind, Command, CommandArg, CommandContext, CommandFn};
+pub use cooldown::{Cooldown, CooldownError, CooldownKind, CooldownScope};
+pub use error::CommandError;
+pub use help::{HelpCommand, HelpFormat, DEFAULT_HELP_PER_PAGE};
+pub use hooks::{AfterFn, BeforeFn, ErrorFn};
+pub use router::{CommandHandlers, CommandRouter};
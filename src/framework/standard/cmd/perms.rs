@@ -0,0 +1,114 @@
+use crate::client::{Client, ClientError};
+use crate::models::{resolve_permissions, Message, Party, PartyMember, Permissions, RoleId, RoomKind};
+
+use super::{CheckError, Checks, CommandError};
+
+/// Resolves the permissions of a message's author within its room, see [`CommandContext::author_permissions`]
+///
+/// [`CommandContext::author_permissions`]: super::CommandContext::author_permissions
+pub(super) async fn author_permissions(client: &Client, msg: &Message) -> Result<Permissions, ClientError> {
+    let room = client.room(msg.room_id).fetch().await?.room;
+
+    if room.flags.kind() == RoomKind::DirectMessage {
+        return Ok(Permissions::empty());
+    }
+
+    let (party, member) = party_member(client, msg).await?;
+
+    Ok(resolve_permissions(&party, &room, &member))
+}
+
+/// Whether the message was sent in a direct message, rather than within a party
+pub(super) async fn is_direct_message(client: &Client, msg: &Message) -> Result<bool, ClientError> {
+    Ok(client.room(msg.room_id).fetch().await?.room.flags.kind() == RoomKind::DirectMessage)
+}
+
+/// Fetches the party a message was sent in, along with its author as a full member
+async fn party_member(client: &Client, msg: &Message) -> Result<(Party, PartyMember), ClientError> {
+    let party = client.party(msg.party_id);
+
+    // roles may be excluded from the message author, so fetch the full member if needed
+    Ok(match msg.author.roles.is_empty() {
+        true => futures::try_join!(party.fetch(), client.member(msg.party_id, msg.author.user.id).fetch())?,
+        false => (party.fetch().await?, msg.author.clone()),
+    })
+}
+
+/// What is known about the author of a message and the room it was sent in, to evaluate [`Checks`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Invoker {
+    pub is_dm: bool,
+    pub bot_permissions: Permissions,
+    pub is_owner: bool,
+    /// Roles of the author, if their membership of the party was fetched
+    pub roles: Option<Vec<RoleId>>,
+    pub permissions: Permissions,
+}
+
+impl Invoker {
+    /// Nothing is known, which only passes empty checks
+    pub fn unknown() -> Self {
+        Invoker {
+            is_dm: false,
+            bot_permissions: Permissions::empty(),
+            is_owner: false,
+            roles: None,
+            permissions: Permissions::empty(),
+        }
+    }
+
+    /// Fetches the room of the message, and with `needs_member`, the author's membership of the party
+    pub async fn fetch(client: &Client, msg: &Message, needs_member: bool) -> Result<Self, ClientError> {
+        let full_room = client.room(msg.room_id).fetch().await?;
+
+        let mut invoker = Invoker {
+            is_dm: full_room.room.flags.kind() == RoomKind::DirectMessage,
+            bot_permissions: full_room.perms,
+            ..Invoker::unknown()
+        };
+
+        // there are no roles, owners or permissions outside of parties
+        if needs_member && !invoker.is_dm {
+            let (party, member) = party_member(client, msg).await?;
+
+            invoker.permissions = resolve_permissions(&party, &full_room.room, &member);
+            invoker.is_owner = party.owner == member.user.id;
+            invoker.roles = Some(member.roles.to_vec());
+        }
+
+        Ok(invoker)
+    }
+
+    /// Evaluates the checks, which must not [need the member](Checks::needs_member) unless it was fetched
+    pub fn check(&self, checks: &Checks) -> Result<(), CheckError> {
+        checks.check_room(self.is_dm, self.bot_permissions)?;
+
+        match checks.needs_member() {
+            true => checks.check_member(self.is_owner, self.roles.as_deref().unwrap_or_default(), self.permissions),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Evaluates the [`Checks`] of a command against the message that invoked it,
+/// fetching only what the checks need.
+///
+/// Returns what was fetched, if anything, so it can be reused for the same invocation.
+pub(super) async fn check<E>(client: &Client, msg: &Message, checks: &Checks) -> Result<Option<Invoker>, E>
+where
+    E: From<ClientError> + From<CommandError>,
+{
+    if checks.is_empty() {
+        return Ok(None);
+    }
+
+    let invoker = Invoker::fetch(client, msg, checks.needs_member()).await?;
+
+    invoker.check(checks).map_err(fail::<E>)?;
+
+    Ok(Some(invoker))
+}
+
+fn fail<E: From<CommandError>>(err: CheckError) -> E {
+    CommandError::Check(err).into()
+}
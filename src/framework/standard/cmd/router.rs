@@ -0,0 +1,752 @@
+use core::ops::ControlFlow;
+use core::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures::Future;
+
+use smol_str::SmolStr;
+
+use crate::client::ClientError;
+use crate::models::events::Ready;
+use crate::models::gateway::message::{ServerMsg, ServerMsgHandlers, ServerMsgOpcode};
+use crate::models::{Arc as ModelArc, ElevationLevel, Message, UserId};
+
+use super::super::StandardContext;
+use super::check::Checks;
+use super::command::{Command, CommandContext, CommandFn};
+use super::error::CommandError;
+use super::help::HelpCommand;
+use super::hooks::Hooks;
+use super::perms::Invoker;
+
+/// Routes messages starting with a prefix or a mention of the bot to registered [`Command`]s
+///
+/// ```rust,ignore
+/// let mut router = CommandRouter::new();
+///
+/// router
+///     .prefix("!")
+///     .mention(true)
+///     .command(Command::new("ping").handler(|cmd| async move {
+///         // ...
+///         Ok(())
+///     }));
+///
+/// let bot = Standard::new_with_handlers(client, CommandHandlers::new(handlers, router));
+/// ```
+pub struct CommandRouter<S, E> {
+    state: Arc<S>,
+    prefixes: Vec<SmolStr>,
+    mention: bool,
+    case_insensitive: bool,
+    ignore_bots: bool,
+    ignore_self: bool,
+    commands: Vec<Command<S, E>>,
+    help: Option<HelpCommand>,
+    hooks: Hooks<S, E>,
+    self_id: Mutex<Option<UserId>>,
+}
+
+/// A matched command invocation, before the handler is run
+pub(super) struct CommandMatch<'a, S, E> {
+    pub command: &'a Command<S, E>,
+    /// Parent groups of the command, outermost first
+    pub parents: Vec<&'a Command<S, E>>,
+    pub prefix: SmolStr,
+    pub path: SmolStr,
+    pub args_start: usize,
+}
+
+impl<E> CommandRouter<(), E> {
+    pub fn new() -> Self {
+        Self::new_with_state(())
+    }
+}
+
+impl<E> Default for CommandRouter<(), E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S, E> CommandRouter<S, E> {
+    /// Creates a router with state shared between all commands, see [`CommandContext::state`]
+    ///
+    /// By default, the router has no prefixes, matches mentions of the bot, matches commands
+    /// case-insensitively, and ignores messages from bots and itself.
+    pub fn new_with_state(state: impl Into<Arc<S>>) -> Self {
+        CommandRouter {
+            state: state.into(),
+            prefixes: Vec::new(),
+            mention: true,
+            case_insensitive: true,
+            ignore_bots: true,
+            ignore_self: true,
+            commands: Vec::new(),
+            help: None,
+            hooks: Hooks::default(),
+            self_id: Mutex::new(None),
+        }
+    }
+
+    /// Adds a prefix that commands may be invoked with. The longest matching prefix is used.
+    pub fn prefix(&mut self, prefix: impl Into<SmolStr>) -> &mut Self {
+        self.prefixes.push(prefix.into());
+        self
+    }
+
+    /// Whether a mention of the bot, such as `<@id> ping`, may be used as a prefix. Defaults to `true`.
+    ///
+    /// The bot's own ID is learned from the [`Ready`] event, or may be [set manually](Self::set_self_id).
+    pub fn mention(&mut self, mention: bool) -> &mut Self {
+        self.mention = mention;
+        self
+    }
+
+    /// Whether prefixes and command names are matched regardless of case. Defaults to `true`.
+    pub fn case_insensitive(&mut self, case_insensitive: bool) -> &mut Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Whether messages from bots and system users are ignored. Defaults to `true`.
+    pub fn ignore_bots(&mut self, ignore_bots: bool) -> &mut Self {
+        self.ignore_bots = ignore_bots;
+        self
+    }
+
+    /// Whether messages sent by the bot itself are ignored. Defaults to `true`.
+    pub fn ignore_self(&mut self, ignore_self: bool) -> &mut Self {
+        self.ignore_self = ignore_self;
+        self
+    }
+
+    /// Registers a top-level command
+    pub fn command(&mut self, command: Command<S, E>) -> &mut Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// Enables the built-in help command, listing the registered commands. Disabled by default.
+    pub fn help(&mut self, help: HelpCommand) -> &mut Self {
+        self.help = Some(help);
+        self
+    }
+
+    /// Adds a hook run before every command, after its checks have passed but before its cooldown is used.
+    /// Returning [`ControlFlow::Break`] cancels the command, which still counts as handled, but not towards the cooldown.
+    pub fn before<F, R>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(CommandContext<S>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<ControlFlow<()>, E>> + Send + 'static,
+    {
+        self.hooks.before(hook);
+        self
+    }
+
+    /// Adds a hook run after every command's handler, given its result and how long it took.
+    /// The returned result is passed on to any further hooks.
+    pub fn after<F, R>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(CommandContext<S>, Result<(), E>, Duration) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        self.hooks.after(hook);
+        self
+    }
+
+    /// Adds a hook run when a matched command fails, including its checks, cooldown and other hooks.
+    /// Returning `Ok` handles the error, otherwise it is passed on and eventually returned from [`dispatch`](Self::dispatch).
+    pub fn on_error<F, R>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(CommandContext<S>, E) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        self.hooks.on_error(hook);
+        self
+    }
+
+    /// Registered top-level commands
+    pub fn commands(&self) -> &[Command<S, E>] {
+        &self.commands
+    }
+
+    /// The bot's own user ID, used for mentions and to ignore its own messages
+    #[must_use]
+    pub fn self_id(&self) -> Option<UserId> {
+        *self.self_id.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sets the bot's own user ID, which is otherwise learned from the [`Ready`] event
+    pub fn set_self_id(&self, id: UserId) {
+        *self.self_id.lock().unwrap_or_else(|e| e.into_inner()) = Some(id);
+    }
+
+    /// Whether the message should be considered for commands at all
+    fn accepts(&self, msg: &Message) -> bool {
+        let author = &msg.author.user;
+
+        if self.ignore_bots && matches!(author.flags.elevation(), ElevationLevel::Bot | ElevationLevel::System) {
+            return false;
+        }
+
+        !(self.ignore_self && self.self_id() == Some(author.id))
+    }
+
+    /// Strips the longest matching prefix, or a mention of the bot, returning the prefix and remainder
+    fn strip_prefix<'a>(&self, content: &'a str) -> Option<(SmolStr, &'a str)> {
+        let mut best: Option<(&SmolStr, &'a str)> = None;
+
+        for prefix in &self.prefixes {
+            if best.is_some_and(|(best, _)| best.len() >= prefix.len()) {
+                continue;
+            }
+
+            let rest = match self.case_insensitive {
+                true => strip_prefix_ignore_case(content, prefix),
+                false => content.strip_prefix(prefix.as_str()),
+            };
+
+            if let Some(rest) = rest {
+                best = Some((prefix, rest));
+            }
+        }
+
+        if let Some((prefix, rest)) = best {
+            return Some((prefix.clone(), rest));
+        }
+
+        if self.mention {
+            if let Some(id) = self.self_id() {
+                let mention = smol_str::format_smolstr!("<@{id}>");
+
+                if let Some(rest) = content.strip_prefix(mention.as_str()) {
+                    return Some((mention, rest));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Finds the command invoked by the given message content, if any
+    pub(super) fn find<'a>(&'a self, content: &str) -> Option<CommandMatch<'a, S, E>> {
+        let (prefix, rest) = self.strip_prefix(content)?;
+
+        let mut commands = &self.commands;
+        let mut found: Option<&Command<S, E>> = None;
+        let mut parents = Vec::new();
+        let mut path = String::new();
+        let mut rest = rest.trim_start();
+
+        loop {
+            let word = rest.split(char::is_whitespace).next().unwrap_or_default();
+
+            if word.is_empty() {
+                break;
+            }
+
+            let Some(command) = commands.iter().find(|c| c.is_named(word, self.case_insensitive)) else {
+                break;
+            };
+
+            if !path.is_empty() {
+                path.push(' ');
+            }
+
+            path.push_str(&command.name);
+
+            parents.extend(found);
+            found = Some(command);
+            commands = &command.subcommands;
+            rest = rest[word.len()..].trim_start();
+        }
+
+        // groups without a handler of their own require a sub-command
+        let command = found.filter(|c| c.handler.is_some())?;
+
+        Some(CommandMatch {
+            command,
+            parents,
+            prefix,
+            path: SmolStr::from(path),
+            args_start: content.len() - rest.len(),
+        })
+    }
+
+    /// Finds an invocation of the built-in help command, returning the prefix and arguments
+    fn find_help<'a>(&self, content: &'a str) -> Option<(&HelpCommand, SmolStr, &'a str)> {
+        let help = self.help.as_ref()?;
+
+        let (prefix, rest) = self.strip_prefix(content)?;
+        let rest = rest.trim_start();
+
+        let word = rest.split(char::is_whitespace).next().unwrap_or_default();
+
+        let is_help = help.names.iter().any(|name| match self.case_insensitive {
+            true => super::command::eq_ignore_case(name, word),
+            false => name == word,
+        });
+
+        match is_help && !word.is_empty() {
+            true => Some((help, prefix, rest[word.len()..].trim())),
+            false => None,
+        }
+    }
+
+    /// Replies with help, listing only commands the author may run in the room
+    async fn run_help(&self, ctx: &StandardContext, msg: &Message, help: &HelpCommand, prefix: &str, args: &str) -> Result<(), E>
+    where
+        E: From<ClientError>,
+    {
+        // avoid fetching anything when no command has checks, and the member when none need it
+        let invoker = match any_checks(&self.commands, |checks| !checks.is_empty()) {
+            true => Invoker::fetch(ctx.client(), msg, any_checks(&self.commands, Checks::needs_member)).await?,
+            false => Invoker::unknown(),
+        };
+
+        let builder = help.render(&self.commands, &invoker, prefix, args, self.case_insensitive);
+
+        builder.reply_to(msg.id).send(ctx.client(), msg.room_id).await?;
+
+        Ok(())
+    }
+
+    /// Runs the command invoked by the message, if any, returning whether a command was run
+    ///
+    /// A registered command takes precedence over the built-in [help](Self::help) of the same name.
+    /// If the command's [checks](Command::checks), including those of its parent groups, fail or
+    /// the command is on [cooldown](Command::cooldown), a [`CommandError`] is passed to the
+    /// [error hooks](Self::on_error) instead, and returned if they do not handle it.
+    pub async fn dispatch(&self, ctx: StandardContext, msg: ModelArc<Message>) -> Result<bool, E>
+    where
+        E: From<ClientError> + From<CommandError>,
+    {
+        if !self.accepts(&msg) {
+            return Ok(false);
+        }
+
+        let Some(ref content) = msg.content else {
+            return Ok(false);
+        };
+
+        let Some(found) = self.find(content) else {
+            let Some((help, prefix, args)) = self.find_help(content) else {
+                return Ok(false);
+            };
+
+            self.run_help(&ctx, &msg, help, &prefix, args).await?;
+
+            return Ok(true);
+        };
+
+        let Some(ref handler) = found.command.handler else {
+            return Ok(false);
+        };
+
+        let chain: Vec<_> = found.parents.iter().copied().chain([found.command]).collect();
+
+        // global hooks wrap those of groups, which wrap those of their sub-commands
+        let hooks: Vec<_> = [&self.hooks].into_iter().chain(chain.iter().map(|c| &c.hooks)).collect();
+
+        let cmd = CommandContext {
+            ctx,
+            state: self.state.clone(),
+            prefix: found.prefix,
+            command: found.path,
+            args_start: found.args_start,
+            extensions: Default::default(),
+            msg,
+        };
+
+        let mut result = self.invoke(&cmd, &chain, &hooks, handler).await;
+
+        for hook in hooks.iter().rev().flat_map(|h| h.on_error.iter().rev()) {
+            let Err(err) = result else { break };
+
+            result = hook(cmd.clone(), err).await;
+        }
+
+        result.map(|()| true)
+    }
+
+    /// Runs checks, before hooks, the cooldown, the handler and after hooks, in that order
+    async fn invoke(
+        &self,
+        cmd: &CommandContext<S>,
+        chain: &[&Command<S, E>],
+        hooks: &[&Hooks<S, E>],
+        handler: &CommandFn<S, E>,
+    ) -> Result<(), E>
+    where
+        E: From<ClientError> + From<CommandError>,
+    {
+        let (client, msg) = (cmd.ctx.client(), &*cmd.msg);
+
+        let mut checks = Checks::default();
+
+        for command in chain {
+            checks.merge(&command.checks);
+        }
+
+        let invoker = super::perms::check::<E>(client, msg, &checks).await?;
+
+        for hook in hooks.iter().flat_map(|h| &h.before) {
+            if hook(cmd.clone()).await?.is_break() {
+                return Ok(());
+            }
+        }
+
+        // checked last, so failed checks and cancelled commands do not count towards the cooldown
+        if let Some(cooldown) = chain.last().and_then(|c| c.cooldown.as_ref()) {
+            cooldown.check::<E>(client, msg, invoker.as_ref()).await?;
+        }
+
+        let start = Instant::now();
+        let mut result = handler(cmd.clone()).await;
+        let elapsed = start.elapsed();
+
+        for hook in hooks.iter().rev().flat_map(|h| h.after.iter().rev()) {
+            result = hook(cmd.clone(), result, elapsed).await;
+        }
+
+        result
+    }
+}
+
+/// Whether any of the commands, or their sub-commands, have checks matching the predicate
+fn any_checks<S, E>(commands: &[Command<S, E>], predicate: fn(&Checks) -> bool) -> bool {
+    commands.iter().any(|c| predicate(c.get_checks()) || any_checks(c.subcommands(), predicate))
+}
+
+/// Like [`str::strip_prefix`], but ignoring case
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    let mut end = 0;
+    let mut chars = s.chars();
+
+    for p in prefix.chars() {
+        let c = chars.next()?;
+
+        if !c.to_lowercase().eq(p.to_lowercase()) {
+            return None;
+        }
+
+        end += c.len_utf8();
+    }
+
+    Some(&s[end..])
+}
+
+/// Wraps existing [`ServerMsgHandlers`] to route [`MessageCreate`](ServerMsg::MessageCreate) events
+/// through a [`CommandRouter`] first
+///
+/// Messages that don't invoke a command, and all other events, are passed on to the inner handlers.
+pub struct CommandHandlers<H, S, E> {
+    inner: H,
+    router: CommandRouter<S, E>,
+}
+
+impl<H, S, E> CommandHandlers<H, S, E> {
+    pub fn new(inner: H, router: CommandRouter<S, E>) -> Self {
+        CommandHandlers { inner, router }
+    }
+
+    pub fn inner(&mut self) -> &mut H {
+        &mut self.inner
+    }
+
+    pub fn router(&mut self) -> &mut CommandRouter<S, E> {
+        &mut self.router
+    }
+}
+
+impl<H, S, E> ServerMsgHandlers<StandardContext, Result<(), E>> for CommandHandlers<H, S, E>
+where
+    H: ServerMsgHandlers<StandardContext, Result<(), E>>,
+    S: Send + Sync + 'static,
+    E: From<ClientError> + From<CommandError> + Send + 'static,
+{
+    #[inline(always)]
+    async fn fallback(&self, ctx: StandardContext, msg: ServerMsg) -> Result<(), E> {
+        self.inner.dispatch(ctx, msg).await
+    }
+
+    fn has_handler(&self, opcode: ServerMsgOpcode) -> bool {
+        opcode == ServerMsgOpcode::MessageCreate || self.inner.has_handler(opcode)
+    }
+
+    async fn ready(&self, ctx: StandardContext, inner: ModelArc<Ready>) -> Result<(), E> {
+        self.router.set_self_id(inner.user.id);
+
+        self.inner.ready(ctx, inner).await
+    }
+
+    async fn message_create(&self, ctx: StandardContext, inner: ModelArc<Message>) -> Result<(), E> {
+        if self.router.dispatch(ctx.clone(), inner.clone()).await? {
+            return Ok(());
+        }
+
+        self.inner.message_create(ctx, inner).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+
+    use super::super::{CheckError, Cooldown, CooldownScope};
+
+    fn find<'a>(router: &'a CommandRouter<(), ()>, content: &str) -> Option<(&'a str, SmolStr, String)> {
+        router.find(content).map(|m| (m.command.name(), m.path, content[m.args_start..].to_owned()))
+    }
+
+    #[test]
+    fn test_find_command() {
+        let mut router = CommandRouter::<(), ()>::new();
+
+        let config = Command::new("config")
+            .subcommand(Command::new("set").handler(|_| async { Ok(()) }))
+            .subcommand(Command::new("get").handler(|_| async { Ok(()) }));
+
+        router.prefix("!").prefix("!!").command(config);
+        router.command(Command::new("ping").alias("p").handler(|_| async { Ok(()) }));
+
+        router.set_self_id("1234".parse().unwrap());
+
+        assert_eq!(find(&router, "!ping"), Some(("ping", "ping".into(), "".into())));
+        assert_eq!(find(&router, "!! P  a b"), Some(("ping", "ping".into(), "a b".into())));
+        assert_eq!(find(&router, "<@1234> ping a"), Some(("ping", "ping".into(), "a".into())));
+        assert_eq!(find(&router, "!Config SET x"), Some(("set", "config set".into(), "x".into())));
+        assert_eq!(find(&router, "!config get"), Some(("get", "config get".into(), "".into())));
+
+        // groups without a handler need a sub-command
+        assert!(find(&router, "!config").is_none());
+        assert!(find(&router, "!config other").is_none());
+
+        assert!(find(&router, "ping").is_none());
+        assert!(find(&router, "!pingx").is_none());
+        assert!(find(&router, "<@4321> ping").is_none());
+
+        router.case_insensitive(false);
+        assert!(find(&router, "!PING").is_none());
+    }
+
+    #[derive(Debug)]
+    enum TestError {
+        Client,
+        Command(CommandError),
+        Handler,
+    }
+
+    impl From<ClientError> for TestError {
+        fn from(_: ClientError) -> Self {
+            TestError::Client
+        }
+    }
+
+    impl From<CommandError> for TestError {
+        fn from(err: CommandError) -> Self {
+            TestError::Command(err)
+        }
+    }
+
+    /// Router whose state logs what ran, in order
+    type TestRouter = CommandRouter<Mutex<Vec<String>>, TestError>;
+
+    fn router() -> TestRouter {
+        let mut router = CommandRouter::new_with_state(Mutex::new(Vec::new()));
+        router.prefix("!");
+        router
+    }
+
+    fn log(cmd: &CommandContext<Mutex<Vec<String>>>, entry: impl Into<String>) {
+        cmd.state().lock().unwrap().push(entry.into());
+    }
+
+    fn logged(router: &TestRouter) -> Vec<String> {
+        core::mem::take(&mut *router.state.lock().unwrap())
+    }
+
+    async fn dispatch(router: &TestRouter, content: &str) -> Result<bool, TestError> {
+        let msg = serde_json::from_value(serde_json::json!({
+            "id": "3",
+            "room_id": "2",
+            "party_id": "1",
+            "author": {
+                "user": { "id": "4", "username": "someone", "discriminator": 1, "flags": 0 },
+                "joined_at": null,
+            },
+            "content": content,
+        }))
+        .unwrap();
+
+        let (ctx, _) = StandardContext::new(Client::new("http://localhost").unwrap());
+
+        router.dispatch(ctx, ModelArc::new(msg)).await
+    }
+
+    #[tokio::test]
+    async fn test_hook_order() {
+        #[derive(Clone)]
+        struct Locale(&'static str);
+
+        let mut router = router();
+
+        router
+            .before(|cmd| async move {
+                // storage starts empty for each invocation
+                log(&cmd, format!("global before {}", cmd.insert(Locale("nb")).is_some()));
+                Ok(ControlFlow::Continue(()))
+            })
+            .after(|cmd, res, _| async move {
+                log(&cmd, "global after");
+                res
+            });
+
+        router.command(
+            Command::new("config")
+                .before(|cmd| async move {
+                    log(&cmd, "group before");
+                    Ok(ControlFlow::Continue(()))
+                })
+                .after(|cmd, res, _| async move {
+                    log(&cmd, "group after");
+                    res
+                })
+                .subcommand(
+                    Command::new("set")
+                        .before(|cmd| async move {
+                            log(&cmd, "command before");
+                            Ok(ControlFlow::Continue(()))
+                        })
+                        .after(|cmd, res, _| async move {
+                            log(&cmd, "command after");
+                            res
+                        })
+                        .handler(|cmd| async move {
+                            // values stored by hooks are visible to the handler
+                            log(&cmd, format!("handler {}", cmd.get::<Locale>().map_or("", |l| l.0)));
+                            Ok(())
+                        }),
+                ),
+        );
+
+        for _ in 0..2 {
+            assert!(dispatch(&router, "!config set").await.unwrap());
+            assert_eq!(
+                logged(&router),
+                [
+                    "global before false",
+                    "group before",
+                    "command before",
+                    "handler nb",
+                    "command after",
+                    "group after",
+                    "global after"
+                ]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_before_break() {
+        let mut router = router();
+
+        router.after(|cmd, res, _| async move {
+            log(&cmd, "after");
+            res
+        });
+
+        router.command(
+            Command::new("ping")
+                .cooldown(Cooldown::fixed(CooldownScope::Global, 1, Duration::from_secs(60)))
+                .before(|cmd| async move {
+                    log(&cmd, "before");
+                    Ok(ControlFlow::Break(()))
+                })
+                .handler(|cmd| async move {
+                    log(&cmd, "handler");
+                    Ok(())
+                }),
+        );
+
+        // cancelled commands are still handled, but neither the handler nor after hooks run,
+        // and the cooldown isn't used up
+        for _ in 0..2 {
+            assert!(dispatch(&router, "!ping").await.unwrap());
+            assert_eq!(logged(&router), ["before"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_after_result() {
+        let mut router = router();
+
+        router.command(
+            Command::new("slow")
+                .after(|cmd, res, elapsed| async move {
+                    log(&cmd, format!("{res:?} {}", elapsed >= Duration::from_millis(20)));
+                    Ok(())
+                })
+                .handler(|_| async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    Err(TestError::Handler)
+                }),
+        );
+
+        // the after hook handles the error
+        assert!(dispatch(&router, "!slow").await.unwrap());
+        assert_eq!(logged(&router), ["Err(Handler) true"]);
+    }
+
+    #[tokio::test]
+    async fn test_error_hooks() {
+        let mut router = router();
+
+        router.on_error(|cmd, err| async move {
+            match err {
+                TestError::Command(CommandError::Check(_) | CommandError::Cooldown(_)) => {
+                    log(&cmd, format!("handled {err:?}"));
+                    Ok(())
+                }
+                err => Err(err),
+            }
+        });
+
+        router.command(
+            Command::new("roll")
+                .cooldown(Cooldown::fixed(CooldownScope::Global, 1, Duration::from_secs(60)))
+                .on_error(|cmd, err| async move {
+                    log(&cmd, "command error");
+                    Err(err)
+                })
+                .handler(|_| async { Ok(()) }),
+        );
+
+        // checks need the room to be fetched, so fail one from a hook instead
+        router.command(
+            Command::new("owner")
+                .before(|_| async { Err(CommandError::Check(CheckError::OwnerOnly).into()) })
+                .handler(|_| async { Ok(()) }),
+        );
+
+        router.command(Command::new("fail").handler(|_| async { Err(TestError::Handler) }));
+
+        assert!(dispatch(&router, "!roll").await.unwrap());
+        assert!(logged(&router).is_empty());
+
+        // command hooks run before global ones, which may handle the error
+        assert!(dispatch(&router, "!roll").await.unwrap());
+        let entries = logged(&router);
+        assert_eq!(entries[0], "command error");
+        assert!(entries[1].starts_with("handled Command(Cooldown("), "{}", entries[1]);
+
+        assert!(dispatch(&router, "!owner").await.unwrap());
+        assert_eq!(logged(&router), ["handled Command(Check(OwnerOnly))"]);
+
+        // unhandled errors are returned
+        assert!(matches!(dispatch(&router, "!fail").await, Err(TestError::Handler)));
+    }
+}
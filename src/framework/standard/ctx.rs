@@ -1,36 +1,45 @@
-use std::sync::{
-    atomic::{AtomicU32, Ordering::SeqCst},
-    Arc,
-};
+use core::future::Future;
+use core::time::Duration;
+use std::sync::Arc;
 
 use crate::{
     client::Client,
     driver::Driver,
     framework::{ServerMsg, ServerMsgHandlers},
-    models::{gateway::message::ClientMsg, *},
+    gateway::{Collector, EventBus, GatewayConnectionControl},
+    models::{
+        gateway::message::{ClientMsg, ServerMsgOpcode},
+        *,
+    },
 };
 
-use tokio::sync::mpsc::{self, error::SendError};
+use tokio::sync::mpsc::error::{SendError, TrySendError};
 
-#[derive(Debug)]
-pub enum StandardResponse {
-    Message(ClientMsg),
-    Close,
-}
+use super::outbox::{Outbox, DEFAULT_OUTBOUND_CAPACITY};
 
 struct StandardContextInner {
-    tx: mpsc::UnboundedSender<StandardResponse>,
+    outbox: Arc<Outbox>,
     client: Client,
+    events: EventBus,
 }
 
 #[derive(Clone)]
 pub struct StandardContext(Arc<StandardContextInner>);
 
 impl StandardContext {
-    pub(super) fn new(client: Client) -> (Self, mpsc::UnboundedReceiver<StandardResponse>) {
-        let (tx, rx) = mpsc::unbounded_channel();
-
-        (StandardContext(Arc::new(StandardContextInner { client, tx })), rx)
+    pub(super) fn new(client: Client) -> (Self, Arc<Outbox>) {
+        let outbox = Arc::new(Outbox::new(DEFAULT_OUTBOUND_CAPACITY));
+
+        let events = EventBus::new();
+
+        (
+            StandardContext(Arc::new(StandardContextInner {
+                outbox: outbox.clone(),
+                client,
+                events,
+            })),
+            outbox,
+        )
     }
 
     fn inner(&self) -> &StandardContextInner {
@@ -45,76 +54,97 @@ impl StandardContext {
         self.client().driver()
     }
 
+    /// Bus on which every received event is published before being dispatched,
+    /// including typed collectors such as [`EventBus::wait_for_message`].
+    pub fn events(&self) -> &EventBus {
+        &self.inner().events
+    }
+
+    /// Waits for the next event for which the predicate returns `true`, see [`EventBus::wait_for`]
+    ///
+    /// Events keep being received while handlers run, so this may be awaited within a handler.
+    pub fn wait_for<F>(&self, predicate: F, timeout: Duration) -> impl Future<Output = Option<Arc<ServerMsg>>> + Send + 'static
+    where
+        F: Fn(&ServerMsg) -> bool + Send + Sync + 'static,
+    {
+        self.events().wait_for(predicate, timeout)
+    }
+
+    /// Collects up to `limit` events for which the predicate returns `true`, see [`EventBus::collect`]
+    pub fn collect<F>(&self, predicate: F, limit: usize, timeout: Duration) -> Collector
+    where
+        F: Fn(&ServerMsg) -> bool + Send + Sync + 'static,
+    {
+        self.events().collect(predicate, limit, timeout)
+    }
+
+    /// Stops the bot once any queued messages have been sent, returning `false` if it was already stopping.
     #[must_use]
     pub fn close(&self) -> bool {
-        self.inner().tx.send(StandardResponse::Close).is_ok()
+        self.inner().outbox.close()
     }
 
-    pub fn send(&self, msg: ClientMsg) -> Result<(), SendError<ClientMsg>> {
-        match self.inner().tx.send(StandardResponse::Message(msg)) {
-            Ok(()) => Ok(()),
-            Err(SendError(StandardResponse::Message(msg))) => Err(SendError(msg)),
-            Err(_) => unreachable!(),
-        }
+    /// Queues a message to be sent to the gateway, waiting for space if the outbound queue is full.
+    ///
+    /// Messages are sent in order, subject to the [outbound rate limit](super::Standard::outbound).
+    /// Fails only if the bot is stopping.
+    pub async fn send(&self, msg: ClientMsg) -> Result<(), SendError<ClientMsg>> {
+        self.inner().outbox.send(msg).await
     }
 
-    pub fn set_presence(&self, presence: UserPresence) -> Result<(), SendError<UserPresence>> {
-        match self.send(ClientMsg::new_set_presence(commands::SetPresence { presence })) {
-            Ok(()) => Ok(()),
-            Err(SendError(ClientMsg::SetPresence(payload))) => Err(SendError(payload.inner.presence)),
-            Err(_) => unreachable!(),
-        }
+    /// Queues a message to be sent to the gateway, or fails right away if the outbound queue is full.
+    pub fn try_send(&self, msg: ClientMsg) -> Result<(), TrySendError<ClientMsg>> {
+        self.inner().outbox.try_send(msg)
+    }
+
+    /// Updates the bot's presence. If a previous update is still queued, it's replaced rather than sent twice.
+    pub fn set_presence(&self, presence: UserPresence) -> Result<(), TrySendError<UserPresence>> {
+        self.try_send(ClientMsg::new_set_presence(commands::SetPresence { presence })).map_err(|e| match e {
+            TrySendError::Full(msg) => TrySendError::Full(into_presence(msg)),
+            TrySendError::Closed(msg) => TrySendError::Closed(into_presence(msg)),
+        })
     }
 }
 
-use tokio::sync::Notify;
+fn into_presence(msg: ClientMsg) -> UserPresence {
+    match msg {
+        ClientMsg::SetPresence(payload) => payload.inner.presence,
+        _ => unreachable!(),
+    }
+}
 
 pub struct InternalEventHandlers<H> {
     pub user: H,
-    heartbeat: Arc<Notify>,
-    interval: AtomicU32,
+    control: Arc<GatewayConnectionControl>,
 }
 
 impl<H> InternalEventHandlers<H> {
-    pub fn new(state: H) -> Self {
-        InternalEventHandlers {
-            user: state,
-            heartbeat: Default::default(),
-            interval: AtomicU32::new(45_000),
-        }
-    }
-
-    #[allow(clippy::needless_return)] // ugh
-    fn setup_new_heartbeat(&self, ctx: StandardContext) {
-        let hb = self.heartbeat.clone();
-        let interval = self.interval.load(SeqCst);
+    pub fn new(state: H, control: Arc<GatewayConnectionControl>) -> Self {
+        // heartbeats are handled by the gateway connection itself
+        control.set_heartbeat(true);
 
-        hb.notify_waiters();
-        tokio::spawn(async move {
-            let duration = tokio::time::Duration::from_millis(interval as u64);
-
-            tokio::time::sleep(duration).await;
-
-            if ctx.send(ClientMsg::new_heartbeat()).is_err() {
-                return;
-            }
-
-            // once the heartbeat is sent, the server will respond with ack and
-            // trigger the killswitch to cancel this sleep, avoiding `close()`
-            // but if the server does not respond, close will be called
+        InternalEventHandlers { user: state, control }
+    }
 
-            let mut sleep = core::pin::pin!(tokio::time::sleep(duration));
+    /// Answers control messages as soon as they're received, rather than when they're dispatched,
+    /// as a busy handler or a full backlog could otherwise delay the handshake until the session expires.
+    pub fn control(&self, ctx: &StandardContext, msg: &ServerMsg) {
+        match msg {
+            // the session has been forgotten by the connection upon InvalidSession, so this will identify
+            ServerMsg::Hello(_) | ServerMsg::InvalidSession(_) => self.handshake(ctx),
+            _ => {}
+        }
+    }
 
-            tokio::select! {
-                biased;
-                _ = hb.notified() => return,
-                _ = &mut sleep => ctx.close(),
-            };
-        });
+    /// Resume the previous session if possible, otherwise identify
+    fn handshake(&self, ctx: &StandardContext) {
+        if let Some(auth) = ctx.client().auth() {
+            // ahead of anything queued, as the server expects nothing else before the handshake
+            let _ = ctx.inner().outbox.send_priority(self.control.handshake(auth));
+        }
     }
 }
 
-use crate::models::events::*;
 impl<H, E> ServerMsgHandlers<StandardContext, Result<(), E>> for InternalEventHandlers<H>
 where
     H: ServerMsgHandlers<StandardContext, Result<(), E>>,
@@ -124,25 +154,9 @@ where
         self.user.dispatch(ctx, msg).await
     }
 
-    async fn hello(&self, ctx: StandardContext, inner: Hello) -> Result<(), E> {
-        self.interval.store(inner.heartbeat_interval, SeqCst);
-
-        self.setup_new_heartbeat(ctx.clone());
-
-        if let Some(auth) = ctx.client().auth() {
-            let _ = ctx.send(ClientMsg::new_identify(commands::Identify {
-                auth,
-                intent: Intent::all(),
-            }));
-        }
-
-        self.user.hello(ctx, inner).await
-    }
-
-    async fn heartbeat_ack(&self, ctx: StandardContext) -> Result<(), E> {
-        self.setup_new_heartbeat(ctx.clone());
-
-        self.user.heartbeat_ack(ctx).await
+    #[inline(always)]
+    fn has_handler(&self, opcode: ServerMsgOpcode) -> bool {
+        self.user.has_handler(opcode)
     }
 
     //async fn ready(&self, ctx: StandardContext, ready: Box<Ready>) -> Result<(), E> {
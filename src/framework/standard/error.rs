@@ -2,6 +2,8 @@ use core::error::Error;
 
 use crate::{client::ClientError, driver::DriverError, gateway::GatewayError};
 
+use super::cmd::CommandError;
+
 #[derive(Debug, thiserror::Error)]
 pub enum StandardError {
     #[error(transparent)]
@@ -12,6 +14,9 @@ pub enum StandardError {
 
     #[error(transparent)]
     GatewayError(#[from] GatewayError),
+
+    #[error(transparent)]
+    CommandError(#[from] CommandError),
 }
 
 /// Required properties for custom error types,
@@ -1,27 +1,47 @@
 #![allow(clippy::type_complexity)]
 
+use std::collections::VecDeque;
 use std::sync::Arc;
 
 use crate::{
+    api::RateLimit,
     client::Client,
-    gateway::{GatewayConnection, GatewayConnectionControl},
+    gateway::{CloseAction, GatewayConnection, GatewayConnectionControl, GatewayError, LagPolicy, ReplayGateway, SessionStatus},
+    models::Intent,
 };
 
-use futures::{stream::SplitSink, SinkExt, StreamExt};
-use tokio::sync::mpsc;
+use futures::{stream::SplitSink, Future, Sink, SinkExt, Stream, StreamExt};
+use tokio::sync::oneshot::error::TryRecvError;
 
 pub mod cmd;
 
 mod ctx;
 mod error;
+mod outbox;
 mod util;
 
 pub use ctx::StandardContext;
 pub use error::{StandardError, StandardErrorExt};
+pub use outbox::DEFAULT_OUTBOUND_CAPACITY;
 
 use self::ctx::InternalEventHandlers;
+use self::outbox::Outbox;
+use self::util::Gcra;
+
+/// Default rate limit for outgoing gateway messages, allowing a burst of 5 messages
+/// and then one message every 500ms.
+pub const DEFAULT_OUTBOUND_RATE_LIMIT: RateLimit = RateLimit {
+    emission_interval: core::time::Duration::from_millis(500),
+    burst_size: match core::num::NonZeroU64::new(5) {
+        Some(burst_size) => burst_size,
+        None => unreachable!(),
+    },
+};
+
+/// Default number of events queued while a handler runs, see [`Standard::backlog`]
+pub const DEFAULT_BACKLOG_CAPACITY: usize = 1024;
 
-use super::{ClientMsg, DynamicServerMsgHandlers, ServerMsgHandlers};
+use super::{ClientMsg, DynamicServerMsgHandlers, ServerMsg, ServerMsgHandlers, ServerMsgOpcode};
 
 /// Dynamic [`ServerMsgHandlers`] suitable for simpler bot applications
 pub type StandardDynamicHandler<S, E> = DynamicServerMsgHandlers<StandardContext, Result<(), E>, S>;
@@ -33,6 +53,8 @@ pub type SimpleStandard<H> = Standard<H, StandardError>;
 type ErrorCb<H, E> = Arc<dyn Fn(E, StandardContext, &H) + Send + Sync + 'static>;
 /// Runs once after first gateway connection is established
 type StartCb<H, E> = Box<dyn FnOnce(StandardContext, &mut H) -> Result<(), E>>;
+/// Runs whenever a gateway session is started, restarted or resumed
+type SessionCb<H> = Box<dyn Fn(SessionStatus, StandardContext, &H) + Send + Sync + 'static>;
 
 pub struct Standard<H, E: StandardErrorExt = StandardError> {
     state: ctx::InternalEventHandlers<H>,
@@ -40,7 +62,10 @@ pub struct Standard<H, E: StandardErrorExt = StandardError> {
     gateway: GatewayConnection,
     on_error: Option<ErrorCb<H, E>>,
     on_start: Option<StartCb<H, E>>,
-    rx: mpsc::UnboundedReceiver<ctx::StandardResponse>,
+    on_session: Option<SessionCb<H>>,
+    outbox: Arc<Outbox>,
+    rate_limit: RateLimit,
+    backlog: (usize, LagPolicy),
 }
 
 impl<E: StandardErrorExt> Standard<StandardDynamicHandler<(), E>, E> {
@@ -67,15 +92,19 @@ where
     H: ServerMsgHandlers<StandardContext, Result<(), E>>,
 {
     pub fn new_with_handlers(client: Client, state: H) -> Self {
-        let (ctx, rx) = StandardContext::new(client.clone());
+        let (ctx, outbox) = StandardContext::new(client.clone());
+        let gateway = GatewayConnection::new(client);
 
         Standard {
-            state: ctx::InternalEventHandlers::new(state),
-            gateway: GatewayConnection::new(client),
+            state: ctx::InternalEventHandlers::new(state, gateway.control()),
+            gateway,
             ctx,
-            rx,
+            outbox,
+            rate_limit: DEFAULT_OUTBOUND_RATE_LIMIT,
+            backlog: (DEFAULT_BACKLOG_CAPACITY, LagPolicy::default()),
             on_error: None,
             on_start: None,
+            on_session: None,
         }
     }
 
@@ -100,6 +129,77 @@ where
         self
     }
 
+    /// Setup a callback to run whenever a gateway session is established, before the event that
+    /// established it is dispatched.
+    ///
+    /// This can be used to tell if a session was [resumed](SessionStatus::Resumed) after a reconnect,
+    /// or [restarted](SessionStatus::Restarted), in which case any events in between were lost.
+    pub fn on_session<F>(&mut self, cb: F) -> &mut Self
+    where
+        F: Fn(SessionStatus, StandardContext, &H) + Send + Sync + 'static,
+    {
+        self.on_session = Some(Box::new(cb));
+        self
+    }
+
+    /// Configures the outbound queue of messages sent through the [`StandardContext`], defaulting to
+    /// [`DEFAULT_OUTBOUND_CAPACITY`] and [`DEFAULT_OUTBOUND_RATE_LIMIT`].
+    ///
+    /// Once `capacity` messages are queued, [`StandardContext::send`] waits for space and
+    /// [`StandardContext::try_send`] fails. Messages are sent no faster than the rate limit allows.
+    pub fn outbound(&mut self, capacity: usize, rate_limit: RateLimit) -> &mut Self {
+        self.outbox.set_capacity(capacity);
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// Configures the backlog of events received while a handler runs, defaulting to
+    /// [`DEFAULT_BACKLOG_CAPACITY`] and [`LagPolicy::DropOldest`].
+    ///
+    /// Handlers run one at a time, so events that arrive in the meantime, such as while a handler
+    /// awaits a [`Collector`](crate::gateway::Collector), are shared with collectors right away but queued
+    /// to be dispatched afterwards. Once `capacity` events are queued, the policy decides which event
+    /// is dropped, and [`LagPolicy::Disconnect`] stops the bot after dispatching the queued events.
+    ///
+    /// Control messages such as [`Hello`](ServerMsg::Hello) are answered as soon as they're received
+    /// and never dropped, so a busy bot still keeps its gateway session.
+    pub fn backlog(&mut self, capacity: usize, policy: LagPolicy) -> &mut Self {
+        self.backlog = (capacity.max(1), policy);
+        self
+    }
+
+    /// Declare the gateway intents the bot needs, defaulting to [`Intent::all()`].
+    ///
+    /// Events outside of these intents will not be dispatched.
+    pub fn intents(&mut self, intents: Intent) -> &mut Self {
+        self.gateway.control().set_intents(intents);
+        self
+    }
+
+    /// Intents required by registered handlers that were not [declared](Self::intents),
+    /// so those handlers would never be called.
+    ///
+    /// These are logged as a warning when the bot starts. To fail early instead, check them
+    /// before [running](Self::run) the bot:
+    ///
+    /// ```rust,ignore
+    /// let missing = bot.missing_intents();
+    /// assert!(missing.is_empty(), "missing intents: {missing:?}");
+    /// ```
+    ///
+    /// Only handlers that report themselves through [`ServerMsgHandlers::has_handler`] can be checked.
+    #[must_use]
+    pub fn missing_intents(&self) -> Intent {
+        let declared = self.gateway.control().intents();
+
+        ServerMsgOpcode::ALL
+            .iter()
+            .filter(|&&opcode| self.state.has_handler(opcode))
+            .filter_map(|opcode| opcode.required_intent())
+            .filter(|&intent| !declared.intersects(intent))
+            .fold(Intent::empty(), |missing, intent| missing | intent)
+    }
+
     pub fn handlers(&mut self) -> &mut H {
         &mut self.state.user
     }
@@ -112,21 +212,61 @@ where
         self.gateway.control()
     }
 
+    /// Connects to the gateway and runs the bot until the connection is closed, or an error
+    /// recommends [giving up](CloseAction::GiveUp) on reconnecting
+    ///
+    /// Handlers for events outside of the declared intents are never called,
+    /// see [`missing_intents`](Self::missing_intents).
     pub async fn run(self) -> Result<(), E> {
+        self.run_on(|mut gateway| async move {
+            gateway.connect().await?;
+            Ok(gateway)
+        })
+        .await
+    }
+
+    /// Runs the bot against a recorded sequence of gateway events instead of a live connection,
+    /// until the recording ends.
+    ///
+    /// Messages sent to the gateway are discarded, and the session callback is never called.
+    /// Recorded events outside of the [declared intents](Self::intents) are skipped, as they
+    /// would be by a live connection.
+    pub async fn replay(self, replay: ReplayGateway) -> Result<(), E> {
+        let control = self.gateway.control();
+
+        self.run_on(move |_| async move { Ok(replay.filter(move |event| futures::future::ready(is_declared(&control, event)))) })
+            .await
+    }
+
+    async fn run_on<G, F, R>(self, open: F) -> Result<(), E>
+    where
+        F: FnOnce(GatewayConnection) -> R,
+        R: Future<Output = Result<G, GatewayError>>,
+        G: Stream<Item = Result<ServerMsg, GatewayError>> + Sink<ClientMsg, Error = GatewayError> + Send + 'static,
+    {
+        let missing = self.missing_intents();
+
+        if !missing.is_empty() {
+            log::warn!("Handlers are registered for events outside of the declared intents, missing intents: {missing:?}");
+        }
+
         let Standard {
             mut state,
             ctx,
-            mut gateway,
+            gateway,
             on_error,
             on_start,
-            rx,
+            on_session,
+            outbox,
+            rate_limit,
+            backlog: (backlog_capacity, backlog_policy),
         } = self;
 
+        let control = gateway.control();
+        let mut session_status = control.session_status();
+
         // connect to gateway first, split streams
-        let (gw_tx, mut gw_rx) = {
-            gateway.connect().await?;
-            gateway.split()
-        };
+        let (gw_tx, mut gw_rx) = open(gateway).await?.split();
 
         if let Some(on_start) = on_start {
             if let Err(e) = on_start(ctx.clone(), &mut state.user) {
@@ -142,22 +282,73 @@ where
         let state = Arc::new(state);
 
         // start running client msg task
-        let client_task = tokio::spawn(run_client(rx, gw_tx, state.clone(), ctx.clone(), on_error.clone(), kill));
+        let client_task = tokio::spawn(run_client(
+            outbox,
+            rate_limit,
+            gw_tx,
+            state.clone(),
+            ctx.clone(),
+            on_error.clone(),
+            kill,
+        ));
+
+        let mut backlog = Backlog::new(backlog_capacity, backlog_policy);
+        let mut ended = false;
 
         // begin listening for events on current task
         loop {
-            let event = tokio::select! {
-                biased;
-                _ = &mut alive => break,
-                event = gw_rx.next() => match event {
-                    Some(event) => event,
-                    None => break,
+            let event = match backlog.pop() {
+                Some(event) => match alive.try_recv() {
+                    Err(TryRecvError::Empty) => event,
+                    _ => break,
+                },
+                None if ended => break,
+                None => tokio::select! {
+                    biased;
+                    _ = &mut alive => break,
+                    event = gw_rx.next() => match event {
+                        Some(event) => receive(&state, &ctx, event),
+                        None => break,
+                    },
                 },
             };
 
+            if let Some(ref session_cb) = on_session {
+                let status = control.session_status();
+
+                if status != session_status {
+                    session_status = status;
+
+                    if let SessionStatus::Started | SessionStatus::Restarted | SessionStatus::Resumed = status {
+                        session_cb(status, ctx.clone(), &state.user);
+                    }
+                }
+            }
+
+            // stop once the gateway recommends against reconnecting, as it won't
+            let give_up = matches!(event, Err(ref e) if e.recommended_action() == CloseAction::GiveUp);
+
             let res = match event {
                 Err(e) => Err(e.into()),
-                Ok(msg) => state.dispatch(ctx.clone(), msg).await,
+                Ok(msg) => {
+                    let mut dispatch = core::pin::pin!(state.dispatch(ctx.clone(), msg));
+
+                    // keep publishing new events to collectors until the handler completes
+                    loop {
+                        tokio::select! {
+                            biased;
+                            res = &mut dispatch => break res,
+                            event = gw_rx.next(), if !ended => match event {
+                                Some(event) => {
+                                    if !backlog.push(receive(&state, &ctx, event)) {
+                                        ended = true;
+                                    }
+                                }
+                                None => ended = true,
+                            },
+                        }
+                    }
+                }
             };
 
             if let Err(e) = res {
@@ -165,36 +356,252 @@ where
                     err_cb(e, ctx.clone(), &state.user);
                 }
             }
+
+            if give_up {
+                break;
+            }
         }
 
+        // stop the client msg task as well, if the gateway stream ended first
+        let _ = ctx.close();
         let _ = client_task.await;
 
         Ok(())
     }
 }
 
-async fn run_client<H, E: StandardErrorExt>(
-    mut rx: mpsc::UnboundedReceiver<ctx::StandardResponse>,
-    mut gw_tx: SplitSink<GatewayConnection, ClientMsg>,
+/// Whether an event is within the declared intents, as a live [`GatewayConnection`] drops any others
+fn is_declared(control: &GatewayConnectionControl, event: &Result<ServerMsg, GatewayError>) -> bool {
+    match event {
+        Ok(msg) => msg.is_within(control.intents()),
+        Err(_) => true,
+    }
+}
+
+/// Handles an incoming event as soon as it's received, before it's dispatched: answering control
+/// messages and sharing the event with any collectors.
+fn receive<H>(
+    state: &InternalEventHandlers<H>,
+    ctx: &StandardContext,
+    event: Result<ServerMsg, GatewayError>,
+) -> Result<ServerMsg, GatewayError> {
+    if let Ok(ref msg) = event {
+        state.control(ctx, msg);
+
+        let events = ctx.events();
+
+        if events.subscribers() > 0 {
+            events.publish(msg.clone());
+        }
+    }
+
+    event
+}
+
+/// Whether the event affects the gateway session, so must never be dropped from the [`Backlog`]
+fn is_control(event: &Result<ServerMsg, GatewayError>) -> bool {
+    matches!(
+        event,
+        Err(_) | Ok(ServerMsg::Hello(_) | ServerMsg::InvalidSession(_) | ServerMsg::HeartbeatAck(_))
+    )
+}
+
+/// Events received while a handler runs, which may itself be waiting on a collector,
+/// queued to be dispatched afterwards.
+///
+/// Control messages and errors are not counted towards the capacity and are never dropped.
+struct Backlog {
+    events: VecDeque<Result<ServerMsg, GatewayError>>,
+    capacity: usize,
+    policy: LagPolicy,
+    /// Number of queued events that may be dropped
+    len: usize,
+}
+
+impl Backlog {
+    fn new(capacity: usize, policy: LagPolicy) -> Self {
+        Backlog {
+            events: VecDeque::new(),
+            capacity,
+            policy,
+            len: 0,
+        }
+    }
+
+    /// Queues an event to be dispatched later, returning `false` if the bot should stop reading events
+    fn push(&mut self, event: Result<ServerMsg, GatewayError>) -> bool {
+        if is_control(&event) {
+            self.events.push_back(event);
+            return true;
+        }
+
+        if self.len >= self.capacity {
+            match self.policy {
+                LagPolicy::DropOldest => {
+                    if let Some(idx) = self.events.iter().position(|event| !is_control(event)) {
+                        self.events.remove(idx);
+                        self.len -= 1;
+                    }
+                }
+                LagPolicy::DropNewest => return true,
+                LagPolicy::Disconnect => return false,
+            }
+        }
+
+        self.events.push_back(event);
+        self.len += 1;
+
+        true
+    }
+
+    fn pop(&mut self) -> Option<Result<ServerMsg, GatewayError>> {
+        let event = self.events.pop_front()?;
+
+        if !is_control(&event) {
+            self.len -= 1;
+        }
+
+        Some(event)
+    }
+}
+
+async fn run_client<G, H, E: StandardErrorExt>(
+    outbox: Arc<Outbox>,
+    rate_limit: RateLimit,
+    mut gw_tx: SplitSink<G, ClientMsg>,
     state: Arc<InternalEventHandlers<H>>,
     ctx: StandardContext,
     on_error: Option<ErrorCb<H, E>>,
     kill: tokio::sync::oneshot::Sender<()>,
-) {
-    while let Some(resp) = rx.recv().await {
-        match resp {
-            ctx::StandardResponse::Message(msg) => {
-                if let Err(e) = gw_tx.send(msg).await {
-                    if let Some(ref err_cb) = on_error {
-                        err_cb(e.into(), ctx.clone(), &state.user);
-                    }
-                }
-            }
-            ctx::StandardResponse::Close => {
-                rx.close();
-                let _ = kill.send(());
-                return;
+) where
+    G: Sink<ClientMsg, Error = GatewayError>,
+{
+    let mut gcra = Gcra::default();
+
+    while let Some(msg) = outbox.recv().await {
+        while let Err(wait) = gcra.check(&rate_limit, std::time::Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+
+        if let Err(e) = gw_tx.send(msg).await {
+            if let Some(ref err_cb) = on_error {
+                err_cb(e.into(), ctx.clone(), &state.user);
             }
         }
     }
+
+    // the outbox is closed and drained
+    let _ = kill.send(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::Encoding;
+    use crate::gateway::{RecordMode, Recorder, ReplaySpeed};
+    use crate::models::{events::Hello, AuthToken, BotToken, PartyId, Snowflake};
+
+    fn id(id: u64) -> PartyId {
+        PartyId::from(id.to_string().parse::<Snowflake>().unwrap())
+    }
+
+    fn event(n: u64) -> Result<ServerMsg, GatewayError> {
+        Ok(ServerMsg::new_party_delete(id(n)))
+    }
+
+    /// Queued party IDs, or `None` for other events
+    fn ids(backlog: &Backlog) -> Vec<Option<PartyId>> {
+        let ids = backlog.events.iter().map(|event| match event {
+            Ok(ServerMsg::PartyDelete(payload)) => Some(payload.id),
+            _ => None,
+        });
+
+        ids.collect()
+    }
+
+    #[test]
+    fn test_backlog_policy() {
+        let mut backlog = Backlog::new(2, LagPolicy::DropOldest);
+
+        assert!(backlog.push(event(1)));
+        assert!(backlog.push(event(2)));
+        assert!(backlog.push(event(3)));
+        assert_eq!(ids(&backlog), [Some(id(2)), Some(id(3))]);
+
+        backlog.policy = LagPolicy::DropNewest;
+        assert!(backlog.push(event(4)));
+        assert_eq!(ids(&backlog), [Some(id(2)), Some(id(3))]);
+
+        backlog.policy = LagPolicy::Disconnect;
+        assert!(!backlog.push(event(5)));
+        assert_eq!(ids(&backlog), [Some(id(2)), Some(id(3))]);
+    }
+
+    #[tokio::test]
+    async fn test_backlog_control() {
+        let client = Client::new("http://localhost").unwrap();
+        let token = "a".repeat(BotToken::LEN).parse::<AuthToken>().unwrap();
+        client.set_auth(Some(token)).unwrap();
+
+        let (ctx, outbox) = StandardContext::new(client.clone());
+        let state = InternalEventHandlers::new((), GatewayConnection::new(client).control());
+
+        for policy in [LagPolicy::DropOldest, LagPolicy::DropNewest, LagPolicy::Disconnect] {
+            let mut backlog = Backlog::new(2, policy);
+
+            assert!(backlog.push(receive(&state, &ctx, event(1))));
+            assert!(backlog.push(receive(&state, &ctx, event(2))));
+
+            // answered right away, and queued for dispatch even though the backlog is full
+            assert!(backlog.push(receive(&state, &ctx, Ok(ServerMsg::new_hello(Hello::default())))));
+            assert!(matches!(outbox.recv().await, Some(ClientMsg::Identify(_))));
+
+            // dropping the oldest event skips over the Hello
+            assert_eq!(backlog.push(event(3)), policy != LagPolicy::Disconnect);
+            assert!(backlog.push(event(4)) || policy == LagPolicy::Disconnect);
+
+            let expected = match policy {
+                LagPolicy::DropOldest => [None, Some(id(3)), Some(id(4))],
+                _ => [Some(id(1)), Some(id(2)), None],
+            };
+
+            assert_eq!(ids(&backlog), expected);
+
+            while backlog.pop().is_some() {}
+
+            assert_eq!(backlog.len, 0);
+        }
+    }
+
+    /// Records the opcode of every event dispatched to it
+    struct Seen(Arc<std::sync::Mutex<Vec<ServerMsgOpcode>>>);
+
+    impl ServerMsgHandlers<StandardContext, Result<(), StandardError>> for Seen {
+        async fn fallback(&self, _ctx: StandardContext, msg: ServerMsg) -> Result<(), StandardError> {
+            self.0.lock().unwrap().extend(msg.opcode());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_intents() {
+        let path = std::env::temp_dir().join(format!("lgwrec-intents-{}", std::process::id()));
+
+        let recorder = Recorder::create(&path, RecordMode::Decoded).unwrap();
+        recorder.record(Encoding::JSON, &[], Some(&ServerMsg::new_room_pins_update()));
+        recorder.record(Encoding::JSON, &[], Some(&ServerMsg::new_heartbeat_ack()));
+        recorder.flush().unwrap();
+
+        let replay = ReplayGateway::open(&path, ReplaySpeed::Instant).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let seen = Arc::default();
+
+        let mut bot = Standard::new_with_handlers(Client::new("http://localhost").unwrap(), Seen(Arc::clone(&seen)));
+        bot.intents(Intent::MESSAGES);
+        bot.replay(replay).await.unwrap();
+
+        // room pins also require the parties intent, so a live connection would never deliver them
+        assert_eq!(*seen.lock().unwrap(), [ServerMsgOpcode::HeartbeatAck]);
+    }
 }
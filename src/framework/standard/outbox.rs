@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tokio::sync::Notify;
+
+use crate::models::gateway::message::ClientMsg;
+
+/// Default number of outgoing messages that may be queued before senders have to wait
+pub const DEFAULT_OUTBOUND_CAPACITY: usize = 64;
+
+#[derive(Default)]
+struct OutboxState {
+    queue: VecDeque<ClientMsg>,
+    closed: bool,
+}
+
+/// Bounded queue of outgoing messages from [`StandardContext`](super::StandardContext) to the gateway
+///
+/// Queued [`SetPresence`](ClientMsg::SetPresence) messages are replaced in place by newer ones,
+/// as only the latest presence matters.
+pub(super) struct Outbox {
+    state: Mutex<OutboxState>,
+    capacity: AtomicUsize,
+    /// Wakes the receiver when a message is queued or the outbox is closed
+    recv: Notify,
+    /// Wakes a waiting sender when space is freed up
+    space: Notify,
+}
+
+impl Outbox {
+    pub fn new(capacity: usize) -> Self {
+        Outbox {
+            state: Mutex::default(),
+            capacity: AtomicUsize::new(capacity.max(1)),
+            recv: Notify::new(),
+            space: Notify::new(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, OutboxState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity.max(1), Ordering::Relaxed);
+    }
+
+    pub fn try_send(&self, msg: ClientMsg) -> Result<(), TrySendError<ClientMsg>> {
+        let mut state = self.state();
+
+        if state.closed {
+            return Err(TrySendError::Closed(msg));
+        }
+
+        let msg = match msg {
+            ClientMsg::SetPresence(presence) => {
+                let queued = state.queue.iter_mut().find(|queued| matches!(queued, ClientMsg::SetPresence(_)));
+
+                if let Some(queued) = queued {
+                    *queued = ClientMsg::SetPresence(presence);
+                    return Ok(());
+                }
+
+                ClientMsg::SetPresence(presence)
+            }
+            msg => msg,
+        };
+
+        if state.queue.len() >= self.capacity.load(Ordering::Relaxed) {
+            return Err(TrySendError::Full(msg));
+        }
+
+        state.queue.push_back(msg);
+        drop(state);
+
+        self.recv.notify_one();
+
+        Ok(())
+    }
+
+    pub async fn send(&self, mut msg: ClientMsg) -> Result<(), SendError<ClientMsg>> {
+        loop {
+            let space = self.space.notified();
+
+            match self.try_send(msg) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Closed(msg)) => return Err(SendError(msg)),
+                Err(TrySendError::Full(full)) => msg = full,
+            }
+
+            space.await;
+        }
+    }
+
+    /// Queues a message ahead of all others, regardless of capacity, such as for the handshake
+    pub fn send_priority(&self, msg: ClientMsg) -> Result<(), SendError<ClientMsg>> {
+        let mut state = self.state();
+
+        if state.closed {
+            return Err(SendError(msg));
+        }
+
+        state.queue.push_front(msg);
+        drop(state);
+
+        self.recv.notify_one();
+
+        Ok(())
+    }
+
+    /// Closes the outbox after any queued messages, returning `false` if it was already closed
+    pub fn close(&self) -> bool {
+        let was_open = !std::mem::replace(&mut self.state().closed, true);
+
+        self.recv.notify_one();
+        self.space.notify_waiters();
+
+        was_open
+    }
+
+    /// Takes the next message, waiting for one if necessary, or `None` once closed and empty
+    pub async fn recv(&self) -> Option<ClientMsg> {
+        loop {
+            let recv = self.recv.notified();
+
+            {
+                let mut state = self.state();
+
+                if let Some(msg) = state.queue.pop_front() {
+                    drop(state);
+                    self.space.notify_one();
+
+                    return Some(msg);
+                }
+
+                if state.closed {
+                    return None;
+                }
+            }
+
+            recv.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{commands::SetPresence, Snowflake, UserPresence, UserPresenceFlags};
+
+    #[tokio::test]
+    async fn test_outbox() {
+        let outbox = Outbox::new(2);
+
+        let presence = |flags| {
+            ClientMsg::new_set_presence(SetPresence {
+                presence: UserPresence::new(flags),
+            })
+        };
+
+        outbox.try_send(ClientMsg::new_heartbeat()).unwrap();
+        outbox.try_send(presence(UserPresenceFlags::AWAY)).unwrap();
+
+        // replaces the queued presence, even though the queue is full
+        outbox.try_send(presence(UserPresenceFlags::BUSY)).unwrap();
+        assert!(matches!(
+            outbox.try_send(ClientMsg::new_heartbeat()),
+            Err(TrySendError::Full(_))
+        ));
+        assert_eq!(outbox.state().queue.len(), 2);
+
+        outbox.send_priority(ClientMsg::new_resume("1".parse::<Snowflake>().unwrap())).unwrap();
+
+        assert!(matches!(outbox.recv().await, Some(ClientMsg::Resume(_))));
+        assert!(matches!(outbox.recv().await, Some(ClientMsg::Heartbeat(_))));
+
+        outbox.send(ClientMsg::new_heartbeat()).await.unwrap();
+
+        assert!(outbox.close());
+        assert!(!outbox.close());
+        assert!(matches!(
+            outbox.try_send(ClientMsg::new_heartbeat()),
+            Err(TrySendError::Closed(_))
+        ));
+
+        // queued messages are still received after closing
+        match outbox.recv().await {
+            Some(ClientMsg::SetPresence(payload)) => assert_eq!(payload.presence.flags, UserPresenceFlags::BUSY),
+            msg => panic!("expected presence, got {msg:?}"),
+        }
+
+        assert!(matches!(outbox.recv().await, Some(ClientMsg::Heartbeat(_))));
+        assert!(outbox.recv().await.is_none());
+    }
+}
@@ -3,7 +3,11 @@ use core::{
     marker::PhantomData,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
+use std::time::Instant;
+
+use crate::api::RateLimit;
 
 // Fallback will likely be called a lot, yet does nothing, so
 // avoid the cost of boxing a future every call by using a ZST
@@ -25,3 +29,57 @@ impl<E> Future for ZSTOkFut<E> {
         Poll::Ready(Ok(()))
     }
 }
+
+/// Generic Cell Rate Algorithm state for a single rate-limited resource, see [`RateLimit`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Gcra {
+    /// Theoretical arrival time of the next request
+    tat: Option<Instant>,
+}
+
+impl Gcra {
+    /// Consumes a slot if a request at `now` conforms to the limit, otherwise returns how long to wait
+    pub fn check(&mut self, limit: &RateLimit, now: Instant) -> Result<(), Duration> {
+        let burst = limit.burst_size.get().saturating_sub(1).min(u32::MAX as u64) as u32;
+        let tolerance = limit.emission_interval.saturating_mul(burst);
+
+        let tat = match self.tat {
+            Some(tat) => tat.max(now),
+            None => now,
+        };
+
+        match (tat - now).checked_sub(tolerance) {
+            Some(wait) if !wait.is_zero() => Err(wait),
+            _ => {
+                self.tat = Some(tat + limit.emission_interval);
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the state has fully replenished by `now`, such that it is equivalent to the default
+    #[must_use]
+    pub fn is_idle(&self, now: Instant) -> bool {
+        self.tat.is_none_or(|tat| tat <= now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gcra_burst() {
+        let limit = RateLimit::DEFAULT;
+        let now = Instant::now();
+
+        let mut gcra = Gcra::default();
+
+        for _ in 0..limit.burst_size.get() {
+            assert!(gcra.check(&limit, now).is_ok());
+        }
+
+        assert_eq!(gcra.check(&limit, now), Err(limit.emission_interval));
+        assert!(gcra.check(&limit, now + limit.emission_interval).is_ok());
+    }
+}
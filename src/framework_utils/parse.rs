@@ -0,0 +1,683 @@
+//! Typed parsing of arguments split by [`ArgumentSplitter`]
+//!
+//! ```rust,ignore
+//! let mut args = Arguments::new("<@1234> 1h30m spamming links");
+//!
+//! let UserMention(user) = args.next()?;
+//! let duration: Duration = args.next_or(Duration::from_secs(3600))?;
+//! let reason = args.rest();
+//! ```
+
+use core::fmt;
+use core::num::IntErrorKind;
+use core::ops::Range;
+use core::time::Duration;
+
+use smol_str::SmolStr;
+
+use crate::models::{cast_id, EmoteId, EmoteOrEmoji, RoleId, RoomId, Snowflake, UserId};
+
+use super::args::{Argument, ArgumentSplitter};
+
+/// Reason an argument could not be parsed, see [`ArgumentError`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ArgumentErrorKind {
+    /// No argument was given where one was required
+    #[error("missing {0}")]
+    Missing(&'static str),
+
+    /// The argument is not in the expected format
+    #[error("expected {0}")]
+    Invalid(&'static str),
+
+    /// The argument is in the expected format, but does not fit the type
+    #[error("{0} out of range")]
+    OutOfRange(&'static str),
+
+    /// More arguments were given than expected
+    #[error("unexpected argument")]
+    Unexpected,
+}
+
+/// Error parsing an argument, with the byte range of the argument in the original input
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{kind} at {}..{}", .range.start, .range.end)]
+pub struct ArgumentError {
+    pub kind: ArgumentErrorKind,
+
+    /// Byte range of the failing argument, including any quotes, see [`Argument::outer`].
+    /// Missing arguments have an empty range at the end of the input.
+    pub range: Range<usize>,
+}
+
+impl ArgumentError {
+    /// Formats the error below the original input, with the failing argument underlined
+    ///
+    /// ```text
+    /// <@1234> 1x30m
+    ///         ^^^^^ expected duration
+    /// ```
+    #[must_use]
+    pub fn annotate(&self, input: &str) -> String {
+        use fmt::Write;
+
+        let start = input.get(..self.range.start).map_or(0, |s| s.chars().count());
+        let len = input.get(self.range.clone()).map_or(0, |s| s.chars().count()).max(1);
+
+        let mut out = String::with_capacity(input.len() * 2 + 32);
+
+        let _ = write!(out, "{input}\n{:start$}{:^<len$} {}", "", "", self.kind);
+
+        out
+    }
+}
+
+/// Types that can be parsed from a single argument
+pub trait FromArgument<'a>: Sized {
+    /// Short description of what is expected, used in error messages, e.g. `"integer"`
+    const EXPECTED: &'static str;
+
+    fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind>;
+}
+
+/// Cursor over split arguments, parsing them into typed values one at a time
+pub struct Arguments<'a> {
+    splitter: ArgumentSplitter<'a>,
+    pos: usize,
+}
+
+impl<'a> From<ArgumentSplitter<'a>> for Arguments<'a> {
+    fn from(splitter: ArgumentSplitter<'a>) -> Self {
+        Arguments { splitter, pos: 0 }
+    }
+}
+
+impl<'a> Arguments<'a> {
+    #[must_use]
+    pub fn new(input: &'a str) -> Self {
+        ArgumentSplitter::split(input).into()
+    }
+
+    /// The original input
+    #[must_use]
+    pub fn orig(&self) -> &'a str {
+        self.splitter.orig()
+    }
+
+    /// Arguments not yet consumed
+    #[must_use]
+    pub fn remaining(&self) -> &[Argument<'a>] {
+        &self.splitter.arguments()[self.pos..]
+    }
+
+    /// Whether all arguments have been consumed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.remaining().is_empty()
+    }
+
+    fn missing<T: FromArgument<'a>>(&self) -> ArgumentError {
+        let end = self.orig().len();
+
+        ArgumentError {
+            kind: ArgumentErrorKind::Missing(T::EXPECTED),
+            range: end..end,
+        }
+    }
+
+    /// Parses the next argument without consuming it
+    pub fn peek<T: FromArgument<'a>>(&self) -> Result<T, ArgumentError> {
+        let Some(arg) = self.remaining().first() else {
+            return Err(self.missing::<T>());
+        };
+
+        T::from_argument(arg).map_err(|kind| ArgumentError {
+            kind,
+            range: arg.outer(),
+        })
+    }
+
+    /// Parses and consumes the next argument, which is required
+    #[allow(clippy::should_implement_trait)]
+    pub fn next<T: FromArgument<'a>>(&mut self) -> Result<T, ArgumentError> {
+        let value = self.peek()?;
+        self.pos += 1;
+        Ok(value)
+    }
+
+    /// Parses and consumes the next argument if it's present and valid. Otherwise, the argument
+    /// is left for the next parser and `None` is returned.
+    pub fn optional<T: FromArgument<'a>>(&mut self) -> Option<T> {
+        self.next().ok()
+    }
+
+    /// Parses and consumes the next argument, or returns the given default value if there are no more arguments
+    ///
+    /// Unlike [`optional`](Self::optional), an argument that is present but invalid is an error.
+    pub fn next_or<T: FromArgument<'a>>(&mut self, default: T) -> Result<T, ArgumentError> {
+        match self.is_empty() {
+            true => Ok(default),
+            false => self.next(),
+        }
+    }
+
+    /// Like [`next_or`](Self::next_or), using the type's default value
+    pub fn next_or_default<T: FromArgument<'a> + Default>(&mut self) -> Result<T, ArgumentError> {
+        self.next_or(T::default())
+    }
+
+    /// Parses and consumes arguments for as long as they are valid
+    pub fn repeated<T: FromArgument<'a>>(&mut self) -> Vec<T> {
+        core::iter::from_fn(|| self.optional()).collect()
+    }
+
+    /// Like [`repeated`](Self::repeated), but requires at least one argument
+    pub fn repeated1<T: FromArgument<'a>>(&mut self) -> Result<Vec<T>, ArgumentError> {
+        let first = self.next()?;
+
+        let mut values = vec![first];
+        values.extend(core::iter::from_fn(|| self.optional()));
+
+        Ok(values)
+    }
+
+    /// Consumes all remaining arguments, returning the rest of the input as it was written,
+    /// including any quotes, or an empty string if nothing remains
+    pub fn rest(&mut self) -> &'a str {
+        let Some(arg) = self.remaining().first() else {
+            return "";
+        };
+
+        let start = arg.outer().start;
+        self.pos = self.splitter.arguments().len();
+
+        self.orig()[start..].trim_end()
+    }
+
+    /// Ensures all arguments have been consumed, erroring on the first unexpected argument otherwise
+    pub fn finish(&self) -> Result<(), ArgumentError> {
+        match self.remaining().first() {
+            None => Ok(()),
+            Some(arg) => Err(ArgumentError {
+                kind: ArgumentErrorKind::Unexpected,
+                range: arg.outer(),
+            }),
+        }
+    }
+}
+
+impl<'a> FromArgument<'a> for &'a str {
+    const EXPECTED: &'static str = "text";
+
+    /// The argument text, without any quotes
+    fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+        Ok(arg.inner_str())
+    }
+}
+
+impl<'a> FromArgument<'a> for String {
+    const EXPECTED: &'static str = "text";
+
+    fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+        Ok(arg.inner_str().to_owned())
+    }
+}
+
+impl<'a> FromArgument<'a> for SmolStr {
+    const EXPECTED: &'static str = "text";
+
+    fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+        Ok(SmolStr::new(arg.inner_str()))
+    }
+}
+
+/// Text that must have been quoted, such as `"like this"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quoted<'a>(pub &'a str);
+
+impl<'a> FromArgument<'a> for Quoted<'a> {
+    const EXPECTED: &'static str = "quoted text";
+
+    fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+        match arg.is_quoted() {
+            true => Ok(Quoted(arg.inner_str())),
+            false => Err(ArgumentErrorKind::Invalid(Self::EXPECTED)),
+        }
+    }
+}
+
+macro_rules! impl_from_argument_int {
+    ($($ty:ty),*) => {$(
+        impl<'a> FromArgument<'a> for $ty {
+            const EXPECTED: &'static str = "integer";
+
+            fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+                arg.inner_str().parse().map_err(|e: core::num::ParseIntError| match e.kind() {
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => ArgumentErrorKind::OutOfRange(Self::EXPECTED),
+                    _ => ArgumentErrorKind::Invalid(Self::EXPECTED),
+                })
+            }
+        }
+    )*};
+}
+
+impl_from_argument_int!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+macro_rules! impl_from_argument_float {
+    ($($ty:ty),*) => {$(
+        impl<'a> FromArgument<'a> for $ty {
+            const EXPECTED: &'static str = "number";
+
+            fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+                match arg.inner_str().parse::<$ty>() {
+                    Ok(value) if value.is_finite() => Ok(value),
+                    _ => Err(ArgumentErrorKind::Invalid(Self::EXPECTED)),
+                }
+            }
+        }
+    )*};
+}
+
+impl_from_argument_float!(f32, f64);
+
+impl<'a> FromArgument<'a> for bool {
+    const EXPECTED: &'static str = "yes or no";
+
+    /// Accepts `true`/`false`, `yes`/`no`, `y`/`n`, `on`/`off` and `1`/`0`, ignoring case
+    fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+        const TRUE: &[&str] = &["true", "yes", "y", "on", "1"];
+        const FALSE: &[&str] = &["false", "no", "n", "off", "0"];
+
+        let value = arg.inner_str();
+
+        if TRUE.iter().any(|t| t.eq_ignore_ascii_case(value)) {
+            Ok(true)
+        } else if FALSE.iter().any(|f| f.eq_ignore_ascii_case(value)) {
+            Ok(false)
+        } else {
+            Err(ArgumentErrorKind::Invalid(Self::EXPECTED))
+        }
+    }
+}
+
+impl<'a> FromArgument<'a> for Duration {
+    const EXPECTED: &'static str = "duration";
+
+    /// Accepts one or more numbers with units, such as `5m`, `1h30m` or `1w2d`. A lone number is in seconds.
+    ///
+    /// Units are `w`, `d`, `h`, `m`, `s` and `ms`.
+    fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+        const INVALID: ArgumentErrorKind = ArgumentErrorKind::Invalid("duration");
+        const OUT_OF_RANGE: ArgumentErrorKind = ArgumentErrorKind::OutOfRange("duration");
+
+        let mut s = arg.inner_str();
+
+        if s.is_empty() {
+            return Err(INVALID);
+        }
+
+        if let Ok(secs) = s.parse::<u64>() {
+            return Ok(Duration::from_secs(secs));
+        }
+
+        let mut total = Duration::ZERO;
+
+        while !s.is_empty() {
+            let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let units = s[digits..].find(|c: char| c.is_ascii_digit()).map_or(s.len(), |i| i + digits);
+
+            let value: u64 = match s[..digits].parse() {
+                Ok(value) => value,
+                Err(ref e) if *e.kind() == IntErrorKind::PosOverflow => return Err(OUT_OF_RANGE),
+                Err(_) => return Err(INVALID),
+            };
+
+            let unit = match &s[digits..units] {
+                "w" => Duration::from_secs(7 * 24 * 60 * 60),
+                "d" => Duration::from_secs(24 * 60 * 60),
+                "h" => Duration::from_secs(60 * 60),
+                "m" => Duration::from_secs(60),
+                "s" => Duration::from_secs(1),
+                "ms" => Duration::from_millis(1),
+                _ => return Err(INVALID),
+            };
+
+            let value = u32::try_from(value).ok().and_then(|value| unit.checked_mul(value)).ok_or(OUT_OF_RANGE)?;
+
+            total = total.checked_add(value).ok_or(OUT_OF_RANGE)?;
+            s = &s[units..];
+        }
+
+        Ok(total)
+    }
+}
+
+/// Kind of a [`Mention`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MentionKind {
+    /// `<@id>`
+    User,
+    /// `<@&id>`
+    Role,
+    /// `<#id>`
+    Room,
+}
+
+impl MentionKind {
+    const fn expected(self) -> &'static str {
+        match self {
+            MentionKind::User => "user",
+            MentionKind::Role => "role",
+            MentionKind::Room => "room",
+        }
+    }
+}
+
+/// Mention or custom emote enclosed in angle brackets, see [`parse_tag`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Tag<'a> {
+    Mention(Mention),
+    Emote { name: &'a str, id: EmoteId },
+}
+
+/// Parses an ID consisting only of ASCII digits
+pub(crate) fn parse_snowflake(id: &str) -> Option<Snowflake> {
+    match !id.is_empty() && id.bytes().all(|c| c.is_ascii_digit()) {
+        true => id.parse().ok(),
+        false => None,
+    }
+}
+
+/// Parses the inside of a mention such as `<@&1234>`, or a custom emote such as `<:name:1234>`,
+/// without the angle brackets. Emote names may only contain ASCII letters, digits and underscores.
+///
+/// This is shared with [`md::parse`](crate::framework::md::parse), so both agree on what is a mention.
+pub(crate) fn parse_tag(inner: &str) -> Option<Tag<'_>> {
+    if let Some(id) = inner.strip_prefix("@&") {
+        return parse_snowflake(id).map(|id| Tag::Mention(Mention::Role(cast_id(id))));
+    }
+
+    if let Some(id) = inner.strip_prefix('@') {
+        return parse_snowflake(id).map(|id| Tag::Mention(Mention::User(cast_id(id))));
+    }
+
+    if let Some(id) = inner.strip_prefix('#') {
+        return parse_snowflake(id).map(|id| Tag::Mention(Mention::Room(cast_id(id))));
+    }
+
+    let (name, id) = inner.strip_prefix(':')?.split_once(':')?;
+
+    match !name.is_empty() && name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_') {
+        true => parse_snowflake(id).map(|id| Tag::Emote { name, id: cast_id(id) }),
+        false => None,
+    }
+}
+
+/// Parses a mention or custom emote, including the angle brackets
+fn parse_bracketed(s: &str) -> Option<Tag<'_>> {
+    parse_tag(s.strip_prefix('<')?.strip_suffix('>')?)
+}
+
+/// Parses either a mention of the given kind or a plain ID
+fn parse_id(s: &str, kind: Option<MentionKind>, expected: &'static str) -> Result<Snowflake, ArgumentErrorKind> {
+    let id = match s.starts_with('<') {
+        true => match parse_bracketed(s) {
+            Some(Tag::Mention(mention)) if kind.is_none_or(|kind| kind == mention.kind()) => Some(mention.id()),
+            _ => None,
+        },
+        false => parse_snowflake(s),
+    };
+
+    id.ok_or(ArgumentErrorKind::Invalid(expected))
+}
+
+impl<'a> FromArgument<'a> for Snowflake {
+    const EXPECTED: &'static str = "ID";
+
+    /// Accepts a plain ID or any kind of mention
+    ///
+    /// Without the `strict_ids` feature, [`UserId`], [`RoleId`] and [`RoomId`] are aliases of
+    /// [`Snowflake`], so use [`Mention`] to tell them apart.
+    fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+        parse_id(arg.inner_str(), None, Self::EXPECTED)
+    }
+}
+
+#[cfg(feature = "strict_ids")]
+macro_rules! impl_from_argument_id {
+    ($($ty:ident => $kind:ident,)*) => {$(
+        impl<'a> FromArgument<'a> for $ty {
+            const EXPECTED: &'static str = MentionKind::$kind.expected();
+
+            fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+                parse_id(arg.inner_str(), Some(MentionKind::$kind), Self::EXPECTED).map($ty)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "strict_ids")]
+impl_from_argument_id! {
+    UserId => User,
+    RoleId => Role,
+    RoomId => Room,
+}
+
+/// A user, role or room, given by mention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mention {
+    User(UserId),
+    Role(RoleId),
+    Room(RoomId),
+}
+
+impl Mention {
+    #[must_use]
+    pub const fn kind(&self) -> MentionKind {
+        match self {
+            Mention::User(_) => MentionKind::User,
+            Mention::Role(_) => MentionKind::Role,
+            Mention::Room(_) => MentionKind::Room,
+        }
+    }
+
+    /// ID of the mentioned user, role or room
+    #[must_use]
+    pub fn id(&self) -> Snowflake {
+        match *self {
+            Mention::User(id) => cast_id(id),
+            Mention::Role(id) => cast_id(id),
+            Mention::Room(id) => cast_id(id),
+        }
+    }
+}
+
+impl<'a> FromArgument<'a> for Mention {
+    const EXPECTED: &'static str = "mention";
+
+    fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+        match parse_bracketed(arg.inner_str()) {
+            Some(Tag::Mention(mention)) => Ok(mention),
+            _ => Err(ArgumentErrorKind::Invalid(Self::EXPECTED)),
+        }
+    }
+}
+
+/// A user given by mention or ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UserMention(pub UserId);
+
+/// A role given by mention or ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RoleMention(pub RoleId);
+
+/// A room given by mention or ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RoomMention(pub RoomId);
+
+macro_rules! impl_from_argument_mention {
+    ($($ty:ident => $kind:ident,)*) => {$(
+        impl<'a> FromArgument<'a> for $ty {
+            const EXPECTED: &'static str = MentionKind::$kind.expected();
+
+            fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+                parse_id(arg.inner_str(), Some(MentionKind::$kind), Self::EXPECTED).map(|id| $ty(cast_id(id)))
+            }
+        }
+    )*};
+}
+
+impl_from_argument_mention! {
+    UserMention => User,
+    RoleMention => Role,
+    RoomMention => Room,
+}
+
+impl<'a> FromArgument<'a> for EmoteOrEmoji {
+    const EXPECTED: &'static str = "emote or emoji";
+
+    /// Accepts a custom emote as `<:name:id>`, or a Unicode emoji
+    ///
+    /// Emoji are only checked to consist of characters from the emoji blocks, along with joiners, variation
+    /// selectors and keycaps, not that they form a valid emoji sequence.
+    fn from_argument(arg: &Argument<'a>) -> Result<Self, ArgumentErrorKind> {
+        const INVALID: ArgumentErrorKind = ArgumentErrorKind::Invalid("emote or emoji");
+
+        let s = arg.inner_str();
+
+        if s.starts_with('<') {
+            return match parse_bracketed(s) {
+                Some(Tag::Emote { id, .. }) => Ok(EmoteOrEmoji::Emote { emote: id }),
+                _ => Err(INVALID),
+            };
+        }
+
+        // digits, `#` and `*` are only part of keycap sequences such as 1️⃣
+        let keycap = s.ends_with('\u{20E3}');
+
+        if s.is_empty() || !s.chars().all(|c| is_emoji_char(c) || (keycap && matches!(c, '0'..='9' | '#' | '*'))) {
+            return Err(INVALID);
+        }
+
+        Ok(EmoteOrEmoji::Emoji { emoji: SmolStr::new(s) })
+    }
+}
+
+/// Whether the character may appear within an emoji, excluding the ASCII characters of keycaps
+const fn is_emoji_char(c: char) -> bool {
+    matches!(c,
+        '\u{A9}' | '\u{AE}' | '\u{203C}' | '\u{2049}' | '\u{2122}' | '\u{2139}'
+        | '\u{3030}' | '\u{303D}' | '\u{3297}' | '\u{3299}'
+        | '\u{2190}'..='\u{21FF}'     // arrows
+        | '\u{2300}'..='\u{23FF}'     // miscellaneous technical
+        | '\u{24C2}'
+        | '\u{25A0}'..='\u{27BF}'     // geometric shapes, miscellaneous symbols and dingbats
+        | '\u{2900}'..='\u{297F}'     // supplemental arrows
+        | '\u{2B00}'..='\u{2BFF}'     // miscellaneous symbols and arrows
+        | '\u{1F000}'..='\u{1FAFF}'   // pictographs, emoticons, regional indicators and skin tones
+        | '\u{200D}'                  // zero width joiner
+        | '\u{20E3}'                  // combining enclosing keycap
+        | '\u{FE0E}' | '\u{FE0F}'     // variation selectors
+        | '\u{E0020}'..='\u{E007F}'   // tags, as used by subdivision flags
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arguments() {
+        let mut args = Arguments::new(r#"12 -3 yes 1h30m <@1234> <@&5> <#6> "quoted text" 🦀 <:crab:7> a b  c  "#);
+
+        assert_eq!(args.next::<u8>(), Ok(12));
+        assert_eq!(args.next::<i64>(), Ok(-3));
+        assert_eq!(args.next::<bool>(), Ok(true));
+        assert_eq!(args.next::<Duration>(), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(args.next::<UserMention>().map(|u| u.0.to_string()), Ok("1234".to_owned()));
+        assert!(matches!(args.next::<Mention>(), Ok(Mention::Role(_))));
+        assert!(matches!(args.next::<Mention>(), Ok(Mention::Room(_))));
+        assert_eq!(args.next::<Quoted>(), Ok(Quoted("quoted text")));
+        assert!(matches!(args.next::<EmoteOrEmoji>(), Ok(EmoteOrEmoji::Emoji { ref emoji }) if emoji == "🦀"));
+        assert!(matches!(args.next::<EmoteOrEmoji>(), Ok(EmoteOrEmoji::Emote { .. })));
+        assert_eq!(args.rest(), "a b  c");
+        assert!(args.finish().is_ok());
+        assert_eq!(args.rest(), "");
+    }
+
+    #[test]
+    fn test_optional_arguments() {
+        let mut args = Arguments::new("1 2 3 x 5m");
+
+        assert_eq!(args.repeated::<u32>(), vec![1, 2, 3]);
+        assert_eq!(args.optional::<u32>(), None);
+        assert_eq!(args.next::<&str>(), Ok("x"));
+        assert_eq!(args.next_or_default::<Duration>(), Ok(Duration::from_secs(300)));
+        assert_eq!(args.next_or(true), Ok(true));
+
+        // present but invalid as the last argument
+        let mut args = Arguments::new("abc");
+        assert!(args.next_or(1u8).is_err());
+    }
+
+    #[test]
+    fn test_argument_errors() {
+        let input = r#"300 "2x" <#1>"#;
+        let mut args = Arguments::new(input);
+
+        let err = args.next::<u8>().unwrap_err();
+        assert_eq!(err.kind, ArgumentErrorKind::OutOfRange("integer"));
+        assert_eq!(err.range, 0..3);
+
+        args.next::<u16>().unwrap();
+
+        let err = args.next::<Duration>().unwrap_err();
+        assert_eq!(err.range, 4..8);
+        assert_eq!(err.annotate(input), "300 \"2x\" <#1>\n    ^^^^ expected duration");
+
+        args.next::<Duration>().unwrap_err();
+        args.next::<&str>().unwrap();
+
+        assert_eq!(
+            args.next::<UserMention>().unwrap_err().kind,
+            ArgumentErrorKind::Invalid("user")
+        );
+        assert_eq!(args.finish().unwrap_err().range, 9..13);
+
+        args.next::<RoomMention>().unwrap();
+        assert_eq!(args.next::<bool>().unwrap_err().range, 13..13);
+    }
+
+    #[cfg(feature = "strict_ids")]
+    #[test]
+    fn test_strict_id_arguments() {
+        let mut args = Arguments::new("<@1> 2 <@&3> <#4> <@5>");
+
+        assert_eq!(args.next::<UserId>().map(|id| id.to_string()), Ok("1".to_owned()));
+        assert_eq!(args.next::<UserId>().map(|id| id.to_string()), Ok("2".to_owned()));
+        assert_eq!(args.next::<RoleId>().map(|id| id.to_string()), Ok("3".to_owned()));
+        assert_eq!(args.next::<RoomId>().map(|id| id.to_string()), Ok("4".to_owned()));
+
+        // each kind of ID only accepts its own kind of mention
+        assert_eq!(args.next::<RoleId>().unwrap_err().kind, ArgumentErrorKind::Invalid("role"));
+        assert_eq!(args.next::<RoomId>().unwrap_err().kind, ArgumentErrorKind::Invalid("room"));
+        assert_eq!(args.next::<Snowflake>().map(|id| id.to_string()), Ok("5".to_owned()));
+    }
+
+    #[test]
+    fn test_emote_or_emoji() {
+        let parse = |s: &str| Arguments::new(s).next::<EmoteOrEmoji>();
+
+        for emoji in ["🦀", "👍🏽", "🏳️‍🌈", "1️⃣", "❤️", "🇳🇴"] {
+            assert!(matches!(parse(emoji), Ok(EmoteOrEmoji::Emoji { .. })), "{emoji}");
+        }
+
+        // same rules as mentions within markdown
+        for invalid in ["日本", "abc", "1", ":1", "<:a-b:1>", "<::1>", "<:crab:x>", "<@1>"] {
+            assert!(parse(invalid).is_err(), "{invalid}");
+        }
+
+        assert!(matches!(parse_tag("@&12"), Some(Tag::Mention(Mention::Role(_)))));
+        assert_eq!(parse_tag("@+12"), None);
+    }
+}
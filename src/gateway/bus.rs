@@ -0,0 +1,384 @@
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use futures::task::AtomicWaker;
+use futures::{Stream, StreamExt};
+
+use crate::models::gateway::message::{ServerMsg, ServerMsgOpcode};
+use crate::models::{PartyId, RoomId, UserId};
+
+/// Default number of events queued per subscriber before the [`LagPolicy`] applies
+pub const DEFAULT_SUBSCRIBER_CAPACITY: usize = 256;
+
+/// What to do when a subscriber's queue is full because it isn't keeping up
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LagPolicy {
+    /// Drop the oldest queued event to make room for the new one
+    #[default]
+    DropOldest,
+    /// Drop the new event, keeping the queued events
+    DropNewest,
+    /// Unsubscribe, ending the subscription stream after any queued events
+    Disconnect,
+}
+
+/// Selects which events are delivered to a [`Subscription`]
+///
+/// All conditions must match, and an empty filter matches every event. Events that don't pertain to
+/// a party, room or user never match a filter with the respective condition.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EventFilter {
+    opcodes: Vec<ServerMsgOpcode>,
+    party_id: Option<PartyId>,
+    room_id: Option<RoomId>,
+    user_id: Option<UserId>,
+}
+
+impl EventFilter {
+    /// Creates a filter that matches every event
+    #[must_use]
+    pub fn new() -> Self {
+        EventFilter::default()
+    }
+
+    /// Only match events with this opcode. May be given multiple times to match any of them.
+    #[must_use]
+    pub fn opcode(mut self, opcode: ServerMsgOpcode) -> Self {
+        if !self.opcodes.contains(&opcode) {
+            self.opcodes.push(opcode);
+        }
+
+        self
+    }
+
+    /// Only match events within this party, see [`ServerMsg::party_id`]
+    #[must_use]
+    pub fn party(mut self, party_id: PartyId) -> Self {
+        self.party_id = Some(party_id);
+        self
+    }
+
+    /// Only match events within this room, see [`ServerMsg::room_id`]
+    #[must_use]
+    pub fn room(mut self, room_id: RoomId) -> Self {
+        self.room_id = Some(room_id);
+        self
+    }
+
+    /// Only match events originating from this user, see [`ServerMsg::user_id`]
+    #[must_use]
+    pub fn user(mut self, user_id: UserId) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    #[must_use]
+    pub fn matches(&self, msg: &ServerMsg) -> bool {
+        fn check<T: PartialEq>(expected: Option<T>, actual: impl FnOnce() -> Option<T>) -> bool {
+            expected.is_none() || expected == actual()
+        }
+
+        (self.opcodes.is_empty() || msg.opcode().is_some_and(|opcode| self.opcodes.contains(&opcode)))
+            && check(self.party_id, || msg.party_id())
+            && check(self.room_id, || msg.room_id())
+            && check(self.user_id, || msg.user_id())
+    }
+}
+
+/// State shared between the bus and a single subscription
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<VecDeque<Arc<ServerMsg>>>,
+    waker: AtomicWaker,
+    closed: AtomicBool,
+    lagged: AtomicU64,
+}
+
+impl Shared {
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.waker.wake();
+    }
+}
+
+/// Predicate deciding which events are delivered to a subscriber
+type Predicate = Box<dyn Fn(&ServerMsg) -> bool + Send + Sync>;
+
+struct Subscriber {
+    predicate: Predicate,
+    capacity: usize,
+    policy: LagPolicy,
+    shared: Arc<Shared>,
+}
+
+impl Subscriber {
+    /// Queues the event if it matches, returning `false` if the subscriber should be removed
+    fn deliver(&self, msg: &Arc<ServerMsg>) -> bool {
+        if self.shared.closed.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        if !(self.predicate)(msg) {
+            return true;
+        }
+
+        let mut queue = self.shared.queue.lock().unwrap_or_else(|e| e.into_inner());
+
+        if queue.len() >= self.capacity {
+            match self.policy {
+                LagPolicy::DropOldest => {
+                    queue.pop_front();
+                }
+                LagPolicy::DropNewest => {
+                    self.shared.lagged.fetch_add(1, Ordering::Relaxed);
+                    return true;
+                }
+                LagPolicy::Disconnect => {
+                    drop(queue);
+                    self.shared.close();
+                    return false;
+                }
+            }
+
+            self.shared.lagged.fetch_add(1, Ordering::Relaxed);
+        }
+
+        queue.push_back(msg.clone());
+        drop(queue);
+
+        self.shared.waker.wake();
+
+        true
+    }
+}
+
+struct EventBusInner {
+    subscribers: Mutex<Vec<Subscriber>>,
+    capacity: usize,
+}
+
+/// Fans out gateway events to any number of subscribers, each with their own [`EventFilter`]
+///
+/// Events are shared between subscribers as `Arc<ServerMsg>`. Each subscriber has a bounded queue,
+/// and a [`LagPolicy`] decides what happens when it fills up, so one slow subscriber never holds up the others.
+///
+/// ```rust,ignore
+/// let bus = EventBus::new();
+///
+/// let mut messages = bus.subscribe(EventFilter::new().opcode(ServerMsgOpcode::MessageCreate).room(room_id));
+///
+/// tokio::spawn(bus.clone().forward(gateway.filter_map(|res| async move { res.ok() })));
+///
+/// while let Some(msg) = messages.next().await {
+///     // ...
+/// }
+/// ```
+#[derive(Clone)]
+pub struct EventBus(Arc<EventBusInner>);
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+impl EventBus {
+    /// Creates a bus where each subscriber queues up to [`DEFAULT_SUBSCRIBER_CAPACITY`] events
+    #[must_use]
+    pub fn new() -> Self {
+        EventBus::with_capacity(DEFAULT_SUBSCRIBER_CAPACITY)
+    }
+
+    /// Creates a bus where each subscriber queues up to `capacity` events by default
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        EventBus(Arc::new(EventBusInner {
+            subscribers: Mutex::new(Vec::new()),
+            capacity: capacity.max(1),
+        }))
+    }
+
+    /// Subscribe to events matching the filter, dropping the oldest events if the subscriber lags behind
+    #[must_use]
+    pub fn subscribe(&self, filter: EventFilter) -> Subscription {
+        self.subscribe_with(filter, self.0.capacity, LagPolicy::default())
+    }
+
+    /// Subscribe to events matching the filter, with a specific queue capacity and [`LagPolicy`]
+    #[must_use]
+    pub fn subscribe_with(&self, filter: EventFilter, capacity: usize, policy: LagPolicy) -> Subscription {
+        self.subscribe_where(move |msg| filter.matches(msg), capacity, policy)
+    }
+
+    /// Subscribe to events for which the predicate returns `true`, with a specific queue capacity and [`LagPolicy`]
+    ///
+    /// The predicate is called while publishing, so it should be quick and must not touch the bus itself.
+    #[must_use]
+    pub fn subscribe_where<F>(&self, predicate: F, capacity: usize, policy: LagPolicy) -> Subscription
+    where
+        F: Fn(&ServerMsg) -> bool + Send + Sync + 'static,
+    {
+        let shared = Arc::new(Shared::default());
+
+        self.0.subscribers.lock().unwrap_or_else(|e| e.into_inner()).push(Subscriber {
+            predicate: Box::new(predicate),
+            capacity: capacity.max(1),
+            policy,
+            shared: shared.clone(),
+        });
+
+        Subscription {
+            shared,
+            bus: Arc::downgrade(&self.0),
+        }
+    }
+
+    /// Number of active subscribers, not counting those disconnected since the last published event
+    #[must_use]
+    pub fn subscribers(&self) -> usize {
+        self.0.subscribers.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Delivers an event to all matching subscribers, returning it as shared
+    pub fn publish(&self, msg: ServerMsg) -> Arc<ServerMsg> {
+        let msg = Arc::new(msg);
+
+        self.0.subscribers.lock().unwrap_or_else(|e| e.into_inner()).retain(|sub| sub.deliver(&msg));
+
+        msg
+    }
+
+    /// Publishes every event from the stream until it ends, such as a
+    /// [`GatewayConnection`](super::GatewayConnection) with errors filtered out.
+    pub async fn forward<S>(self, stream: S)
+    where
+        S: Stream<Item = ServerMsg>,
+    {
+        let mut stream = core::pin::pin!(stream);
+
+        while let Some(msg) = stream.next().await {
+            self.publish(msg);
+        }
+    }
+
+    /// Ends all subscriptions after their queued events have been received
+    pub fn close(&self) {
+        for sub in self.0.subscribers.lock().unwrap_or_else(|e| e.into_inner()).drain(..) {
+            sub.shared.close();
+        }
+    }
+}
+
+/// Stream of events from an [`EventBus`], ending when unsubscribed
+///
+/// Dropping the subscription unsubscribes it.
+#[must_use = "streams do nothing unless polled"]
+pub struct Subscription {
+    shared: Arc<Shared>,
+    bus: Weak<EventBusInner>,
+}
+
+impl Subscription {
+    /// Takes the next queued event, if any, without waiting
+    pub fn try_recv(&mut self) -> Option<Arc<ServerMsg>> {
+        self.shared.queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front()
+    }
+
+    /// Number of events dropped so far because this subscriber lagged behind
+    #[must_use]
+    pub fn lagged(&self) -> u64 {
+        self.shared.lagged.load(Ordering::Relaxed)
+    }
+
+    /// Whether the subscription has ended, though queued events may remain
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::SeqCst)
+    }
+}
+
+impl Stream for Subscription {
+    type Item = Arc<ServerMsg>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(msg) = self.try_recv() {
+            return Poll::Ready(Some(msg));
+        }
+
+        self.shared.waker.register(cx.waker());
+
+        // check again in case an event arrived before the waker was registered
+        match self.try_recv() {
+            Some(msg) => Poll::Ready(Some(msg)),
+            None if self.is_closed() => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+
+        // unregister right away, rather than waiting for the bus to notice upon the next event
+        if let Some(bus) = self.bus.upgrade() {
+            let mut subscribers = bus.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+
+            subscribers.retain(|sub| !Arc::ptr_eq(&sub.shared, &self.shared));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fanout_and_lag() {
+        let bus = EventBus::new();
+
+        let mut all = bus.subscribe(EventFilter::new());
+        let mut pins = bus.subscribe(EventFilter::new().opcode(ServerMsgOpcode::RoomPinsUpdate));
+        let mut newest = bus.subscribe_with(EventFilter::new(), 2, LagPolicy::DropNewest);
+        let mut disconnect = bus.subscribe_with(EventFilter::new(), 2, LagPolicy::Disconnect);
+
+        bus.publish(ServerMsg::new_heartbeat_ack());
+        bus.publish(ServerMsg::new_room_pins_update());
+        bus.publish(ServerMsg::new_heartbeat_ack());
+
+        assert_eq!(bus.subscribers(), 3);
+        assert!(disconnect.is_closed());
+        assert_eq!(
+            disconnect.try_recv().and_then(|msg| msg.opcode()),
+            Some(ServerMsgOpcode::HeartbeatAck)
+        );
+
+        assert_eq!(newest.lagged(), 1);
+        assert_eq!(
+            newest.try_recv().and_then(|msg| msg.opcode()),
+            Some(ServerMsgOpcode::HeartbeatAck)
+        );
+        assert_eq!(
+            newest.try_recv().and_then(|msg| msg.opcode()),
+            Some(ServerMsgOpcode::RoomPinsUpdate)
+        );
+        assert!(newest.try_recv().is_none());
+
+        assert_eq!(
+            pins.try_recv().and_then(|msg| msg.opcode()),
+            Some(ServerMsgOpcode::RoomPinsUpdate)
+        );
+        assert!(pins.try_recv().is_none());
+
+        drop(pins);
+        assert_eq!(bus.subscribers(), 2);
+
+        bus.publish(ServerMsg::new_heartbeat_ack());
+
+        assert_eq!(all.lagged(), 0);
+        assert_eq!((0..5).filter_map(|_| all.try_recv()).count(), 4);
+    }
+}
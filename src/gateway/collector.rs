@@ -0,0 +1,268 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt};
+use tokio::time::Sleep;
+
+use crate::models::events::{TypingStart, UserReactionEvent};
+use crate::models::gateway::message::ServerMsg;
+use crate::models::{Arc as ModelArc, Message};
+
+use super::{EventBus, LagPolicy, Subscription};
+
+/// Stream of events matching a predicate, ending after a limit or timeout, see [`EventBus::collect`]
+///
+/// The collector unregisters from the bus as soon as it ends or is dropped.
+#[must_use = "streams do nothing unless polled"]
+pub struct Collector {
+    sub: Option<Subscription>,
+    remaining: usize,
+    deadline: Pin<Box<Sleep>>,
+}
+
+impl Collector {
+    /// Number of events that may still be collected before the limit is reached
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        match self.sub {
+            Some(_) => self.remaining,
+            None => 0,
+        }
+    }
+
+    /// Stops collecting, unregistering from the bus. Already collected events are discarded.
+    pub fn stop(&mut self) {
+        self.sub = None;
+    }
+}
+
+impl Stream for Collector {
+    type Item = Arc<ServerMsg>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        let Some(ref mut sub) = this.sub else {
+            return Poll::Ready(None);
+        };
+
+        // events that arrived before the deadline are still yielded
+        match sub.poll_next_unpin(cx) {
+            Poll::Ready(Some(msg)) => {
+                this.remaining -= 1;
+
+                if this.remaining == 0 {
+                    this.sub = None;
+                }
+
+                Poll::Ready(Some(msg))
+            }
+            Poll::Pending if this.deadline.as_mut().poll(cx).is_pending() => Poll::Pending,
+            _ => {
+                this.sub = None;
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+impl EventBus {
+    /// Collects up to `limit` events for which the predicate returns `true`, until the timeout elapses
+    ///
+    /// The collector is registered immediately, so events published after this call are never missed,
+    /// even if the stream is first polled later.
+    ///
+    /// ```rust,ignore
+    /// let is_reply = move |msg: &ServerMsg| matches!(msg, ServerMsg::MessageCreate(m) if m.parent == Some(msg_id));
+    ///
+    /// let mut replies = bus.collect(is_reply, 10, Duration::from_secs(30));
+    ///
+    /// while let Some(reply) = replies.next().await {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn collect<F>(&self, predicate: F, limit: usize, timeout: Duration) -> Collector
+    where
+        F: Fn(&ServerMsg) -> bool + Send + Sync + 'static,
+    {
+        let sub = match limit {
+            0 => None,
+            _ => Some(self.subscribe_where(predicate, limit, LagPolicy::DropNewest)),
+        };
+
+        Collector {
+            sub,
+            remaining: limit,
+            deadline: Box::pin(tokio::time::sleep(timeout)),
+        }
+    }
+
+    /// Waits for the next event for which the predicate returns `true`, or `None` if the timeout
+    /// elapses or the bus is closed first
+    ///
+    /// Like [`EventBus::collect`], this is registered immediately rather than when first polled.
+    pub fn wait_for<F>(&self, predicate: F, timeout: Duration) -> impl Future<Output = Option<Arc<ServerMsg>>> + Send + 'static
+    where
+        F: Fn(&ServerMsg) -> bool + Send + Sync + 'static,
+    {
+        let mut collector = self.collect(predicate, 1, timeout);
+
+        async move { collector.next().await }
+    }
+
+    /// Waits for the next [`MessageCreate`](ServerMsg::MessageCreate) event for which the predicate returns `true`
+    pub fn wait_for_message<F>(
+        &self,
+        predicate: F,
+        timeout: Duration,
+    ) -> impl Future<Output = Option<ModelArc<Message>>> + Send + 'static
+    where
+        F: Fn(&Message) -> bool + Send + Sync + 'static,
+    {
+        let mut collector = self.collect_messages(predicate, 1, timeout);
+
+        async move { collector.next().await }
+    }
+
+    /// Collects up to `limit` [`MessageCreate`](ServerMsg::MessageCreate) events for which the predicate returns `true`
+    pub fn collect_messages<F>(
+        &self,
+        predicate: F,
+        limit: usize,
+        timeout: Duration,
+    ) -> impl Stream<Item = ModelArc<Message>> + Send + Unpin
+    where
+        F: Fn(&Message) -> bool + Send + Sync + 'static,
+    {
+        self.collect_typed(as_message, predicate, limit, timeout)
+    }
+
+    /// Waits for the next [`MessageReactionAdd`](ServerMsg::MessageReactionAdd) event for which the predicate returns `true`
+    pub fn wait_for_reaction<F>(
+        &self,
+        predicate: F,
+        timeout: Duration,
+    ) -> impl Future<Output = Option<ModelArc<UserReactionEvent>>> + Send + 'static
+    where
+        F: Fn(&UserReactionEvent) -> bool + Send + Sync + 'static,
+    {
+        let mut collector = self.collect_reactions(predicate, 1, timeout);
+
+        async move { collector.next().await }
+    }
+
+    /// Collects up to `limit` [`MessageReactionAdd`](ServerMsg::MessageReactionAdd) events for which the predicate returns `true`
+    pub fn collect_reactions<F>(
+        &self,
+        predicate: F,
+        limit: usize,
+        timeout: Duration,
+    ) -> impl Stream<Item = ModelArc<UserReactionEvent>> + Send + Unpin
+    where
+        F: Fn(&UserReactionEvent) -> bool + Send + Sync + 'static,
+    {
+        self.collect_typed(as_reaction, predicate, limit, timeout)
+    }
+
+    /// Waits for the next [`TypingStart`](ServerMsg::TypingStart) event for which the predicate returns `true`
+    pub fn wait_for_typing<F>(
+        &self,
+        predicate: F,
+        timeout: Duration,
+    ) -> impl Future<Output = Option<ModelArc<TypingStart>>> + Send + 'static
+    where
+        F: Fn(&TypingStart) -> bool + Send + Sync + 'static,
+    {
+        let mut collector = self.collect_typing(predicate, 1, timeout);
+
+        async move { collector.next().await }
+    }
+
+    /// Collects up to `limit` [`TypingStart`](ServerMsg::TypingStart) events for which the predicate returns `true`
+    pub fn collect_typing<F>(
+        &self,
+        predicate: F,
+        limit: usize,
+        timeout: Duration,
+    ) -> impl Stream<Item = ModelArc<TypingStart>> + Send + Unpin
+    where
+        F: Fn(&TypingStart) -> bool + Send + Sync + 'static,
+    {
+        self.collect_typed(as_typing, predicate, limit, timeout)
+    }
+
+    fn collect_typed<T, F>(
+        &self,
+        extract: fn(&ServerMsg) -> Option<&ModelArc<T>>,
+        predicate: F,
+        limit: usize,
+        timeout: Duration,
+    ) -> impl Stream<Item = ModelArc<T>> + Send + Unpin
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.collect(move |msg| extract(msg).is_some_and(|event| predicate(event)), limit, timeout)
+            .filter_map(move |msg| futures::future::ready(extract(&msg).cloned()))
+    }
+}
+
+fn as_message(msg: &ServerMsg) -> Option<&ModelArc<Message>> {
+    match msg {
+        ServerMsg::MessageCreate(payload) => Some(&payload.inner),
+        _ => None,
+    }
+}
+
+fn as_reaction(msg: &ServerMsg) -> Option<&ModelArc<UserReactionEvent>> {
+    match msg {
+        ServerMsg::MessageReactionAdd(payload) => Some(&payload.inner),
+        _ => None,
+    }
+}
+
+fn as_typing(msg: &ServerMsg) -> Option<&ModelArc<TypingStart>> {
+    match msg {
+        ServerMsg::TypingStart(payload) => Some(&payload.inner),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::gateway::message::ServerMsgOpcode;
+
+    #[tokio::test]
+    async fn test_collectors() {
+        let bus = EventBus::new();
+
+        let is_pins = |msg: &ServerMsg| msg.opcode() == Some(ServerMsgOpcode::RoomPinsUpdate);
+
+        let mut pins = bus.collect(is_pins, 2, Duration::from_secs(30));
+        let wait = bus.wait_for(is_pins, Duration::from_secs(30));
+        let timeout = bus.wait_for(|_| false, Duration::from_millis(10));
+
+        assert_eq!(bus.subscribers(), 3);
+
+        bus.publish(ServerMsg::new_heartbeat_ack());
+        bus.publish(ServerMsg::new_room_pins_update());
+        bus.publish(ServerMsg::new_room_pins_update());
+        bus.publish(ServerMsg::new_room_pins_update());
+
+        assert!(wait.await.is_some());
+        assert!(timeout.await.is_none());
+        assert_eq!(bus.subscribers(), 1);
+
+        assert_eq!((&mut pins).count().await, 2);
+        assert_eq!(pins.remaining(), 0);
+        assert_eq!(bus.subscribers(), 0);
+
+        // dropping a collector unregisters it
+        drop(bus.collect_messages(|_| true, 10, Duration::from_secs(30)));
+        assert_eq!(bus.subscribers(), 0);
+    }
+}
@@ -0,0 +1,331 @@
+use miniz_oxide::deflate::core::{create_comp_flags_from_zip_params, CompressorOxide};
+use miniz_oxide::inflate::stream::InflateState;
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus, StreamResult};
+
+use crate::api::gateway::{GatewayCompression, GatewayQueryParams};
+
+use super::GatewayError;
+
+/// Default compression level for outgoing gateway messages, the same level used before it was configurable
+pub const DEFAULT_COMPRESSION_LEVEL: u8 = 9;
+
+/// Minimum amount of spare output capacity to give each streaming call
+const MIN_CHUNK: usize = 1024;
+
+enum CompressorKind {
+    None,
+    Zlib {
+        level: u8,
+    },
+    ZlibStream(Box<CompressorOxide>),
+    #[cfg(feature = "zstd")]
+    Zstd {
+        encoder: Box<zstd::stream::raw::Encoder<'static>>,
+        stream: bool,
+    },
+}
+
+/// Compresses outgoing gateway messages, according to the negotiated [`GatewayQueryParams`]
+///
+/// With per-message compression, each message is a complete zlib or zstd frame. With streaming compression,
+/// a single frame lasts for the whole connection, and each message ends with a flush.
+pub struct Compressor(CompressorKind);
+
+enum DecompressorKind {
+    None,
+    Zlib,
+    ZlibStream(Box<InflateState>),
+    // the zstd decoder moves on to the next frame by itself, so it handles both modes
+    #[cfg(feature = "zstd")]
+    Zstd(Box<zstd::stream::raw::Decoder<'static>>),
+}
+
+/// Decompresses incoming gateway messages, see [`Compressor`]
+pub struct Decompressor(DecompressorKind);
+
+impl Compressor {
+    /// Sets up a compressor for a new connection, with a level from `0` (none) to `10` (slowest) for zlib,
+    /// or `1` to `22` for zstd.
+    pub fn new(params: &GatewayQueryParams, level: u8) -> Result<Self, GatewayError> {
+        Ok(Compressor(match params.compress {
+            GatewayCompression::None => CompressorKind::None,
+            GatewayCompression::Zlib => CompressorKind::Zlib { level: level.min(10) },
+            GatewayCompression::ZlibStream => {
+                let flags = create_comp_flags_from_zip_params(level.min(10) as i32, 15, 0);
+
+                CompressorKind::ZlibStream(Box::new(CompressorOxide::new(flags)))
+            }
+            #[cfg(feature = "zstd")]
+            GatewayCompression::Zstd | GatewayCompression::ZstdStream => CompressorKind::Zstd {
+                encoder: Box::new(
+                    zstd::stream::raw::Encoder::new(level.min(22) as i32).map_err(|_| GatewayError::CompressionError)?,
+                ),
+                stream: params.compress.is_streaming(),
+            },
+            #[cfg(not(feature = "zstd"))]
+            GatewayCompression::Zstd | GatewayCompression::ZstdStream => {
+                return Err(GatewayError::UnsupportedCompression(params.compress))
+            }
+        }))
+    }
+
+    /// Sets up a compressor with a shared dictionary, which only applies to zstd compression.
+    #[cfg(feature = "zstd")]
+    pub fn with_dictionary(params: &GatewayQueryParams, level: u8, dict: &ZstdDictionary) -> Result<Self, GatewayError> {
+        match params.compress {
+            GatewayCompression::Zstd | GatewayCompression::ZstdStream => Ok(Compressor(CompressorKind::Zstd {
+                encoder: Box::new(
+                    zstd::stream::raw::Encoder::with_dictionary(level.min(22) as i32, &dict.data)
+                        .map_err(|_| GatewayError::CompressionError)?,
+                ),
+                stream: params.compress.is_streaming(),
+            })),
+            _ => Compressor::new(params, level),
+        }
+    }
+
+    pub fn compress(&mut self, data: Vec<u8>) -> Result<Vec<u8>, GatewayError> {
+        match self.0 {
+            CompressorKind::None => Ok(data),
+            CompressorKind::Zlib { level } => Ok(miniz_oxide::deflate::compress_to_vec_zlib(&data, level)),
+            CompressorKind::ZlibStream(ref mut compressor) => stream(&data, data.len() / 2, |input, output| {
+                miniz_oxide::deflate::stream::deflate(compressor, input, output, MZFlush::Sync)
+            }),
+            #[cfg(feature = "zstd")]
+            CompressorKind::Zstd { ref mut encoder, stream } => {
+                zstd_codec::compress(encoder, &data, !stream).map_err(|_| GatewayError::CompressionError)
+            }
+        }
+    }
+}
+
+impl Decompressor {
+    /// Sets up a decompressor for a new connection
+    pub fn new(params: &GatewayQueryParams) -> Result<Self, GatewayError> {
+        Ok(Decompressor(match params.compress {
+            GatewayCompression::None => DecompressorKind::None,
+            GatewayCompression::Zlib => DecompressorKind::Zlib,
+            GatewayCompression::ZlibStream => DecompressorKind::ZlibStream(InflateState::new_boxed(DataFormat::Zlib)),
+            #[cfg(feature = "zstd")]
+            GatewayCompression::Zstd | GatewayCompression::ZstdStream => DecompressorKind::Zstd(Box::new(
+                zstd::stream::raw::Decoder::new().map_err(|_| GatewayError::CompressionError)?,
+            )),
+            #[cfg(not(feature = "zstd"))]
+            GatewayCompression::Zstd | GatewayCompression::ZstdStream => {
+                return Err(GatewayError::UnsupportedCompression(params.compress))
+            }
+        }))
+    }
+
+    /// Sets up a decompressor with a shared dictionary, which only applies to zstd compression.
+    #[cfg(feature = "zstd")]
+    pub fn with_dictionary(params: &GatewayQueryParams, dict: &ZstdDictionary) -> Result<Self, GatewayError> {
+        match params.compress {
+            GatewayCompression::Zstd | GatewayCompression::ZstdStream => Ok(Decompressor(DecompressorKind::Zstd(Box::new(
+                zstd::stream::raw::Decoder::with_dictionary(&dict.data).map_err(|_| GatewayError::CompressionError)?,
+            )))),
+            _ => Decompressor::new(params),
+        }
+    }
+
+    pub fn decompress(&mut self, data: Vec<u8>) -> Result<Vec<u8>, GatewayError> {
+        match self.0 {
+            DecompressorKind::None => Ok(data),
+            DecompressorKind::Zlib => {
+                miniz_oxide::inflate::decompress_to_vec_zlib(&data).map_err(|_| GatewayError::CompressionError)
+            }
+            DecompressorKind::ZlibStream(ref mut state) => stream(&data, data.len() * 4, |input, output| {
+                miniz_oxide::inflate::stream::inflate(state, input, output, MZFlush::None)
+            }),
+            #[cfg(feature = "zstd")]
+            DecompressorKind::Zstd(ref mut decoder) => {
+                zstd_codec::decompress(decoder, &data).map_err(|_| GatewayError::CompressionError)
+            }
+        }
+    }
+}
+
+/// Drives a streaming (de)compressor over the whole input, growing the output as needed
+fn stream<F>(mut input: &[u8], capacity: usize, mut f: F) -> Result<Vec<u8>, GatewayError>
+where
+    F: FnMut(&[u8], &mut [u8]) -> StreamResult,
+{
+    let mut output = Vec::with_capacity(capacity.max(MIN_CHUNK));
+
+    loop {
+        let written = output.len();
+        let spare = (output.capacity() - written).max(written).max(MIN_CHUNK);
+        output.resize(written + spare, 0);
+
+        let res = f(input, &mut output[written..]);
+
+        output.truncate(written + res.bytes_written);
+        input = &input[res.bytes_consumed..];
+
+        // done once all input is consumed and the output wasn't filled, so nothing is left pending
+        let done = input.is_empty() && res.bytes_written < spare;
+
+        match res.status {
+            Ok(MZStatus::StreamEnd) => return Ok(output),
+            Ok(_) if done => return Ok(output),
+            Ok(_) => continue,
+            // no progress could be made, which is fine if there is nothing left to process
+            Err(MZError::Buf) if input.is_empty() => return Ok(output),
+            Err(_) => return Err(GatewayError::CompressionError),
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+pub use self::zstd_codec::ZstdDictionary;
+
+#[cfg(feature = "zstd")]
+mod zstd_codec {
+    use std::io;
+    use std::sync::Arc;
+
+    use zstd::stream::raw::{Decoder, Encoder, InBuffer, Operation, OutBuffer};
+
+    use super::MIN_CHUNK;
+
+    /// Shared zstd dictionary, which must be identical on both ends of the connection
+    ///
+    /// Dictionaries greatly improve the compression ratio of small messages, particularly with per-message compression.
+    #[derive(Debug, Clone)]
+    pub struct ZstdDictionary {
+        id: u32,
+        pub(super) data: Arc<[u8]>,
+    }
+
+    impl ZstdDictionary {
+        /// Loads a dictionary, returning `None` if it lacks a dictionary ID
+        #[must_use]
+        pub fn new(data: impl Into<Arc<[u8]>>) -> Option<Self> {
+            let data = data.into();
+            let id = zstd::zstd_safe::get_dict_id_from_dict(&data)?.get();
+
+            Some(ZstdDictionary { id, data })
+        }
+
+        /// Trains a dictionary of up to `max_size` bytes from sample payloads,
+        /// such as encoded messages recorded from a typical session.
+        pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> io::Result<Self> {
+            let data = zstd::dict::from_samples(samples, max_size)?;
+
+            ZstdDictionary::new(data).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "dictionary has no ID"))
+        }
+
+        /// Dictionary ID, to be sent as [`GatewayQueryParams::dict`](crate::api::gateway::GatewayQueryParams::dict)
+        #[must_use]
+        pub fn id(&self) -> u32 {
+            self.id
+        }
+
+        /// Raw dictionary data
+        #[must_use]
+        pub fn data(&self) -> &[u8] {
+            &self.data
+        }
+    }
+
+    /// Ensures there is at least [`MIN_CHUNK`] of spare capacity for the next call
+    fn reserve(output: &mut Vec<u8>) {
+        if output.capacity() - output.len() < MIN_CHUNK {
+            output.reserve(output.len().max(MIN_CHUNK));
+        }
+    }
+
+    /// Compresses a whole message, then either ends the frame or flushes it to continue the stream
+    pub fn compress(encoder: &mut Encoder<'static>, data: &[u8], end_frame: bool) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity((data.len() / 2).max(MIN_CHUNK));
+        let mut input = InBuffer::around(data);
+
+        while input.pos() < data.len() {
+            reserve(&mut output);
+
+            let pos = output.len();
+            encoder.run(&mut input, &mut OutBuffer::around_pos(&mut output, pos))?;
+        }
+
+        // repeat until nothing remains buffered within the encoder
+        loop {
+            reserve(&mut output);
+
+            let pos = output.len();
+            let mut out = OutBuffer::around_pos(&mut output, pos);
+
+            let remaining = match end_frame {
+                true => encoder.finish(&mut out, true)?,
+                false => encoder.flush(&mut out)?,
+            };
+
+            if remaining == 0 {
+                break;
+            }
+        }
+
+        if end_frame {
+            encoder.reinit()?;
+        }
+
+        Ok(output)
+    }
+
+    pub fn decompress(decoder: &mut Decoder<'static>, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(data.len() * 4);
+        let mut input = InBuffer::around(data);
+
+        loop {
+            reserve(&mut output);
+
+            let pos = output.len();
+            let mut out = OutBuffer::around_pos(&mut output, pos);
+
+            decoder.run(&mut input, &mut out)?;
+
+            // a full output buffer means more may be pending within the decoder
+            let filled = out.pos() == out.capacity();
+
+            if input.pos() == data.len() && !filled {
+                return Ok(output);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(compress: GatewayCompression) {
+        let params = GatewayQueryParams {
+            compress,
+            ..GatewayQueryParams::default()
+        };
+
+        let mut compressor = Compressor::new(&params, DEFAULT_COMPRESSION_LEVEL).unwrap();
+        let mut decompressor = Decompressor::new(&params).unwrap();
+
+        for i in 0..100 {
+            let msg = format!(r#"{{"o":19,"p":{{"id":"{i}","content":"{}"}}}}"#, "hello ".repeat(i * 50));
+
+            let compressed = compressor.compress(msg.clone().into_bytes()).unwrap();
+            let decompressed = decompressor.decompress(compressed).unwrap();
+
+            assert_eq!(decompressed, msg.as_bytes(), "{compress:?}");
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        roundtrip(GatewayCompression::None);
+        roundtrip(GatewayCompression::Zlib);
+        roundtrip(GatewayCompression::ZlibStream);
+
+        #[cfg(feature = "zstd")]
+        {
+            roundtrip(GatewayCompression::Zstd);
+            roundtrip(GatewayCompression::ZstdStream);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use core::time::Duration;
+
+use http::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, USER_AGENT};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use url::Url;
+
+#[cfg(not(feature = "zstd"))]
+use crate::api::gateway::GatewayCompression;
+use crate::api::gateway::GatewayQueryParams;
+use crate::driver::Driver;
+use crate::models::AuthToken;
+
+#[cfg(feature = "zstd")]
+use super::compression::ZstdDictionary;
+use super::compression::DEFAULT_COMPRESSION_LEVEL;
+use super::{GatewayError, GatewayProxy, Recorder};
+
+pub(crate) type WebSocket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>;
+
+/// TLS connector used for secure gateway connections, which may be constructed with custom root certificates
+#[cfg(feature = "_internal_tls")]
+pub use tokio_tungstenite::Connector as TlsConnector;
+
+/// Options for connecting to the gateway, built from the base URL of the server
+///
+/// `http` and `https` base URLs are mapped to `ws` and `wss`, respectively, and any path on
+/// the base URL is kept as a prefix to the gateway endpoint.
+///
+/// ```rust,ignore
+/// let config = GatewayConfig::new("https://lantern.chat/chat")?
+///     .params(GatewayQueryParams { compress: GatewayCompression::ZlibStream, ..Default::default() })
+///     .user_agent(HeaderValue::from_static("my-bot/1.0"))
+///     .timeout(Duration::from_secs(10))
+///     .proxy(GatewayProxy::new("socks5://localhost:1080")?);
+/// ```
+#[must_use]
+#[derive(Clone)]
+pub struct GatewayConfig {
+    url: Url,
+    params: GatewayQueryParams,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    proxy: Option<GatewayProxy>,
+    compression_level: u8,
+    recorder: Option<Recorder>,
+
+    #[cfg(feature = "zstd")]
+    dictionary: Option<ZstdDictionary>,
+
+    #[cfg(feature = "_internal_tls")]
+    tls: Option<TlsConnector>,
+}
+
+impl GatewayConfig {
+    /// Parses the base URL of the server, e.g. `https://lantern.chat`
+    pub fn new(base: &str) -> Result<Self, GatewayError> {
+        Self::from_url(Url::parse(base)?)
+    }
+
+    /// See [`GatewayConfig::new`]
+    pub fn from_url(mut url: Url) -> Result<Self, GatewayError> {
+        let scheme = match url.scheme() {
+            "http" | "ws" => "ws",
+            "https" | "wss" => "wss",
+            scheme => return Err(GatewayError::UnsupportedScheme(scheme.into())),
+        };
+
+        // `Url::set_scheme` refuses to switch between special and non-special schemes, but all of these are special
+        if url.set_scheme(scheme).is_err() || url.host().is_none() {
+            return Err(GatewayError::UnsupportedScheme(url.scheme().into()));
+        }
+
+        let prefix = url.path().trim_end_matches('/').to_owned();
+
+        url.set_path(&format!("{prefix}/api/v1/gateway"));
+        url.set_query(None);
+        url.set_fragment(None);
+
+        Ok(GatewayConfig {
+            url,
+            params: GatewayQueryParams::default(),
+            headers: HeaderMap::new(),
+            timeout: None,
+            proxy: None,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            recorder: None,
+
+            #[cfg(feature = "zstd")]
+            dictionary: None,
+
+            #[cfg(feature = "_internal_tls")]
+            tls: None,
+        })
+    }
+
+    /// Uses the driver's base URL and preferred encoding
+    pub fn from_driver(driver: &Driver) -> Result<Self, GatewayError> {
+        let mut config = Self::new(&driver.uri)?;
+        config.params.encoding = driver.encoding;
+        Ok(config)
+    }
+
+    /// Sets the query parameters, selecting the encoding and compression method.
+    ///
+    /// With the `zstd` feature, a configured [dictionary](GatewayConfig::zstd_dictionary) overrides `params.dict`.
+    pub fn params(mut self, params: GatewayQueryParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Sets the compression level for outgoing messages, from `0` (none) to `10` (slowest) for zlib,
+    /// or up to `22` for zstd. Defaults to [`DEFAULT_COMPRESSION_LEVEL`].
+    pub fn compression_level(mut self, level: u8) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    /// Sets a shared dictionary for zstd compression, which must also be known to the server.
+    #[cfg(feature = "zstd")]
+    pub fn zstd_dictionary(mut self, dict: ZstdDictionary) -> Self {
+        self.dictionary = Some(dict);
+        self
+    }
+
+    /// Adds an extra header to the websocket upgrade request, replacing any previous value
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Sets the `User-Agent` header of the websocket upgrade request
+    pub fn user_agent(self, value: HeaderValue) -> Self {
+        self.header(USER_AGENT, value)
+    }
+
+    /// Sets the `Authorization` header of the websocket upgrade request
+    ///
+    /// The gateway itself authenticates through [`Identify`](crate::models::gateway::commands::Identify),
+    /// but this may be required by intermediate proxies or gateways.
+    pub fn authorization(self, token: AuthToken) -> Result<Self, GatewayError> {
+        match token.headervalue() {
+            Ok(value) => Ok(self.header(AUTHORIZATION, value)),
+            Err(_) => Err(GatewayError::InvalidHeader),
+        }
+    }
+
+    /// Sets a timeout for establishing the connection, including any proxy and websocket handshakes
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Tunnels the connection through an HTTP or SOCKS5 proxy
+    pub fn proxy(mut self, proxy: GatewayProxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Records all incoming traffic, to later be replayed with [`ReplayGateway`](super::ReplayGateway)
+    pub fn recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Sets the TLS connector for `wss` connections, such as to trust custom root certificates
+    #[cfg(feature = "_internal_tls")]
+    pub fn tls_connector(mut self, connector: TlsConnector) -> Self {
+        self.tls = Some(connector);
+        self
+    }
+
+    /// Gateway URL without the query string
+    #[must_use]
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Query parameters sent to the gateway
+    #[must_use]
+    pub fn query_params(&self) -> GatewayQueryParams {
+        #[allow(unused_mut)]
+        let mut params = self.params;
+
+        #[cfg(feature = "zstd")]
+        if let Some(ref dict) = self.dictionary {
+            params.dict = Some(dict.id());
+        }
+
+        params
+    }
+
+    pub(crate) fn level(&self) -> u8 {
+        self.compression_level
+    }
+
+    pub(crate) fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_ref()
+    }
+
+    #[cfg(feature = "zstd")]
+    pub(crate) fn dictionary(&self) -> Option<&ZstdDictionary> {
+        self.dictionary.as_ref()
+    }
+
+    /// Full gateway URL, including the query string
+    ///
+    /// Fails with [`GatewayError::UnsupportedCompression`] if zstd compression is selected without the `zstd` feature.
+    pub fn request_url(&self) -> Result<Url, GatewayError> {
+        let params = self.query_params();
+
+        #[cfg(not(feature = "zstd"))]
+        if matches!(params.compress, GatewayCompression::Zstd | GatewayCompression::ZstdStream) {
+            return Err(GatewayError::UnsupportedCompression(params.compress));
+        }
+
+        let mut url = self.url.clone();
+
+        match serde_urlencoded::to_string(params) {
+            Ok(query) => url.set_query(Some(&query)),
+            Err(_) => return Err(GatewayError::InvalidQuery),
+        }
+
+        Ok(url)
+    }
+
+    /// Establishes the websocket connection, subject to the timeout
+    pub(crate) async fn connect(&self) -> Result<WebSocket, GatewayError> {
+        match self.timeout {
+            None => self.connect_inner().await,
+            Some(timeout) => match tokio::time::timeout(timeout, self.connect_inner()).await {
+                Ok(res) => res,
+                Err(_) => Err(GatewayError::ConnectTimeout),
+            },
+        }
+    }
+
+    async fn connect_inner(&self) -> Result<WebSocket, GatewayError> {
+        let mut req = self.request_url()?.as_str().into_client_request()?;
+
+        for (name, value) in &self.headers {
+            req.headers_mut().insert(name, value.clone());
+        }
+
+        // validated upon construction
+        let host = self.url.host_str().unwrap_or_default();
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let port = self.url.port_or_known_default().unwrap_or(80);
+
+        let stream = match self.proxy {
+            Some(ref proxy) => proxy.connect(host, port).await?,
+            None => TcpStream::connect((host, port)).await?,
+        };
+
+        #[cfg(feature = "_internal_tls")]
+        let connector = self.tls.clone();
+
+        #[cfg(not(feature = "_internal_tls"))]
+        let connector = None;
+
+        let (ws, _) = tokio_tungstenite::client_async_tls_with_config(req, stream, None, connector).await?;
+
+        Ok(ws)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::gateway::GatewayCompression;
+
+    #[test]
+    fn test_gateway_url() {
+        let config = GatewayConfig::new("https://lantern.chat").unwrap();
+        assert_eq!(config.url().as_str(), "wss://lantern.chat/api/v1/gateway");
+        assert_eq!(
+            config.request_url().unwrap().as_str(),
+            "wss://lantern.chat/api/v1/gateway?encoding=json&compress=true"
+        );
+
+        let config = GatewayConfig::new("http://localhost:8080/prefix/?x=1").unwrap();
+        assert_eq!(config.url().as_str(), "ws://localhost:8080/prefix/api/v1/gateway");
+
+        assert!(matches!(
+            GatewayConfig::new("ftp://lantern.chat"),
+            Err(GatewayError::UnsupportedScheme(_))
+        ));
+        assert!(matches!(
+            GatewayProxy::new("https://proxy"),
+            Err(GatewayError::UnsupportedScheme(_))
+        ));
+    }
+
+    #[test]
+    fn test_compression_compat() {
+        let params: GatewayQueryParams = serde_json::from_str(r#"{"compress":true}"#).unwrap();
+        assert_eq!(params.compress, GatewayCompression::Zlib);
+
+        let params: GatewayQueryParams = serde_json::from_str(r#"{"c":false}"#).unwrap();
+        assert_eq!(params.compress, GatewayCompression::None);
+
+        let params: GatewayQueryParams = serde_json::from_str(r#"{"compress":"zlib-stream"}"#).unwrap();
+        assert_eq!(params.compress, GatewayCompression::ZlibStream);
+        assert!(serde_json::from_str::<GatewayQueryParams>(r#"{"compress":"brotli"}"#).is_err());
+
+        let json = serde_json::to_string(&GatewayQueryParams::default()).unwrap();
+        assert_eq!(json, r#"{"encoding":"json","compress":true}"#);
+
+        let params: GatewayQueryParams = serde_urlencoded::from_str("encoding=json&compress=false").unwrap();
+        assert_eq!(params.compress, GatewayCompression::None);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_zstd_unsupported() {
+        let config = GatewayConfig::new("https://lantern.chat").unwrap().params(GatewayQueryParams {
+            compress: GatewayCompression::Zstd,
+            ..Default::default()
+        });
+
+        assert!(matches!(
+            config.request_url(),
+            Err(GatewayError::UnsupportedCompression(GatewayCompression::Zstd))
+        ));
+    }
+}
@@ -1,15 +1,20 @@
 use core::num::NonZeroUsize;
 use core::pin::Pin;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use core::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
+use std::time::Instant;
 
-use futures::{future::BoxFuture, FutureExt, Sink, SinkExt, Stream};
+use futures::{future::BoxFuture, FutureExt, Sink, SinkExt, Stream, StreamExt};
 
 use crate::client::Client;
+use crate::models::gateway::commands::Identify;
 use crate::models::gateway::message::{ClientMsg, ServerMsg};
+use crate::models::{AuthToken, Intent, Snowflake};
 
-use super::{GatewayError, GatewaySocket};
+use super::heartbeat::{Heartbeat, HeartbeatAction, LatencySamples};
+use super::{CloseAction, GatewayConfig, GatewayError, GatewaySocket, LatencyStats, ReconnectPolicy};
 
 /// Gateway connection that provides automatic reconnect
 /// functionality as part of the [Sink]/[Stream] APIs.
@@ -17,14 +22,28 @@ use super::{GatewayError, GatewaySocket};
 /// However, it does not automatically perform the [Hello](ServerMsg::Hello)/[Identify](ClientMsg::Identify) handshake.
 ///
 /// Upon reconnecting the underlying websocket, the server will send
-/// a [Hello](ServerMsg::Hello) event to initiate the handshake.
+/// a [Hello](ServerMsg::Hello) event to initiate the handshake, which should be answered
+/// with [`GatewayConnectionControl::handshake`] to resume the previous session if possible.
 ///
-/// Any errors that occur will still be passed through, and must be handled appropriately. Spamming
-/// servers will reconnections will lead to rate-limiting and possibly automated bans.
+/// Any errors that occur will still be passed through, and must be handled appropriately. Reconnection
+/// attempts are spaced out according to the [`ReconnectPolicy`], as spamming servers with reconnections
+/// will lead to rate-limiting and possibly automated bans.
+///
+/// After an error, the connection follows its [recommended action](GatewayError::recommended_action).
+/// If the server closes the connection with a code that forbids reconnecting, such as
+/// [`AuthFailed`](super::GatewayErrorCode::AuthFailed), no further attempts are made until
+/// [`GatewayConnectionControl::reset`] is called. Codes that invalidate the session clear it, so the
+/// next handshake will Identify, and a server restart is resumed right away without waiting for the backoff.
+///
+/// Heartbeats can optionally be handled by the connection itself, see [`GatewayConnectionControl::set_heartbeat`].
 pub struct GatewayConnection {
     client: Client,
     connecting: Option<BoxFuture<'static, Result<GatewaySocket, GatewayError>>>,
     socket: Option<GatewaySocket>,
+    connected_at: Option<Instant>,
+    heartbeat: Option<Heartbeat>,
+    config: Option<GatewayConfig>,
+    resume_now: bool,
     control: Arc<GatewayConnectionControl>,
 }
 
@@ -32,6 +51,41 @@ pub struct GatewayConnectionControl {
     closed: AtomicBool,
     reconnects: AtomicUsize,
     reconnect_limit: AtomicUsize,
+    policy: Mutex<ReconnectPolicy>,
+    session: Mutex<Option<Snowflake>>,
+    status: AtomicU8,
+    established: AtomicBool,
+    heartbeat: AtomicBool,
+    latency: Mutex<LatencySamples>,
+    intents: AtomicU32,
+}
+
+/// State of the gateway session on the current socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum SessionStatus {
+    /// No session has been established on the current socket yet
+    Connecting = 0,
+    /// A [Resume](ClientMsg::Resume) was sent, but has not yet been confirmed
+    Resuming = 1,
+    /// The first session on this connection was started with a fresh [Ready](ServerMsg::Ready)
+    Started = 2,
+    /// A new session was started after a disconnect, so any events in the gap were lost
+    Restarted = 3,
+    /// The previous session was resumed after a disconnect
+    Resumed = 4,
+}
+
+impl SessionStatus {
+    const fn from_u8(value: u8) -> SessionStatus {
+        match value {
+            1 => SessionStatus::Resuming,
+            2 => SessionStatus::Started,
+            3 => SessionStatus::Restarted,
+            4 => SessionStatus::Resumed,
+            _ => SessionStatus::Connecting,
+        }
+    }
 }
 
 impl GatewayConnectionControl {
@@ -51,6 +105,125 @@ impl GatewayConnectionControl {
     pub fn noreconnect(&self) {
         self.closed.store(true, Ordering::SeqCst);
     }
+
+    /// Sets the backoff policy used for subsequent reconnection attempts, with its jitter clamped to `[0, 1]`
+    pub fn set_reconnect_policy(&self, mut policy: ReconnectPolicy) {
+        policy.jitter = policy.jitter();
+
+        *self.policy.lock().unwrap_or_else(|e| e.into_inner()) = policy;
+    }
+
+    /// The current backoff policy for reconnection attempts
+    #[must_use]
+    pub fn reconnect_policy(&self) -> ReconnectPolicy {
+        *self.policy.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Number of connection attempts made since the last stable connection or [`reset`](Self::reset)
+    #[must_use]
+    pub fn reconnects(&self) -> usize {
+        self.reconnects.load(Ordering::SeqCst)
+    }
+
+    /// Enables or disables the built-in heartbeat, taking effect upon the next [Hello](ServerMsg::Hello).
+    ///
+    /// When enabled, the connection sends [Heartbeat](ClientMsg::Heartbeat) messages at the interval
+    /// given by the server while the stream is being polled. If a heartbeat is not acknowledged before the
+    /// next one is due, the connection is considered dead, the stream yields [`GatewayError::HeartbeatTimeout`]
+    /// and the socket is dropped to reconnect.
+    pub fn set_heartbeat(&self, enabled: bool) {
+        self.heartbeat.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Heartbeat round-trip latency statistics, only measured with the built-in heartbeat enabled
+    #[must_use]
+    pub fn latency(&self) -> LatencyStats {
+        self.latency.lock().unwrap_or_else(|e| e.into_inner()).stats()
+    }
+
+    /// Declares the intents to [Identify](ClientMsg::Identify) with, taking effect upon the next new session.
+    ///
+    /// Events outside of these intents are dropped by the connection, even if the server sends them anyway.
+    /// Defaults to [`Intent::all()`].
+    pub fn set_intents(&self, intents: Intent) {
+        self.intents.store(intents.bits(), Ordering::SeqCst);
+    }
+
+    /// The declared intents, see [`set_intents`](Self::set_intents)
+    #[must_use]
+    pub fn intents(&self) -> Intent {
+        Intent::from_bits_truncate(self.intents.load(Ordering::SeqCst))
+    }
+
+    /// The session ID from the last [Ready](ServerMsg::Ready), if it has not since been invalidated
+    #[must_use]
+    pub fn session(&self) -> Option<Snowflake> {
+        *self.session.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Forget the current session, so the next handshake will [Identify](ClientMsg::Identify)
+    pub fn clear_session(&self) {
+        *self.session.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// State of the session on the current socket
+    #[must_use]
+    pub fn session_status(&self) -> SessionStatus {
+        SessionStatus::from_u8(self.status.load(Ordering::SeqCst))
+    }
+
+    /// Build the message to answer a [Hello](ServerMsg::Hello) with.
+    ///
+    /// If a previous session is known, this is a [Resume](ClientMsg::Resume),
+    /// otherwise an [Identify](ClientMsg::Identify) to start a new session. Should the server
+    /// reply with [InvalidSession](ServerMsg::InvalidSession), the session is forgotten and
+    /// calling this again will Identify instead, using the [declared intents](Self::set_intents).
+    #[must_use]
+    pub fn handshake(&self, auth: AuthToken) -> ClientMsg {
+        match self.session() {
+            Some(session) => {
+                self.set_status(SessionStatus::Resuming);
+
+                ClientMsg::new_resume(session)
+            }
+            None => ClientMsg::new_identify(Identify {
+                auth,
+                intent: self.intents(),
+            }),
+        }
+    }
+
+    fn set_status(&self, status: SessionStatus) {
+        self.status.store(status as u8, Ordering::SeqCst);
+    }
+
+    /// Track session state from incoming messages
+    fn observe(&self, msg: &ServerMsg) {
+        match msg {
+            ServerMsg::Hello(_) => {}
+            ServerMsg::Ready(ready) => {
+                *self.session.lock().unwrap_or_else(|e| e.into_inner()) = Some(ready.inner.session);
+
+                self.set_status(match self.established.swap(true, Ordering::SeqCst) {
+                    true => SessionStatus::Restarted,
+                    false => SessionStatus::Started,
+                });
+            }
+            ServerMsg::InvalidSession(_) => {
+                self.clear_session();
+                self.set_status(SessionStatus::Connecting);
+            }
+            // any other message after a Resume means the server accepted it
+            _ => {
+                let _ = self.status.compare_exchange(
+                    SessionStatus::Resuming as u8,
+                    SessionStatus::Resumed as u8,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                );
+            }
+        }
+    }
 }
 
 impl GatewayConnection {
@@ -60,10 +233,21 @@ impl GatewayConnection {
             client,
             connecting: None,
             socket: None,
+            connected_at: None,
+            heartbeat: None,
+            config: None,
+            resume_now: false,
             control: Arc::new(GatewayConnectionControl {
                 closed: AtomicBool::new(false),
                 reconnects: AtomicUsize::new(0),
                 reconnect_limit: AtomicUsize::new(20),
+                policy: Mutex::new(ReconnectPolicy::default()),
+                session: Mutex::new(None),
+                status: AtomicU8::new(SessionStatus::Connecting as u8),
+                established: AtomicBool::new(false),
+                heartbeat: AtomicBool::new(false),
+                latency: Mutex::new(LatencySamples::default()),
+                intents: AtomicU32::new(Intent::all().bits()),
             }),
         }
     }
@@ -75,11 +259,78 @@ impl GatewayConnection {
         futures::future::poll_fn(move |cx| self.poll_project_socket(cx).map_ok(|_| ())).await
     }
 
+    /// Sets the configuration used for subsequent connections, such as to select
+    /// a different [compression method](crate::api::gateway::GatewayQueryParams::compress) or use a proxy.
+    ///
+    /// By default, [`GatewayConfig::from_driver`] is used with the client's current driver.
+    pub fn set_config(&mut self, config: GatewayConfig) {
+        self.config = Some(config);
+    }
+
     /// Get a reference to the control structure
     pub fn control(&self) -> Arc<GatewayConnectionControl> {
         self.control.clone()
     }
 
+    /// Drop the current socket, so the next poll will reconnect.
+    ///
+    /// If the socket was up long enough to be considered stable, the attempt counter is reset first.
+    fn drop_socket(&mut self) {
+        self.socket = None;
+        self.heartbeat = None;
+
+        if let Some(connected_at) = self.connected_at.take() {
+            if connected_at.elapsed() >= self.control.reconnect_policy().stable_after {
+                self.control.reconnects.store(0, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Send a heartbeat if one is due, or detect a missing acknowledgement
+    fn poll_heartbeat(&mut self, cx: &mut Context<'_>) -> Result<(), GatewayError> {
+        let (Some(heartbeat), Some(socket)) = (&mut self.heartbeat, &mut self.socket) else {
+            return Ok(());
+        };
+
+        match heartbeat.poll(cx) {
+            HeartbeatAction::Wait => Ok(()),
+            HeartbeatAction::Timeout => Err(GatewayError::HeartbeatTimeout),
+            HeartbeatAction::Send => match socket.poll_ready_unpin(cx) {
+                // will be retried when the socket is ready
+                Poll::Pending => Ok(()),
+                Poll::Ready(Err(e)) => Err(e),
+                Poll::Ready(Ok(())) => {
+                    socket.start_send_unpin(ClientMsg::new_heartbeat())?;
+                    heartbeat.sent();
+
+                    // the flush will continue as the socket is polled
+                    match socket.poll_flush_unpin(cx) {
+                        Poll::Ready(Err(e)) => Err(e),
+                        _ => Ok(()),
+                    }
+                }
+            },
+        }
+    }
+
+    /// Start the heartbeat upon Hello, and measure latency upon acknowledgement
+    fn observe_heartbeat(&mut self, msg: &ServerMsg) {
+        match msg {
+            ServerMsg::Hello(hello) if self.control.heartbeat.load(Ordering::SeqCst) => {
+                self.heartbeat = Some(Heartbeat::new(hello.inner.heartbeat_interval));
+
+                // samples from a previous socket would skew the new session's latency
+                self.control.latency.lock().unwrap_or_else(|e| e.into_inner()).clear();
+            }
+            ServerMsg::HeartbeatAck(_) => {
+                if let Some(rtt) = self.heartbeat.as_mut().and_then(Heartbeat::ack) {
+                    self.control.latency.lock().unwrap_or_else(|e| e.into_inner()).push(rtt);
+                }
+            }
+            _ => {}
+        }
+    }
+
     /// Acquire a pinned projection of the socket, or poll the connecting future.
     fn poll_project_socket(&mut self, cx: &mut Context<'_>) -> Poll<Result<Pin<&mut GatewaySocket>, GatewayError>> {
         // fast path, project socket
@@ -100,22 +351,48 @@ impl GatewayConnection {
             }
 
             let limit = self.control.reconnect_limit.load(Ordering::SeqCst);
-            if self.control.reconnects.fetch_add(1, Ordering::SeqCst) > limit {
+            let attempt = self.control.reconnects.fetch_add(1, Ordering::SeqCst);
+
+            if attempt > limit {
                 self.control.closed.store(true, Ordering::SeqCst);
 
                 return Poll::Ready(Err(GatewayError::ReconnectLimitExceeded(limit)));
             }
 
-            self.connecting = Some(GatewaySocket::connect(self.client.driver()).boxed());
+            let delay = match core::mem::take(&mut self.resume_now) {
+                true => Duration::ZERO,
+                false => self.control.reconnect_policy().delay(attempt),
+            };
+            let config = match self.config {
+                Some(ref config) => Ok(config.clone()),
+                None => GatewayConfig::from_driver(&self.client.driver()),
+            };
+
+            self.connecting = Some(
+                async move {
+                    if !delay.is_zero() {
+                        tokio::time::sleep(delay).await;
+                    }
+
+                    GatewaySocket::connect_with(&config?).await
+                }
+                .boxed(),
+            );
         }
 
         match self.connecting {
             Some(ref mut connecting) => match connecting.poll_unpin(cx) {
                 Poll::Pending => Poll::Pending,
-                Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+                Poll::Ready(Err(e)) => {
+                    self.connecting = None;
+
+                    Poll::Ready(Err(e))
+                }
                 Poll::Ready(Ok(socket)) => {
                     self.socket = Some(socket);
                     self.connecting = None;
+                    self.connected_at = Some(Instant::now());
+                    self.control.set_status(SessionStatus::Connecting);
 
                     Poll::Ready(Ok(match self.socket {
                         // just assigned, project
@@ -134,17 +411,46 @@ impl Stream for GatewayConnection {
     type Item = Result<ServerMsg, GatewayError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let res = match self.poll_project_socket(cx) {
-            Poll::Pending => return Poll::Pending,
-            Poll::Ready(Ok(socket)) => futures::ready!(socket.poll_next(cx)),
-            Poll::Ready(Err(e)) => Some(Err(e)),
-        };
+        loop {
+            let this = &mut *self;
+
+            let res = match this.poll_project_socket(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => Some(Err(e)),
+                Poll::Ready(Ok(_)) => match this.poll_heartbeat(cx) {
+                    Err(e) => Some(Err(e)),
+                    Ok(()) => match this.socket {
+                        Some(ref mut socket) => futures::ready!(socket.poll_next_unpin(cx)),
+                        None => Some(Err(GatewayError::Disconnected)),
+                    },
+                },
+            };
+
+            match res {
+                Some(Ok(ref msg)) => {
+                    self.control.observe(msg);
+                    self.observe_heartbeat(msg);
+
+                    // drop events the server sent outside of the declared intents
+                    if !msg.is_within(self.control.intents()) {
+                        continue;
+                    }
+                }
+                Some(Err(ref e)) => {
+                    match e.recommended_action() {
+                        CloseAction::Reconnect => {}
+                        CloseAction::Resume => self.resume_now = true,
+                        CloseAction::Reidentify => self.control.clear_session(),
+                        CloseAction::GiveUp => self.control.noreconnect(),
+                    }
+
+                    self.drop_socket();
+                }
+                None => self.drop_socket(),
+            }
 
-        if let None | Some(Err(_)) = res {
-            self.socket = None; // drop socket
+            return Poll::Ready(res);
         }
-
-        Poll::Ready(res)
     }
 }
 
@@ -159,7 +465,7 @@ impl Sink<ClientMsg> for GatewayConnection {
         };
 
         if res.is_err() {
-            self.socket = None; // drop socket
+            self.drop_socket();
         }
 
         Poll::Ready(res)
@@ -168,9 +474,7 @@ impl Sink<ClientMsg> for GatewayConnection {
     #[inline]
     fn start_send(mut self: Pin<&mut Self>, item: ClientMsg) -> Result<(), GatewayError> {
         match self.socket {
-            Some(ref mut socket) => socket.start_send_unpin(item).inspect_err(|_| {
-                self.socket = None; // drop socket
-            }),
+            Some(ref mut socket) => socket.start_send_unpin(item).inspect_err(|_| self.drop_socket()),
             // `start_send` doesn't poll or have a context, so there is no way to initiate the reconnect
             None => Err(GatewayError::Disconnected),
         }
@@ -183,22 +487,23 @@ impl Sink<ClientMsg> for GatewayConnection {
         };
 
         if res.is_err() {
-            self.socket = None; // drop socket
+            self.drop_socket();
         }
 
         Poll::Ready(res)
     }
 
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), GatewayError>> {
-        // ensure it won't reconnect automatically
+        // ensure it won't reconnect automatically, and the session won't be resumed later
         self.control.closed.store(true, Ordering::SeqCst);
+        self.control.clear_session();
 
         let res = match futures::ready!(self.poll_project_socket(cx)) {
             Ok(socket) => futures::ready!(socket.poll_close(cx)),
             Err(e) => Err(e),
         };
 
-        self.socket = None; // drop socket
+        self.drop_socket();
 
         Poll::Ready(res)
     }
@@ -1,5 +1,7 @@
 use tokio_tungstenite::tungstenite::Error as WSError;
 
+use crate::api::gateway::GatewayCompression;
+
 #[derive(Debug, thiserror::Error)]
 pub enum GatewayError {
     #[error("WS Error: {0}")]
@@ -8,6 +10,33 @@ pub enum GatewayError {
     #[error("Gateway Disconnected")]
     Disconnected,
 
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("URL Parse Error: {0}")]
+    UrlError(#[from] url::ParseError),
+
+    #[error("Unsupported URL Scheme: {0}")]
+    UnsupportedScheme(String),
+
+    #[error("Invalid Header Value")]
+    InvalidHeader,
+
+    #[error("Invalid Query Parameters")]
+    InvalidQuery,
+
+    #[error("Connection Timed Out")]
+    ConnectTimeout,
+
+    #[error("Proxy Error: {0}")]
+    ProxyError(&'static str),
+
+    #[error("Invalid Gateway Recording")]
+    InvalidRecording,
+
+    #[error("Heartbeat Not Acknowledged")]
+    HeartbeatTimeout,
+
     #[error("Exceeded Reconnect Limit of {0} Attempts")]
     ReconnectLimitExceeded(usize),
 
@@ -24,8 +53,114 @@ pub enum GatewayError {
     #[error("Compression Error")]
     CompressionError,
 
-    #[error("Close Error: {0:?}")]
-    CloseError(GatewayErrorCode),
+    /// The compression method requires a feature that is not enabled, such as `zstd`
+    #[error("Unsupported Compression Method: {}", .0.as_str())]
+    UnsupportedCompression(GatewayCompression),
+
+    #[error("Close Error: {0}")]
+    CloseError(GatewayClose),
+}
+
+impl GatewayError {
+    /// What to do about this error, either as a client or within [`GatewayConnection`](super::GatewayConnection)
+    ///
+    /// Close errors defer to [`GatewayClose::action`], errors that would only recur on every attempt,
+    /// such as an invalid URL, recommend [giving up](CloseAction::GiveUp), and anything else may reconnect.
+    #[must_use]
+    pub fn recommended_action(&self) -> CloseAction {
+        match self {
+            GatewayError::CloseError(close) => close.action(),
+            GatewayError::UrlError(_)
+            | GatewayError::UnsupportedScheme(_)
+            | GatewayError::InvalidHeader
+            | GatewayError::InvalidQuery
+            | GatewayError::UnsupportedCompression(_)
+            | GatewayError::InvalidRecording
+            | GatewayError::ReconnectLimitExceeded(_) => CloseAction::GiveUp,
+            _ => CloseAction::Reconnect,
+        }
+    }
+
+    /// The close code sent by the server, if it closed the connection with one
+    #[must_use]
+    pub fn close_code(&self) -> Option<u16> {
+        match self {
+            GatewayError::CloseError(close) => Some(close.code),
+            _ => None,
+        }
+    }
+}
+
+/// Recommended course of action after the gateway connection was closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CloseAction {
+    /// Reconnect after the usual backoff delay, resuming the session if possible
+    Reconnect,
+    /// Reconnect right away and resume the session, as the server expects the client back shortly
+    Resume,
+    /// Reconnect after the usual backoff delay, but start a new session
+    Reidentify,
+    /// Do not reconnect, as it would only fail again
+    GiveUp,
+}
+
+/// Close frame received from the server, including its reason text
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GatewayClose {
+    /// Raw close code, either a standard websocket code or a [`GatewayErrorCode`]
+    pub code: u16,
+    /// Reason given by the server, which may be empty
+    pub reason: String,
+}
+
+impl GatewayClose {
+    /// Close frame with the given code and reason
+    #[must_use]
+    pub fn new(code: u16, reason: impl Into<String>) -> Self {
+        GatewayClose {
+            code,
+            reason: reason.into(),
+        }
+    }
+
+    /// The gateway-specific error code, if the close code is a known one
+    #[must_use]
+    pub fn error_code(&self) -> Option<GatewayErrorCode> {
+        use num_traits::FromPrimitive;
+
+        GatewayErrorCode::from_u16(self.code)
+    }
+
+    /// Recommended course of action after this close, see [`GatewayErrorCode::action`]
+    ///
+    /// Unknown gateway codes and abnormal websocket closures reconnect, while policy
+    /// violations and unsupported data are not retried.
+    #[must_use]
+    pub fn action(&self) -> CloseAction {
+        match self.error_code() {
+            Some(code) => code.action(),
+            // Going Away, such as a server shutting down
+            None if self.code == 1001 => CloseAction::Resume,
+            // Unsupported Data, Policy Violation or Message Too Big
+            None if matches!(self.code, 1003 | 1008 | 1009) => CloseAction::GiveUp,
+            None => CloseAction::Reconnect,
+        }
+    }
+}
+
+impl core::fmt::Display for GatewayClose {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.error_code() {
+            Some(code) => write!(f, "{code:?} ({})", self.code)?,
+            None => write!(f, "{}", self.code)?,
+        }
+
+        if !self.reason.is_empty() {
+            write!(f, ": {}", self.reason)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[rustfmt::skip]
@@ -40,4 +175,41 @@ pub enum GatewayErrorCode {
     DecodeError         = 4002,
     NotAuthenticated    = 4003,
     AuthFailed          = 4004,
+    RateLimited         = 4005,
+    InvalidIntents      = 4006,
+    SessionTimeout      = 4007,
+    ServerRestart       = 4008,
+}
+
+impl GatewayErrorCode {
+    /// Recommended course of action after the server closed the connection with this code
+    #[rustfmt::skip] #[must_use]
+    pub const fn action(self) -> CloseAction {
+        match self {
+            | GatewayErrorCode::UnknownError
+            | GatewayErrorCode::UnknownOpcode
+            | GatewayErrorCode::DecodeError
+            | GatewayErrorCode::RateLimited
+                => CloseAction::Reconnect,
+
+            GatewayErrorCode::ServerRestart
+                => CloseAction::Resume,
+
+            | GatewayErrorCode::NotAuthenticated
+            | GatewayErrorCode::SessionTimeout
+                => CloseAction::Reidentify,
+
+            | GatewayErrorCode::AuthFailed
+            | GatewayErrorCode::InvalidIntents
+                => CloseAction::GiveUp,
+        }
+    }
+
+    /// Whether it makes sense to reconnect after the server closed the connection with this code.
+    ///
+    /// Reconnecting after [`AuthFailed`](GatewayErrorCode::AuthFailed) would only fail again.
+    #[must_use]
+    pub const fn can_reconnect(self) -> bool {
+        !matches!(self.action(), CloseAction::GiveUp)
+    }
 }
@@ -0,0 +1,150 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::Context;
+use core::time::Duration;
+use std::collections::VecDeque;
+
+use tokio::time::{Instant, Sleep};
+
+/// Number of round-trip samples kept for [`LatencyStats`]
+const MAX_SAMPLES: usize = 64;
+
+/// Heartbeat round-trip latency, measured between sending [`Heartbeat`](crate::models::gateway::message::ClientMsg::Heartbeat)
+/// and receiving [`HeartbeatAck`](crate::models::gateway::message::ServerMsg::HeartbeatAck)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Most recent round-trip time
+    pub last: Option<Duration>,
+    /// Mean round-trip time over the recent samples
+    pub average: Option<Duration>,
+    /// 99th percentile round-trip time over the recent samples
+    pub p99: Option<Duration>,
+    /// Number of recent samples the statistics were computed from
+    pub samples: usize,
+}
+
+#[derive(Default)]
+pub(super) struct LatencySamples {
+    samples: VecDeque<Duration>,
+}
+
+impl LatencySamples {
+    pub fn push(&mut self, rtt: Duration) {
+        if self.samples.len() >= MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(rtt);
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn stats(&self) -> LatencyStats {
+        let samples = self.samples.len();
+
+        if samples == 0 {
+            return LatencyStats::default();
+        }
+
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+
+        // nearest-rank percentile
+        let p99 = sorted[(samples * 99).div_ceil(100) - 1];
+
+        LatencyStats {
+            last: self.samples.back().copied(),
+            average: Some(sorted.iter().sum::<Duration>() / samples as u32),
+            p99: Some(p99),
+            samples,
+        }
+    }
+}
+
+/// Heartbeat state for a single socket, started upon [`Hello`](crate::models::events::Hello)
+pub(super) struct Heartbeat {
+    interval: Duration,
+    timer: Pin<Box<Sleep>>,
+    sent_at: Option<Instant>,
+    due: bool,
+}
+
+/// Action for the connection to take after polling the heartbeat timer
+pub(super) enum HeartbeatAction {
+    /// Nothing to do yet
+    Wait,
+    /// A heartbeat should be sent now
+    Send,
+    /// The previous heartbeat was never acknowledged, so the connection is a zombie
+    Timeout,
+}
+
+impl Heartbeat {
+    pub fn new(interval_ms: u32) -> Self {
+        // guard against a bogus interval turning into a busy loop
+        let interval = Duration::from_millis(interval_ms.max(1000) as u64);
+
+        Heartbeat {
+            interval,
+            timer: Box::pin(tokio::time::sleep(interval)),
+            sent_at: None,
+            due: false,
+        }
+    }
+
+    pub fn poll(&mut self, cx: &mut Context<'_>) -> HeartbeatAction {
+        if !self.due {
+            if self.timer.as_mut().poll(cx).is_pending() {
+                return HeartbeatAction::Wait;
+            }
+
+            if self.sent_at.is_some() {
+                return HeartbeatAction::Timeout;
+            }
+
+            self.due = true;
+            self.timer.as_mut().reset(Instant::now() + self.interval);
+
+            // register the waker for the next deadline
+            let _ = self.timer.as_mut().poll(cx);
+        }
+
+        HeartbeatAction::Send
+    }
+
+    /// Mark the heartbeat as sent, starting the round-trip measurement
+    pub fn sent(&mut self) {
+        self.due = false;
+        self.sent_at = Some(Instant::now());
+    }
+
+    /// Returns the round-trip time of the acknowledged heartbeat, if one was in flight
+    pub fn ack(&mut self) -> Option<Duration> {
+        self.sent_at.take().map(|sent_at| sent_at.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_stats() {
+        let mut samples = LatencySamples::default();
+
+        assert_eq!(samples.stats(), LatencyStats::default());
+
+        for ms in 1..=100 {
+            samples.push(Duration::from_millis(ms));
+        }
+
+        let stats = samples.stats();
+
+        assert_eq!(stats.samples, MAX_SAMPLES);
+        assert_eq!(stats.last, Some(Duration::from_millis(100)));
+        assert_eq!(stats.p99, Some(Duration::from_millis(100)));
+        assert_eq!(stats.average, Some(Duration::from_micros(68_500)));
+    }
+}
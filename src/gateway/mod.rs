@@ -1,7 +1,29 @@
+mod bus;
+mod collector;
+mod compression;
+mod config;
 mod conn;
 mod error;
+mod heartbeat;
+mod proxy;
+mod reconnect;
+pub mod record;
 mod socket;
 
-pub use conn::{GatewayConnection, GatewayConnectionControl};
-pub use error::{GatewayError, GatewayErrorCode};
+pub use bus::{EventBus, EventFilter, LagPolicy, Subscription, DEFAULT_SUBSCRIBER_CAPACITY};
+pub use collector::Collector;
+pub use compression::{Compressor, Decompressor, DEFAULT_COMPRESSION_LEVEL};
+pub use config::GatewayConfig;
+pub use conn::{GatewayConnection, GatewayConnectionControl, SessionStatus};
+pub use error::{CloseAction, GatewayClose, GatewayError, GatewayErrorCode};
+pub use heartbeat::LatencyStats;
+pub use proxy::GatewayProxy;
+pub use reconnect::ReconnectPolicy;
+pub use record::{RecordMode, Recorder, ReplayGateway, ReplaySpeed};
 pub use socket::GatewaySocket;
+
+#[cfg(feature = "zstd")]
+pub use compression::ZstdDictionary;
+
+#[cfg(feature = "_internal_tls")]
+pub use config::TlsConnector;
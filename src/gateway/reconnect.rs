@@ -0,0 +1,116 @@
+use core::time::Duration;
+
+/// Controls how quickly [`GatewayConnection`](super::GatewayConnection) reconnects after
+/// the socket is lost, to avoid hammering the server with reconnection attempts.
+///
+/// Each consecutive attempt waits `initial_delay * multiplier^(attempt - 1)`, capped at `max_delay`,
+/// minus a random fraction of up to `jitter` of that delay so many clients don't reconnect in lockstep.
+///
+/// Once a connection has stayed up for `stable_after`, the attempt counter is reset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Delay before the first reconnection attempt
+    pub initial_delay: Duration,
+    /// Upper bound on the delay between attempts
+    pub max_delay: Duration,
+    /// Growth factor of the delay per attempt
+    pub multiplier: f64,
+    /// Fraction of the delay, from `0.0` to `1.0`, that may be randomly subtracted.
+    ///
+    /// Values outside of that range are clamped, and NaN is treated as `0.0`.
+    pub jitter: f64,
+    /// How long a connection must last to be considered stable
+    pub stable_after: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.5,
+            stable_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Reconnect immediately, without any backoff
+    pub const IMMEDIATE: ReconnectPolicy = ReconnectPolicy {
+        initial_delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+        multiplier: 1.0,
+        jitter: 0.0,
+        stable_after: Duration::ZERO,
+    };
+
+    /// Computes the delay before the given attempt, where attempt `0` is the initial connection.
+    #[must_use]
+    pub fn delay(&self, attempt: usize) -> Duration {
+        if attempt == 0 {
+            return Duration::ZERO;
+        }
+
+        let exp = (attempt - 1).min(i32::MAX as usize) as i32;
+        let secs = self.initial_delay.as_secs_f64() * self.multiplier.powi(exp);
+
+        let delay = match Duration::try_from_secs_f64(secs) {
+            Ok(delay) => delay.min(self.max_delay),
+            Err(_) => self.max_delay,
+        };
+
+        delay.mul_f64(1.0 - self.jitter() * rand::random::<f64>())
+    }
+
+    /// The jitter within `[0, 1]`, as NaN would otherwise pass through `clamp` and make `mul_f64` panic
+    pub(crate) fn jitter(&self) -> f64 {
+        match self.jitter.is_nan() {
+            true => 0.0,
+            false => self.jitter.clamp(0.0, 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_bounds() {
+        let policy = ReconnectPolicy::default();
+
+        assert_eq!(policy.delay(0), Duration::ZERO);
+
+        for attempt in 1..20 {
+            let delay = policy.delay(attempt);
+            let base = policy.initial_delay.mul_f64(2f64.powi(attempt as i32 - 1)).min(policy.max_delay);
+
+            assert!(delay <= base);
+            assert!(delay >= base.mul_f64(1.0 - policy.jitter));
+        }
+
+        assert!(policy.delay(usize::MAX) <= policy.max_delay);
+        assert_eq!(ReconnectPolicy::IMMEDIATE.delay(5), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_invalid_jitter() {
+        for jitter in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -1.0, 2.0] {
+            let policy = ReconnectPolicy {
+                jitter,
+                ..ReconnectPolicy::default()
+            };
+
+            assert!(policy.delay(1) <= policy.initial_delay);
+        }
+
+        let policy = ReconnectPolicy {
+            jitter: f64::NAN,
+            ..ReconnectPolicy::default()
+        };
+
+        assert_eq!(policy.jitter(), 0.0);
+        assert_eq!(policy.delay(1), policy.initial_delay);
+    }
+}
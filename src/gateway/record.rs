@@ -0,0 +1,357 @@
+//! Recording and replaying of gateway traffic
+//!
+//! A recording starts with [`MAGIC`], followed by frames of the form:
+//!
+//! | Bytes | Content                                                 |
+//! |-------|---------------------------------------------------------|
+//! | 8     | Microseconds since the recording started, little-endian |
+//! | 1     | Frame kind: `0` for JSON, `1` for CBOR, `2` for close   |
+//! | 4     | Payload length, little-endian                           |
+//! | N     | Payload                                                 |
+//!
+//! Close frames carry the little-endian close code followed by the UTF-8 reason as their payload,
+//! or nothing for a plain disconnect.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures::{Sink, Stream};
+use tokio::time::Sleep;
+
+use crate::driver::Encoding;
+use crate::models::gateway::message::{ClientMsg, ServerMsg};
+
+use super::{GatewayClose, GatewayError};
+
+/// Header at the start of every recording, including the format version
+pub const MAGIC: &[u8; 8] = b"LGWREC\x00\x01";
+
+const FRAME_JSON: u8 = 0;
+#[cfg_attr(not(feature = "cbor"), allow(dead_code))]
+const FRAME_CBOR: u8 = 1;
+const FRAME_CLOSE: u8 = 2;
+
+const FRAME_HEADER_LEN: usize = 8 + 1 + 4;
+
+/// What a [`Recorder`] writes for each incoming message
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordMode {
+    /// The exact decompressed bytes sent by the server, in the negotiated encoding
+    #[default]
+    Raw,
+    /// Each decoded [`ServerMsg`], re-encoded as JSON for readability
+    Decoded,
+}
+
+struct RecorderInner {
+    out: Box<dyn Write + Send>,
+    start: Instant,
+    mode: RecordMode,
+    error: Option<io::Error>,
+}
+
+/// Tees incoming gateway traffic to a file or other writer, see the [module docs](self) for the format
+///
+/// Attach it to a connection with [`GatewayConfig::recorder`](super::GatewayConfig::recorder). Writes are
+/// buffered and synchronous, so prefer a fast local disk. The first write error stops the recording,
+/// and can be retrieved with [`Recorder::take_error`].
+#[derive(Clone)]
+pub struct Recorder(Arc<Mutex<RecorderInner>>);
+
+impl Recorder {
+    /// Creates a new recording file, truncating any existing one
+    pub fn create(path: impl AsRef<Path>, mode: RecordMode) -> io::Result<Self> {
+        Self::new(io::BufWriter::new(std::fs::File::create(path)?), mode)
+    }
+
+    /// Starts a recording on the given writer
+    pub fn new(mut out: impl Write + Send + 'static, mode: RecordMode) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+
+        Ok(Recorder(Arc::new(Mutex::new(RecorderInner {
+            out: Box::new(out),
+            start: Instant::now(),
+            mode,
+            error: None,
+        }))))
+    }
+
+    fn inner(&self) -> std::sync::MutexGuard<'_, RecorderInner> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Flushes any buffered frames to the underlying writer
+    pub fn flush(&self) -> io::Result<()> {
+        self.inner().out.flush()
+    }
+
+    /// Takes the error that stopped the recording, if any
+    #[must_use]
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.inner().error.take()
+    }
+
+    fn write_frame(&self, kind: u8, payload: &[u8]) {
+        let mut inner = self.inner();
+
+        if inner.error.is_some() {
+            return;
+        }
+
+        let elapsed = inner.start.elapsed().as_micros() as u64;
+
+        let mut header = [0u8; FRAME_HEADER_LEN];
+        header[..8].copy_from_slice(&elapsed.to_le_bytes());
+        header[8] = kind;
+        header[9..].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+
+        let res = inner.out.write_all(&header).and_then(|_| inner.out.write_all(payload));
+
+        if let Err(e) = res {
+            inner.error = Some(e);
+        }
+    }
+
+    /// Records a message received on the socket, given its decompressed body and the decoded message,
+    /// if it could be decoded.
+    ///
+    /// Messages that failed to decode are always recorded as-is, so replaying them reproduces the error.
+    pub(crate) fn record(&self, encoding: Encoding, body: &[u8], msg: Option<&ServerMsg>) {
+        let mode = self.inner().mode;
+
+        match (mode, msg) {
+            (RecordMode::Decoded, Some(msg)) => match serde_json::to_vec(msg) {
+                Ok(json) => self.write_frame(FRAME_JSON, &json),
+                Err(e) => self.inner().error = Some(e.into()),
+            },
+            _ => match encoding {
+                Encoding::JSON => self.write_frame(FRAME_JSON, body),
+                #[cfg(feature = "cbor")]
+                Encoding::CBOR => self.write_frame(FRAME_CBOR, body),
+            },
+        }
+    }
+
+    /// Records the socket being closed by the server
+    pub(crate) fn record_close(&self, close: Option<&GatewayClose>) {
+        match close {
+            Some(close) => self.write_frame(
+                FRAME_CLOSE,
+                &[&close.code.to_le_bytes()[..], close.reason.as_bytes()].concat(),
+            ),
+            None => self.write_frame(FRAME_CLOSE, &[]),
+        }
+    }
+}
+
+/// Playback speed for a [`ReplayGateway`]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Reproduce the original timing between events
+    #[default]
+    Original,
+    /// Speed up playback by the given factor, e.g. `10.0` for ten times faster
+    Accelerated(f64),
+    /// Yield every event as fast as possible
+    Instant,
+}
+
+struct Frame {
+    at: Duration,
+    kind: u8,
+    payload: Vec<u8>,
+}
+
+/// Replays a recording made by a [`Recorder`] in place of a live gateway connection
+///
+/// Messages sent to the replay are discarded, and the stream ends with the recording.
+/// It can be given to [`Standard::replay`](crate::framework::standard::Standard::replay) to run a bot against it.
+pub struct ReplayGateway {
+    frames: std::vec::IntoIter<Frame>,
+    speed: ReplaySpeed,
+    start: Option<tokio::time::Instant>,
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl ReplayGateway {
+    /// Reads a recording file. This blocks while reading the whole file into memory.
+    pub fn open(path: impl AsRef<Path>, speed: ReplaySpeed) -> Result<Self, GatewayError> {
+        Self::from_bytes(&std::fs::read(path)?, speed)
+    }
+
+    /// Parses a recording from memory
+    pub fn from_bytes(mut data: &[u8], speed: ReplaySpeed) -> Result<Self, GatewayError> {
+        match data.strip_prefix(MAGIC) {
+            Some(rest) => data = rest,
+            None => return Err(GatewayError::InvalidRecording),
+        }
+
+        let mut frames = Vec::new();
+
+        while !data.is_empty() {
+            if data.len() < FRAME_HEADER_LEN {
+                return Err(GatewayError::InvalidRecording);
+            }
+
+            let (header, rest) = data.split_at(FRAME_HEADER_LEN);
+
+            let at = u64::from_le_bytes(header[..8].try_into().unwrap());
+            let kind = header[8];
+            let len = u32::from_le_bytes(header[9..].try_into().unwrap()) as usize;
+
+            if rest.len() < len {
+                return Err(GatewayError::InvalidRecording);
+            }
+
+            let (payload, rest) = rest.split_at(len);
+
+            frames.push(Frame {
+                at: Duration::from_micros(at),
+                kind,
+                payload: payload.to_vec(),
+            });
+
+            data = rest;
+        }
+
+        Ok(ReplayGateway {
+            frames: frames.into_iter(),
+            speed,
+            start: None,
+            timer: None,
+        })
+    }
+
+    /// Number of frames left to replay
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.frames.len()
+    }
+
+    fn delay(&self, at: Duration) -> Option<Duration> {
+        match self.speed {
+            ReplaySpeed::Instant => None,
+            ReplaySpeed::Original => Some(at),
+            ReplaySpeed::Accelerated(factor) if factor > 0.0 && factor.is_finite() => Some(at.div_f64(factor)),
+            ReplaySpeed::Accelerated(_) => None,
+        }
+    }
+
+    fn decode(frame: Frame) -> Result<ServerMsg, GatewayError> {
+        match frame.kind {
+            FRAME_JSON => Ok(serde_json::from_slice(&frame.payload)?),
+            #[cfg(feature = "cbor")]
+            FRAME_CBOR => Ok(ciborium::de::from_reader(&frame.payload[..])?),
+            FRAME_CLOSE => Err(match frame.payload.split_first_chunk() {
+                Some((code, reason)) => {
+                    GatewayError::CloseError(GatewayClose::new(u16::from_le_bytes(*code), String::from_utf8_lossy(reason)))
+                }
+                None => GatewayError::Disconnected,
+            }),
+            _ => Err(GatewayError::InvalidRecording),
+        }
+    }
+}
+
+impl Stream for ReplayGateway {
+    type Item = Result<ServerMsg, GatewayError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        let Some(next) = this.frames.as_slice().first() else {
+            return Poll::Ready(None);
+        };
+
+        if let Some(delay) = this.delay(next.at) {
+            let start = *this.start.get_or_insert_with(tokio::time::Instant::now);
+
+            let timer = this.timer.get_or_insert_with(|| Box::pin(tokio::time::sleep_until(start + delay)));
+
+            if timer.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+
+            this.timer = None;
+        }
+
+        Poll::Ready(this.frames.next().map(ReplayGateway::decode))
+    }
+}
+
+impl Sink<ClientMsg> for ReplayGateway {
+    type Error = GatewayError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), GatewayError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, _msg: ClientMsg) -> Result<(), GatewayError> {
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), GatewayError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), GatewayError>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::{CloseAction, GatewayErrorCode};
+    use futures::StreamExt;
+
+    /// Writer that can be inspected after the recorder takes ownership
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_replay() {
+        let out = Shared::default();
+        let recorder = Recorder::new(out.clone(), RecordMode::Decoded).unwrap();
+
+        recorder.record(Encoding::JSON, &[], Some(&ServerMsg::new_heartbeat_ack()));
+        recorder.record(Encoding::JSON, &[], Some(&ServerMsg::new_room_pins_update()));
+        recorder.record(Encoding::JSON, b"{\"o\":", None);
+        recorder.record_close(Some(&GatewayClose::new(GatewayErrorCode::AuthFailed as u16, "bad token")));
+
+        let data = out.0.lock().unwrap().clone();
+        let mut replay = ReplayGateway::from_bytes(&data, ReplaySpeed::Accelerated(1000.0)).unwrap();
+
+        assert_eq!(replay.remaining(), 4);
+        assert!(matches!(replay.next().await, Some(Ok(ServerMsg::HeartbeatAck(_)))));
+        assert!(matches!(replay.next().await, Some(Ok(ServerMsg::RoomPinsUpdate(_)))));
+        assert!(matches!(replay.next().await, Some(Err(GatewayError::JsonError(_)))));
+        match replay.next().await {
+            Some(Err(GatewayError::CloseError(close))) => {
+                assert_eq!(close.error_code(), Some(GatewayErrorCode::AuthFailed));
+                assert_eq!(close.reason, "bad token");
+                assert_eq!(close.action(), CloseAction::GiveUp);
+            }
+            res => panic!("expected close error, got {res:?}"),
+        }
+        assert!(replay.next().await.is_none());
+
+        assert!(ReplayGateway::from_bytes(b"nope", ReplaySpeed::Instant).is_err());
+    }
+}
@@ -2,15 +2,15 @@ use core::pin::Pin;
 use core::task::{Context, Poll};
 
 use futures::{Sink, Stream};
-use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
 use tokio_tungstenite::tungstenite::Message as WsMessage;
 
-type WebSocket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
-
 use crate::driver::{Driver, Encoding};
 use crate::models::gateway::message::{ClientMsg, ServerMsg};
 
-use super::error::GatewayErrorCode;
+use super::compression::{Compressor, Decompressor};
+use super::config::{GatewayConfig, WebSocket};
+use super::error::GatewayClose;
+use super::record::Recorder;
 use super::GatewayError;
 
 pin_project_lite::pin_project! {
@@ -18,33 +18,54 @@ pin_project_lite::pin_project! {
     pub struct GatewaySocket {
         #[pin]
         ws: WebSocket,
-        encoding: Encoding,
-        compress: bool,
+        codec: Codec,
     }
 }
 
+/// Encoding and compression state for a single connection
+struct Codec {
+    encoding: Encoding,
+    compressor: Compressor,
+    decompressor: Decompressor,
+    recorder: Option<Recorder>,
+}
+
 impl GatewaySocket {
+    /// Connect using the driver's base URL, encoding and default per-message compression
     pub async fn connect(driver: Driver) -> Result<Self, GatewayError> {
-        // TODO: Setup a circuit breaker to run this in a loop until it succeeds
-        let (ws, _) = tokio_tungstenite::connect_async(format!(
-            "ws{}/api/v1/gateway?compress=true&encoding={}",
-            &driver.uri[4..],
-            match driver.encoding {
-                Encoding::JSON => "json",
-                #[cfg(feature = "cbor")]
-                Encoding::CBOR => "cbor",
-            }
-        ))
-        .await?;
+        Self::connect_with(&GatewayConfig::from_driver(&driver)?).await
+    }
+
+    /// Connect with the given configuration
+    pub async fn connect_with(config: &GatewayConfig) -> Result<Self, GatewayError> {
+        let params = config.query_params();
+
+        #[cfg(feature = "zstd")]
+        let (compressor, decompressor) = match config.dictionary() {
+            Some(dict) => (
+                Compressor::with_dictionary(&params, config.level(), dict)?,
+                Decompressor::with_dictionary(&params, dict)?,
+            ),
+            None => (Compressor::new(&params, config.level())?, Decompressor::new(&params)?),
+        };
+
+        #[cfg(not(feature = "zstd"))]
+        let (compressor, decompressor) = (Compressor::new(&params, config.level())?, Decompressor::new(&params)?);
 
         Ok(GatewaySocket {
-            ws,
-            encoding: driver.encoding,
-            compress: true,
+            ws: config.connect().await?,
+            codec: Codec {
+                encoding: params.encoding,
+                compressor,
+                decompressor,
+                recorder: config.recorder().cloned(),
+            },
         })
     }
+}
 
-    fn encode(&self, msg: ClientMsg) -> Result<WsMessage, GatewayError> {
+impl Codec {
+    fn encode(&mut self, msg: ClientMsg) -> Result<WsMessage, GatewayError> {
         let mut body = Vec::new();
 
         match self.encoding {
@@ -53,46 +74,37 @@ impl GatewaySocket {
             Encoding::CBOR => ciborium::ser::into_writer(&msg, &mut body)?,
         }
 
-        if self.compress {
-            body = miniz_oxide::deflate::compress_to_vec_zlib(&body, 9);
-        }
-
-        Ok(WsMessage::Binary(body))
+        Ok(WsMessage::Binary(self.compressor.compress(body)?))
     }
 
-    fn decode(&self, msg: WsMessage) -> Result<ServerMsg, GatewayError> {
-        match &msg {
-            WsMessage::Close(None) => return Err(GatewayError::Disconnected),
-            WsMessage::Close(Some(msg)) => {
-                return Err(match msg.code {
-                    CloseCode::Library(code) => {
-                        use num_traits::FromPrimitive;
-
-                        GatewayError::CloseError(match GatewayErrorCode::from_u16(code) {
-                            Some(code) => code,
-                            None => GatewayErrorCode::UnknownError,
-                        })
-                    }
-                    _ => GatewayError::Disconnected,
-                });
+    fn decode(&mut self, msg: WsMessage) -> Result<ServerMsg, GatewayError> {
+        if let WsMessage::Close(close) = msg {
+            let close = close.map(|frame| GatewayClose::new(frame.code.into(), frame.reason));
+
+            if let Some(ref recorder) = self.recorder {
+                recorder.record_close(close.as_ref());
             }
-            _ => {}
+
+            return Err(match close {
+                Some(close) => GatewayError::CloseError(close),
+                None => GatewayError::Disconnected,
+            });
         }
 
-        let mut body = msg.into_data();
+        let body = self.decompressor.decompress(msg.into_data())?;
+
+        let msg: Result<ServerMsg, GatewayError> = match self.encoding {
+            Encoding::JSON => serde_json::from_slice(&body).map_err(Into::into),
+            #[cfg(feature = "cbor")]
+            Encoding::CBOR => ciborium::de::from_reader(&body[..]).map_err(Into::into),
+        };
 
-        if self.compress {
-            body = match miniz_oxide::inflate::decompress_to_vec_zlib(&body) {
-                Ok(body) => body,
-                Err(_) => return Err(GatewayError::CompressionError),
-            };
+        // record malformed messages as well, so replaying reproduces the failure
+        if let Some(ref recorder) = self.recorder {
+            recorder.record(self.encoding, &body, msg.as_ref().ok());
         }
 
-        Ok(match self.encoding {
-            Encoding::JSON => serde_json::from_slice(&body)?,
-            #[cfg(feature = "cbor")]
-            Encoding::CBOR => ciborium::de::from_reader(&body[..])?,
-        })
+        msg
     }
 }
 
@@ -106,8 +118,9 @@ impl Sink<ClientMsg> for GatewaySocket {
 
     #[inline]
     fn start_send(self: Pin<&mut Self>, msg: ClientMsg) -> Result<(), GatewayError> {
-        let item = self.encode(msg)?;
-        self.project().ws.start_send(item).map_err(GatewayError::from)
+        let this = self.project();
+        let item = this.codec.encode(msg)?;
+        this.ws.start_send(item).map_err(GatewayError::from)
     }
 
     #[inline]
@@ -125,14 +138,54 @@ impl Stream for GatewaySocket {
     type Item = Result<ServerMsg, GatewayError>;
 
     #[inline]
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        let this = self.as_mut().project();
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.project();
 
         Poll::Ready(match this.ws.poll_next(cx) {
             Poll::Ready(None) => None,
-            Poll::Ready(Some(Ok(msg))) => Some(self.decode(msg)),
+            Poll::Ready(Some(Ok(msg))) => Some(this.codec.decode(msg)),
             Poll::Ready(Some(Err(e))) => Some(Err(e.into())),
             Poll::Pending => return Poll::Pending,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::gateway::{GatewayCompression, GatewayQueryParams};
+    use crate::gateway::{RecordMode, ReplayGateway, ReplaySpeed};
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_record_malformed() {
+        let path = std::env::temp_dir().join(format!("lgwrec-malformed-{}", std::process::id()));
+
+        let params = GatewayQueryParams {
+            compress: GatewayCompression::None,
+            ..Default::default()
+        };
+
+        let recorder = Recorder::create(&path, RecordMode::Decoded).unwrap();
+
+        let mut codec = Codec {
+            encoding: Encoding::JSON,
+            compressor: Compressor::new(&params, 0).unwrap(),
+            decompressor: Decompressor::new(&params).unwrap(),
+            recorder: Some(recorder.clone()),
+        };
+
+        assert!(matches!(
+            codec.decode(WsMessage::Binary(b"{\"o\":".to_vec())),
+            Err(GatewayError::JsonError(_))
+        ));
+
+        recorder.flush().unwrap();
+
+        let mut replay = ReplayGateway::open(&path, ReplaySpeed::Instant).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(replay.remaining(), 1);
+        assert!(matches!(replay.next().await, Some(Err(GatewayError::JsonError(_)))));
+    }
+}
@@ -0,0 +1,318 @@
+//! Snowflake ID types, to easier keep track of what ID is for what.
+//!
+//! By default, these are simple aliases of [`Snowflake`]. With the `strict_ids` feature enabled,
+//! each ID is instead a distinct `#[repr(transparent)]` newtype with identical serde, rkyv, SQL,
+//! schema and TypeScript representations, so passing a [`RoomId`] where a [`MessageId`] is expected
+//! becomes a compile-time error.
+//!
+//! Use [`cast_id`] to explicitly convert between kinds of IDs, which works with or without `strict_ids`.
+
+use super::Snowflake;
+
+#[cfg(not(feature = "strict_ids"))]
+macro_rules! decl_aliases {
+    ($($(#[$meta:meta])* $name:ident,)*) => {
+        $(
+            $(#[$meta])*
+            pub type $name = Snowflake;
+
+            #[cfg(feature = "rkyv")]
+            paste::paste! {
+                #[doc = "Archived version of [`" $name "`]"]
+                pub type [<Archived $name>] = rkyv::Archived<Snowflake>;
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "strict_ids")]
+macro_rules! decl_aliases {
+    ($($(#[$meta:meta])* $name:ident,)*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+            #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+            #[cfg_attr(
+                feature = "rkyv",
+                derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+                rkyv(compare(PartialEq))
+            )]
+            #[serde(transparent)]
+            #[repr(transparent)]
+            pub struct $name(pub Snowflake);
+
+            impl core::ops::Deref for $name {
+                type Target = Snowflake;
+
+                #[inline(always)]
+                fn deref(&self) -> &Snowflake {
+                    &self.0
+                }
+            }
+
+            impl From<Snowflake> for $name {
+                #[inline(always)]
+                fn from(id: Snowflake) -> Self {
+                    $name(id)
+                }
+            }
+
+            impl From<$name> for Snowflake {
+                #[inline(always)]
+                fn from(id: $name) -> Self {
+                    id.0
+                }
+            }
+
+            impl PartialEq<Snowflake> for $name {
+                #[inline(always)]
+                fn eq(&self, other: &Snowflake) -> bool {
+                    self.0 == *other
+                }
+            }
+
+            impl PartialEq<$name> for Snowflake {
+                #[inline(always)]
+                fn eq(&self, other: &$name) -> bool {
+                    *self == other.0
+                }
+            }
+
+            impl core::fmt::Display for $name {
+                #[inline]
+                fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                    core::fmt::Display::fmt(&self.0, f)
+                }
+            }
+
+            impl core::str::FromStr for $name {
+                type Err = <Snowflake as core::str::FromStr>::Err;
+
+                #[inline]
+                fn from_str(s: &str) -> Result<Self, Self::Err> {
+                    s.parse().map($name)
+                }
+            }
+
+            #[cfg(feature = "rkyv")]
+            paste::paste! {
+                impl core::ops::Deref for [<Archived $name>] {
+                    type Target = rkyv::Archived<Snowflake>;
+
+                    #[inline(always)]
+                    fn deref(&self) -> &Self::Target {
+                        &self.0
+                    }
+                }
+            }
+
+            #[cfg(feature = "ts")]
+            impl ts_bindgen::TypeScriptDef for $name {
+                #[inline]
+                fn register(registry: &mut ts_bindgen::TypeRegistry) -> ts_bindgen::TypeScriptType {
+                    <Snowflake as ts_bindgen::TypeScriptDef>::register(registry)
+                }
+            }
+
+            #[cfg(feature = "borsh")]
+            const _: () = {
+                use borsh::{BorshDeserialize, BorshSerialize};
+
+                impl BorshSerialize for $name {
+                    #[inline(always)]
+                    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+                        self.0.serialize(writer)
+                    }
+                }
+
+                impl BorshDeserialize for $name {
+                    #[inline(always)]
+                    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+                        Snowflake::deserialize_reader(reader).map($name)
+                    }
+                }
+            };
+
+            #[cfg(feature = "rusqlite")]
+            const _: () = {
+                use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+
+                impl FromSql for $name {
+                    #[inline]
+                    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                        Snowflake::column_result(value).map($name)
+                    }
+                }
+
+                impl ToSql for $name {
+                    #[inline]
+                    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                        self.0.to_sql()
+                    }
+                }
+            };
+
+            #[cfg(feature = "pg")]
+            const _: () = {
+                use core::error::Error;
+
+                use bytes::BytesMut;
+                use postgres_types::{to_sql_checked, FromSql, IsNull, ToSql, Type};
+
+                impl<'a> FromSql<'a> for $name {
+                    #[inline]
+                    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+                        <Snowflake as FromSql>::from_sql(ty, raw).map($name)
+                    }
+
+                    #[inline]
+                    fn accepts(ty: &Type) -> bool {
+                        <Snowflake as FromSql>::accepts(ty)
+                    }
+                }
+
+                impl ToSql for $name {
+                    #[inline]
+                    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+                    where
+                        Self: Sized,
+                    {
+                        self.0.to_sql(ty, out)
+                    }
+
+                    #[inline]
+                    fn accepts(ty: &Type) -> bool {
+                        <Snowflake as ToSql>::accepts(ty)
+                    }
+
+                    to_sql_checked!();
+                }
+            };
+        )*
+    };
+}
+
+decl_aliases! {
+    /// Snowflake ID for a Party
+    PartyId,
+    /// Snowflake ID for a User
+    UserId,
+    /// Snowflake ID for a Role
+    RoleId,
+    /// Snowflake ID for a Room
+    RoomId,
+    /// Snowflake ID for a Message
+    MessageId,
+    /// Snowflake ID for a Custom Emote
+    EmoteId,
+    /// Snowflake ID for a File
+    FileId,
+    /// Snowflake ID for an party Invite
+    InviteId,
+    /// Snowflake ID for a message Thread
+    ThreadId,
+    /// Snowflake ID for a Pin Folder
+    FolderId,
+}
+
+/// Explicitly converts one kind of ID into another, such as the [`PartyId`]
+/// of a party into the [`RoleId`] of its `@everyone` role.
+///
+/// Without the `strict_ids` feature, this is a no-op.
+#[inline(always)]
+#[must_use]
+pub fn cast_id<T, U>(id: T) -> U
+where
+    T: Into<Snowflake>,
+    U: From<Snowflake>,
+{
+    U::from(id.into())
+}
+
+#[cfg(all(feature = "strict_ids", feature = "rkyv"))]
+pub use self::niche::NicheSnowflake;
+
+#[cfg(all(feature = "strict_ids", feature = "rkyv"))]
+mod niche {
+    use rkyv::{
+        rancor::Fallible,
+        with::{ArchiveWith, DeserializeWith, SerializeWith},
+        Place,
+    };
+
+    use super::Snowflake;
+
+    type Inner = snowflake::NicheSnowflake;
+
+    /// Niche-optimized archiving of optional IDs, with the same archived
+    /// representation as [`snowflake::NicheSnowflake`] for `Option<Snowflake>`.
+    pub struct NicheSnowflake;
+
+    impl<T> ArchiveWith<Option<T>> for NicheSnowflake
+    where
+        T: Copy + Into<Snowflake>,
+    {
+        type Archived = <Inner as ArchiveWith<Option<Snowflake>>>::Archived;
+        type Resolver = <Inner as ArchiveWith<Option<Snowflake>>>::Resolver;
+
+        #[inline]
+        fn resolve_with(field: &Option<T>, resolver: Self::Resolver, out: Place<Self::Archived>) {
+            <Inner as ArchiveWith<Option<Snowflake>>>::resolve_with(&field.map(Into::into), resolver, out)
+        }
+    }
+
+    impl<T, S> SerializeWith<Option<T>, S> for NicheSnowflake
+    where
+        T: Copy + Into<Snowflake>,
+        S: Fallible + ?Sized,
+        Inner: SerializeWith<Option<Snowflake>, S>,
+    {
+        #[inline]
+        fn serialize_with(field: &Option<T>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+            <Inner as SerializeWith<Option<Snowflake>, S>>::serialize_with(&field.map(Into::into), serializer)
+        }
+    }
+
+    impl<A, T, D> DeserializeWith<A, Option<T>, D> for NicheSnowflake
+    where
+        T: From<Snowflake>,
+        D: Fallible + ?Sized,
+        Inner: DeserializeWith<A, Option<Snowflake>, D>,
+    {
+        #[inline]
+        fn deserialize_with(field: &A, deserializer: &mut D) -> Result<Option<T>, D::Error> {
+            Ok(<Inner as DeserializeWith<A, Option<Snowflake>, D>>::deserialize_with(field, deserializer)?.map(T::from))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "strict_ids"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strict_ids() {
+        let id: Snowflake = "1234".parse().unwrap();
+        let room: RoomId = "1234".parse().unwrap();
+
+        assert_eq!(room, RoomId(id));
+        assert_eq!(room, id);
+        assert_eq!(*room, id);
+        assert_eq!(room.to_string(), "1234");
+
+        let message: MessageId = cast_id(room);
+        assert_eq!(message, id);
+        assert_eq!(Snowflake::from(message), id);
+
+        assert_eq!(core::mem::size_of::<RoomId>(), core::mem::size_of::<Snowflake>());
+
+        // same representation as the plain snowflake
+        #[cfg(feature = "serde_json")]
+        {
+            let json = serde_json::to_string(&id).unwrap();
+
+            assert_eq!(serde_json::to_string(&room).unwrap(), json);
+            assert_eq!(serde_json::from_str::<RoomId>(&json).unwrap(), room);
+        }
+    }
+}
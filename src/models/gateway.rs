@@ -315,7 +315,200 @@ pub mod message {
         *value == T::default()
     }
 
+    /// Payload of a message with an unknown opcode, see [`ServerMsg::Unknown`]
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+    pub struct UnknownPayload {
+        /// Opcode as sent over the wire
+        pub opcode: u8,
+
+        /// Undecoded payload, or [`RawValue::Null`] if there was none
+        ///
+        /// This is not archived with `rkyv`.
+        #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Skip))]
+        pub payload: RawValue,
+    }
+
+    /// Self-describing value as decoded from JSON or CBOR, used to preserve the payload of unknown messages
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub enum RawValue {
+        #[default]
+        Null,
+        Bool(bool),
+        Int(i64),
+        UInt(u64),
+        Float(f64),
+        String(String),
+        Bytes(Vec<u8>),
+        Array(Vec<RawValue>),
+        Map(Vec<(RawValue, RawValue)>),
+    }
+
+    impl Serialize for RawValue {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            use serde::ser::{SerializeMap, SerializeSeq};
+
+            match self {
+                RawValue::Null => serializer.serialize_unit(),
+                RawValue::Bool(value) => serializer.serialize_bool(*value),
+                RawValue::Int(value) => serializer.serialize_i64(*value),
+                RawValue::UInt(value) => serializer.serialize_u64(*value),
+                RawValue::Float(value) => serializer.serialize_f64(*value),
+                RawValue::String(value) => serializer.serialize_str(value),
+                RawValue::Bytes(value) => serializer.serialize_bytes(value),
+                RawValue::Array(values) => {
+                    let mut seq = serializer.serialize_seq(Some(values.len()))?;
+
+                    for value in values {
+                        seq.serialize_element(value)?;
+                    }
+
+                    seq.end()
+                }
+                RawValue::Map(entries) => {
+                    let mut map = serializer.serialize_map(Some(entries.len()))?;
+
+                    for (key, value) in entries {
+                        map.serialize_entry(key, value)?;
+                    }
+
+                    map.end()
+                }
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for RawValue {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            use core::fmt;
+
+            struct RawValueVisitor;
+
+            impl<'de> Visitor<'de> for RawValueVisitor {
+                type Value = RawValue;
+
+                fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                    formatter.write_str("any value")
+                }
+
+                fn visit_unit<E>(self) -> Result<RawValue, E> {
+                    Ok(RawValue::Null)
+                }
+
+                fn visit_none<E>(self) -> Result<RawValue, E> {
+                    Ok(RawValue::Null)
+                }
+
+                fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<RawValue, D::Error> {
+                    RawValue::deserialize(deserializer)
+                }
+
+                fn visit_bool<E>(self, value: bool) -> Result<RawValue, E> {
+                    Ok(RawValue::Bool(value))
+                }
+
+                fn visit_i64<E>(self, value: i64) -> Result<RawValue, E> {
+                    Ok(RawValue::Int(value))
+                }
+
+                fn visit_u64<E>(self, value: u64) -> Result<RawValue, E> {
+                    Ok(RawValue::UInt(value))
+                }
+
+                fn visit_f64<E>(self, value: f64) -> Result<RawValue, E> {
+                    Ok(RawValue::Float(value))
+                }
+
+                fn visit_str<E>(self, value: &str) -> Result<RawValue, E> {
+                    Ok(RawValue::String(value.to_owned()))
+                }
+
+                fn visit_string<E>(self, value: String) -> Result<RawValue, E> {
+                    Ok(RawValue::String(value))
+                }
+
+                fn visit_bytes<E>(self, value: &[u8]) -> Result<RawValue, E> {
+                    Ok(RawValue::Bytes(value.to_vec()))
+                }
+
+                fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<RawValue, E> {
+                    Ok(RawValue::Bytes(value))
+                }
+
+                fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<RawValue, A::Error> {
+                    let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(64));
+
+                    while let Some(value) = seq.next_element()? {
+                        values.push(value);
+                    }
+
+                    Ok(RawValue::Array(values))
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawValue, A::Error> {
+                    let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(64));
+
+                    while let Some(entry) = map.next_entry()? {
+                        entries.push(entry);
+                    }
+
+                    Ok(RawValue::Map(entries))
+                }
+            }
+
+            deserializer.deserialize_any(RawValueVisitor)
+        }
+    }
+
     macro_rules! decl_msgs {
+        // opcode accessors, which are optional for messages that may be unknown
+        (@opcode $name:ident [] $($opcode:ident)*) => {paste::paste!{
+            impl $name {
+                /// Returns the discrete opcode for the message
+                #[must_use]
+                pub const fn opcode(&self) -> [<$name Opcode>] {
+                    match self {
+                        $($name::$opcode(_) => [<$name Opcode>]::$opcode,)*
+                    }
+                }
+            }
+
+            impl From<&$name> for [<$name Opcode>] {
+                #[inline]
+                fn from(msg: &$name) -> [<$name Opcode>] {
+                    msg.opcode()
+                }
+            }
+        }};
+
+        (@opcode $name:ident [$Unknown:ident] $($opcode:ident)*) => {paste::paste!{
+            impl $name {
+                #[doc = "Returns the discrete opcode for the message, or `None` if it's [unknown](" $name "::" $Unknown ")"]
+                #[must_use]
+                pub const fn opcode(&self) -> Option<[<$name Opcode>]> {
+                    match self {
+                        $($name::$opcode(_) => Some([<$name Opcode>]::$opcode),)*
+                        $name::$Unknown(_) => None,
+                    }
+                }
+            }
+        }};
+
+        // decodes a message with an unrecognized opcode, rejecting it unless it may be unknown
+        (@unknown $name:ident [] $opcode:ident $payload:expr) => {
+            Err(de::Error::invalid_value(de::Unexpected::Unsigned($opcode as u64), &"a known opcode"))
+        };
+
+        (@unknown $name:ident [$Unknown:ident] $opcode:ident $payload:expr) => {
+            Ok($name::$Unknown(Box::new(UnknownPayload { opcode: $opcode, payload: $payload })))
+        };
+
         (
             $(#[$meta:meta])*
             enum $name:ident {
@@ -325,10 +518,12 @@ pub mod message {
                         $( $(#[$field_meta:meta])* $field:ident $(*$Deref:ident)? : $ty:ty),*$(,)?
                     }
                 ),*$(,)*
+
+                $(_ => $Unknown:ident $(,)?)?
             }
         ) => {paste::paste!{
             #[doc = "OpCodes for [`" $name "`]"]
-            #[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr)]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
             #[cfg_attr(feature = "schema", derive(schemars::JsonSchema_repr))]
             #[cfg_attr(feature = "ts", derive(ts_bindgen::TypeScriptDef), ts(tag = "gateway"))]
             #[repr(u8)]
@@ -336,6 +531,11 @@ pub mod message {
                 $($opcode = $code,)*
             }
 
+            impl [<$name Opcode>] {
+                #[doc = "All opcodes for [`" $name "`], in order"]
+                pub const ALL: &'static [Self] = &[$(Self::$opcode,)*];
+            }
+
             pub mod [<$name:snake _payloads>] {
                 use super::*;
 
@@ -343,7 +543,7 @@ pub mod message {
                     $(#[$variant_meta])*
                     #[doc = ""]
                     #[doc = "Payload struct for [`" $name "::" $opcode "`]"]
-                    #[derive(Debug, Serialize, Deserialize)]
+                    #[derive(Debug, Clone, Serialize, Deserialize)]
                     #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
                     #[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
                     #[cfg_attr(feature = "ts", derive(ts_bindgen::TypeScriptDef), ts(tag = "gateway"))]
@@ -450,6 +650,12 @@ pub mod message {
                     (self.fallback)(self.state.clone(), ctx, msg).await
                 }
 
+                fn has_handler(&self, opcode: [<$name Opcode>]) -> bool {
+                    match opcode {
+                        $([<$name Opcode>]::$opcode => self.[<$opcode:snake _handler>].is_some(),)*
+                    }
+                }
+
                 $(
                     async fn [<$opcode:snake>](&self, ctx: C, $($field: $ty,)*) -> U {
                         match self.[<$opcode:snake _handler>] {
@@ -469,12 +675,25 @@ pub mod message {
                         $($name::$opcode([<$name:snake _payloads>]::[<$opcode Payload>] { $($field,)* }) => {
                             self.[<$opcode:snake>](ctx, $($field,)*).await
                         })*
+                        $($name::$Unknown(payload) => self.fallback(ctx, $name::$Unknown(payload)).await,)?
                     }
                 }
 
                 /// Callback for unhandled messages
+                $(
+                    #[doc = ""]
+                    #[doc = "This includes any [unknown](" $name "::" $Unknown ") messages."]
+                )?
                 async fn fallback(&self, ctx: C, msg: $name) -> U;
 
+                /// Whether a specific handler is known to be registered for the given opcode, rather than
+                /// relying on the fallback. Used for diagnostics only, so this defaults to `false`.
+                #[inline(always)]
+                fn has_handler(&self, opcode: [<$name Opcode>]) -> bool {
+                    let _ = opcode;
+                    false
+                }
+
                 $(
                     $(#[$variant_meta])*
                     #[doc = ""]
@@ -487,7 +706,7 @@ pub mod message {
             }
 
             $(#[$meta])*
-            #[derive(Debug)]
+            #[derive(Debug, Clone)]
             #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
             #[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
             #[repr(u8)]
@@ -499,23 +718,34 @@ pub mod message {
                     #[cfg_attr(feature = "schema", schemars(description = "" $name "::" $opcode "" ))]
                     $opcode([<$name:snake _payloads>]::[<$opcode Payload>]) = $code,
                 )*
+
+                $(
+                    /// Message with an opcode not known to this version of the library, kept as-is rather than
+                    /// failing to decode. Unknown messages are dispatched to the fallback handler.
+                    #[cfg_attr(feature = "schema", schemars(skip))]
+                    $Unknown(Box<UnknownPayload>),
+                )?
             }
 
+            decl_msgs!(@opcode $name [$($Unknown)?] $($opcode)*);
+
             impl $name {
-                /// Returns the discrete opcode for the message
+                /// Returns the opcode as sent over the wire, which may be unknown
                 #[must_use]
-                pub const fn opcode(&self) -> [<$name Opcode>] {
+                pub const fn raw_opcode(&self) -> u8 {
                     match self {
-                        $($name::$opcode(_) => [<$name Opcode>]::$opcode,)*
+                        $($name::$opcode(_) => $code,)*
+                        $($name::$Unknown(payload) => payload.opcode,)?
                     }
                 }
-            }
 
-            impl From<&$name> for [<$name Opcode>] {
-                #[inline]
-                fn from(msg: &$name) -> [<$name Opcode>] {
-                    msg.opcode()
-                }
+                $(
+                    #[doc = "Create a new [unknown](" $name "::" $Unknown ") message with an arbitrary opcode and payload."]
+                    #[inline] #[must_use]
+                    pub fn [<new_ $Unknown:snake>](opcode: u8, payload: RawValue) -> Self {
+                        $name::$Unknown(Box::new(UnknownPayload { opcode, payload }))
+                    }
+                )?
             }
 
             impl $name {
@@ -588,7 +818,23 @@ pub mod message {
 
                             state
                         }
-                    )*};
+                    )*
+                    $(
+                        $name::$Unknown(payload) => {
+                            let skip_payload = payload.payload == RawValue::Null;
+
+                            let mut state = serializer.serialize_struct(stringify!($name), 2 - skip_payload as usize)?;
+
+                            state.serialize_field("o", &payload.opcode)?;
+
+                            if !skip_payload {
+                                state.serialize_field("p", &payload.payload)?;
+                            }
+
+                            state
+                        }
+                    )?
+                    };
 
                     state.end()
                 }
@@ -624,14 +870,14 @@ pub mod message {
                         where
                             V: MapAccess<'de>,
                         {
-                            let opcode = match map.next_entry()? {
+                            let opcode: u8 = match map.next_entry()? {
                                 Some((Field::Opcode, o)) => o,
                                 _ => return Err(de::Error::custom("Missing opcode first")),
                             };
 
                             match opcode {
                                 $(
-                                    [<$name Opcode>]::$opcode => Ok($name::$opcode(match map.next_entry()? {
+                                    $code => Ok($name::$opcode(match map.next_entry()? {
                                         Some((Field::Payload, payload)) => payload,
                                         $(None => $Default::default(),)?
 
@@ -639,7 +885,10 @@ pub mod message {
                                         _ => return Err(de::Error::missing_field("payload")),
                                     })),
                                 )*
-                                // _ => Err(de::Error::custom("Invalid opcode")),
+                                _ => decl_msgs!(@unknown $name [$($Unknown)?] opcode match map.next_entry()? {
+                                    Some((Field::Payload, payload)) => payload,
+                                    _ => RawValue::Null,
+                                }),
                             }
                         }
 
@@ -647,14 +896,14 @@ pub mod message {
                         where
                             A: SeqAccess<'de>
                         {
-                            let opcode: [<$name Opcode>] = match seq.next_element()? {
+                            let opcode: u8 = match seq.next_element()? {
                                 Some(o) => o,
                                 _ => return Err(de::Error::custom("Missing opcode first")),
                             };
 
                             match opcode {
                                 $(
-                                    [<$name Opcode>]::$opcode => Ok($name::$opcode(match seq.next_element()? {
+                                    $code => Ok($name::$opcode(match seq.next_element()? {
                                         Some(payload) => payload,
                                         $(None => $Default::default(),)?
 
@@ -662,6 +911,7 @@ pub mod message {
                                         _ => return Err(de::Error::missing_field("payload")),
                                     })),
                                 )*
+                                _ => decl_msgs!(@unknown $name [$($Unknown)?] opcode seq.next_element()?.unwrap_or_default()),
                             }
                         }
                     }
@@ -734,6 +984,9 @@ pub mod message {
             29 => ProfileUpdate { #[serde(flatten)] inner *Deref: Arc<ProfileUpdateEvent> },
             30 => RelationAdd { #[serde(flatten)] inner *Deref: Arc<Relationship> },
             31 => RelationRemove { user_id: UserId },
+
+            // newer servers may send messages this version doesn't know about, which shouldn't break older clients
+            _ => Unknown,
         }
     }
 
@@ -749,63 +1002,144 @@ pub mod message {
         }
     }
 
-    impl ServerMsg {
+    impl ServerMsgOpcode {
+        /// The intent required to receive messages with this opcode, if any.
+        ///
+        /// Party-specific [`ProfileUpdate`](ServerMsg::ProfileUpdate) messages may also be received with
+        /// [`Intent::PARTY_MEMBERS`], see [`ServerMsg::matching_intent`].
         #[rustfmt::skip] #[must_use]
-        pub fn matching_intent(&self) -> Option<Intent> {
-            Some(match *self {
-                | ServerMsg::PartyCreate { .. }
-                | ServerMsg::PartyDelete { .. }
-                | ServerMsg::PartyUpdate { .. }
-                | ServerMsg::RoleCreate { .. }
-                | ServerMsg::RoleDelete { .. }
-                | ServerMsg::RoleUpdate { .. }
-                | ServerMsg::RoomPinsUpdate { .. }
-                | ServerMsg::RoomCreate { .. }
-                | ServerMsg::RoomDelete { .. }
-                | ServerMsg::RoomUpdate { .. }
+        pub const fn required_intent(self) -> Option<Intent> {
+            Some(match self {
+                | ServerMsgOpcode::PartyCreate
+                | ServerMsgOpcode::PartyDelete
+                | ServerMsgOpcode::PartyUpdate
+                | ServerMsgOpcode::RoleCreate
+                | ServerMsgOpcode::RoleDelete
+                | ServerMsgOpcode::RoleUpdate
+                | ServerMsgOpcode::RoomPinsUpdate
+                | ServerMsgOpcode::RoomCreate
+                | ServerMsgOpcode::RoomDelete
+                | ServerMsgOpcode::RoomUpdate
                     => Intent::PARTIES,
 
-                | ServerMsg::MemberAdd { .. }
-                | ServerMsg::MemberUpdate { .. }
-                | ServerMsg::MemberRemove { .. }
+                | ServerMsgOpcode::MemberAdd
+                | ServerMsgOpcode::MemberUpdate
+                | ServerMsgOpcode::MemberRemove
                     => Intent::PARTY_MEMBERS,
 
-                | ServerMsg::MemberBan {..}
-                | ServerMsg::MemberUnban {..}
+                | ServerMsgOpcode::MemberBan
+                | ServerMsgOpcode::MemberUnban
                     => Intent::PARTY_BANS,
 
-                | ServerMsg::MessageCreate { .. }
-                | ServerMsg::MessageDelete { .. }
-                | ServerMsg::MessageUpdate { .. }
+                | ServerMsgOpcode::MessageCreate
+                | ServerMsgOpcode::MessageDelete
+                | ServerMsgOpcode::MessageUpdate
                     => Intent::MESSAGES,
 
-                | ServerMsg::MessageReactionAdd { .. }
-                | ServerMsg::MessageReactionRemove { .. }
-                | ServerMsg::MessageReactionRemoveAll { .. }
-                | ServerMsg::MessageReactionRemoveEmote { .. }
+                | ServerMsgOpcode::MessageReactionAdd
+                | ServerMsgOpcode::MessageReactionRemove
+                | ServerMsgOpcode::MessageReactionRemoveAll
+                | ServerMsgOpcode::MessageReactionRemoveEmote
                     => Intent::MESSAGE_REACTIONS,
 
-                ServerMsg::PresenceUpdate { .. }
+                ServerMsgOpcode::PresenceUpdate
                     => Intent::PRESENCE,
 
-                ServerMsg::TypingStart { .. }
+                ServerMsgOpcode::TypingStart
                     => Intent::MESSAGE_TYPING,
 
-                ServerMsg::ProfileUpdate(ref payload) => match payload.inner.party_id {
-                    Some(_) => Intent::PROFILE_UPDATES | Intent::PARTY_MEMBERS,
-                    None => Intent::PROFILE_UPDATES,
-                }
+                ServerMsgOpcode::ProfileUpdate
+                    => Intent::PROFILE_UPDATES,
 
-                | ServerMsg::Hello { .. }
-                | ServerMsg::HeartbeatAck { .. }
-                | ServerMsg::Ready { .. }
-                | ServerMsg::UserUpdate { .. }
-                | ServerMsg::InvalidSession { .. }
-                | ServerMsg::RelationAdd { .. }
-                | ServerMsg::RelationRemove { .. }
+                | ServerMsgOpcode::Hello
+                | ServerMsgOpcode::HeartbeatAck
+                | ServerMsgOpcode::Ready
+                | ServerMsgOpcode::UserUpdate
+                | ServerMsgOpcode::InvalidSession
+                | ServerMsgOpcode::RelationAdd
+                | ServerMsgOpcode::RelationRemove
                     => return None,
             })
         }
+    }
+
+    impl ServerMsg {
+        /// The intents that would cause this message to be sent, if any.
+        #[must_use]
+        pub fn matching_intent(&self) -> Option<Intent> {
+            match *self {
+                ServerMsg::ProfileUpdate(ref payload) if payload.inner.party_id.is_some() => {
+                    Some(Intent::PROFILE_UPDATES | Intent::PARTY_MEMBERS)
+                }
+                _ => self.opcode()?.required_intent(),
+            }
+        }
+
+        /// Whether this message would be sent to a session identified with the given intents.
+        ///
+        /// Messages that do not depend on any intent are always accepted.
+        #[must_use]
+        pub fn is_within(&self, intents: Intent) -> bool {
+            match self.matching_intent() {
+                Some(intent) => intents.intersects(intent),
+                None => true,
+            }
+        }
+
+        /// If the event pertains to a specific party, get its ID
+        #[must_use]
+        pub fn party_id(&self) -> Option<PartyId> {
+            Some(match self {
+                ServerMsg::PartyCreate(p) => p.partial.id,
+                ServerMsg::PartyUpdate(p) => match *p.inner {
+                    PartyUpdateEvent::Position(ref u) => u.id,
+                    PartyUpdateEvent::Full(ref party) => party.partial.id,
+                },
+                ServerMsg::PartyDelete(p) => p.id,
+
+                ServerMsg::RoleCreate(r) => r.party_id,
+                ServerMsg::RoleUpdate(r) => r.party_id,
+                ServerMsg::RoleDelete(r) => r.party_id,
+
+                ServerMsg::MemberAdd(e) => e.party_id,
+                ServerMsg::MemberUpdate(e) => e.party_id,
+                ServerMsg::MemberRemove(e) => e.party_id,
+                ServerMsg::MemberBan(e) => e.party_id,
+                ServerMsg::MemberUnban(e) => e.party_id,
+
+                ServerMsg::RoomDelete(r) => return r.party_id,
+
+                ServerMsg::MessageCreate(m) => m.party_id,
+                ServerMsg::MessageUpdate(m) => m.party_id,
+                ServerMsg::MessageDelete(m) => m.party_id,
+
+                ServerMsg::MessageReactionAdd(r) => r.party_id,
+                ServerMsg::MessageReactionRemove(r) => r.party_id,
+
+                ServerMsg::PresenceUpdate(p) => return p.party_id,
+                ServerMsg::TypingStart(t) => t.party_id,
+                ServerMsg::ProfileUpdate(p) => return p.party_id,
+                _ => return None,
+            })
+        }
+
+        /// If the event pertains to a specific room, get its ID
+        #[must_use]
+        pub fn room_id(&self) -> Option<RoomId> {
+            Some(match self {
+                ServerMsg::RoomDelete(r) => r.id,
+
+                ServerMsg::MessageCreate(m) => m.room_id,
+                ServerMsg::MessageUpdate(m) => m.room_id,
+                ServerMsg::MessageDelete(m) => m.room_id,
+
+                ServerMsg::MessageReactionAdd(r) => r.room_id,
+                ServerMsg::MessageReactionRemove(r) => r.room_id,
+
+                ServerMsg::TypingStart(t) => t.room_id,
+                _ => return None,
+            })
+        }
 
         /// If the event originated from a specific user, get their ID
         #[must_use]
@@ -840,6 +1174,54 @@ pub mod message {
             assert_eq!(16, size_of::<ClientMsg>());
         }
 
+        #[cfg(feature = "serde_json")]
+        #[test]
+        fn test_unknown_opcode() {
+            let json = r#"{"o":200,"p":{"id":"123","flags":[1,-2,0.5,null,true]}}"#;
+
+            let msg: ServerMsg = serde_json::from_str(json).unwrap();
+
+            assert_eq!(msg.opcode(), None);
+            assert_eq!(msg.raw_opcode(), 200);
+            assert!(msg.is_within(Intent::empty()));
+            assert_eq!(serde_json::to_string(&msg).unwrap(), json);
+
+            let msg: ServerMsg = serde_json::from_str(r#"{"o":201}"#).unwrap();
+
+            assert!(matches!(msg, ServerMsg::Unknown(ref payload) if payload.payload == RawValue::Null));
+            assert_eq!(serde_json::to_string(&msg).unwrap(), r#"{"o":201}"#);
+
+            // opcode 255 is not reserved
+            let msg: ServerMsg = serde_json::from_str(r#"{"o":255}"#).unwrap();
+            assert_eq!(msg.raw_opcode(), 255);
+
+            // known opcodes still decode normally
+            let msg: ServerMsg = serde_json::from_str(r#"{"o":1}"#).unwrap();
+            assert_eq!(msg.opcode(), Some(ServerMsgOpcode::HeartbeatAck));
+
+            // unknown client messages are rejected rather than kept
+            assert!(serde_json::from_str::<ClientMsg>(r#"{"o":200}"#).is_err());
+            assert!(serde_json::from_str::<ClientMsg>(r#"[200,null]"#).is_err());
+
+            let msg: ClientMsg = serde_json::from_str(r#"{"o":0}"#).unwrap();
+            assert_eq!(msg.opcode(), ClientMsgOpcode::Heartbeat);
+        }
+
+        #[test]
+        fn test_intent_filter() {
+            let pins = ServerMsg::new_room_pins_update();
+
+            assert_eq!(pins.matching_intent(), Some(Intent::PARTIES));
+            assert!(pins.is_within(Intent::PARTIES | Intent::MESSAGES));
+            assert!(!pins.is_within(Intent::MESSAGES));
+
+            // always received, regardless of intents
+            assert!(ServerMsg::new_heartbeat_ack().is_within(Intent::empty()));
+
+            assert_eq!(ServerMsgOpcode::ALL.len(), 32);
+            assert_eq!(ServerMsgOpcode::TypingStart.required_intent(), Some(Intent::MESSAGE_TYPING));
+        }
+
         #[cfg(feature = "ts")]
         #[test]
         fn test_server_msg_ts() {
@@ -26,48 +26,7 @@ pub use sf::Snowflake;
 /// Used for certain asset IDs, such as avatars.
 pub type EncryptedSnowflake = FixedStr<22>;
 
-/// Defines Snowflake aliases to easier keep track of what ID is for what.
-pub mod aliases {
-    use super::Snowflake;
-
-    macro_rules! decl_aliases {
-        ($($(#[$meta:meta])* $name:ident,)*) => {
-            $(
-                $(#[$meta])*
-                pub type $name = Snowflake;
-
-                #[cfg(feature = "rkyv")]
-                paste::paste! {
-                    #[doc = "Archived version of [`" $name "`]"]
-                    pub type [<Archived $name>] = rkyv::Archived<Snowflake>;
-                }
-            )*
-        };
-    }
-
-    decl_aliases! {
-        /// Snowflake ID for a Party
-        PartyId,
-        /// Snowflake ID for a User
-        UserId,
-        /// Snowflake ID for a Role
-        RoleId,
-        /// Snowflake ID for a Room
-        RoomId,
-        /// Snowflake ID for a Message
-        MessageId,
-        /// Snowflake ID for a Custom Emote
-        EmoteId,
-        /// Snowflake ID for a File
-        FileId,
-        /// Snowflake ID for an party Invite
-        InviteId,
-        /// Snowflake ID for a message Thread
-        ThreadId,
-        /// Snowflake ID for a Pin Folder
-        FolderId,
-    }
-}
+pub mod aliases;
 
 pub use aliases::*;
 
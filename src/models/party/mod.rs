@@ -108,6 +108,23 @@ impl Deref for Party {
     }
 }
 
+impl Party {
+    /// ID of the `@everyone` role, which shares its ID with the party itself.
+    #[inline]
+    #[must_use]
+    pub fn everyone_role_id(&self) -> RoleId {
+        cast_id(self.id)
+    }
+
+    /// The `@everyone` role of the party, if roles were included.
+    #[must_use]
+    pub fn everyone_role(&self) -> Option<&Role> {
+        let id = self.everyone_role_id();
+
+        self.roles.iter().find(|role| role.id == id)
+    }
+}
+
 #[cfg(feature = "rkyv")]
 impl Deref for ArchivedParty {
     type Target = ArchivedPartialParty;
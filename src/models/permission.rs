@@ -1,3 +1,5 @@
+use core::fmt;
+
 use super::*;
 
 /// Constructs a `Permissions` set from a list of permissions by name.
@@ -238,6 +240,15 @@ impl Overwrite {
         // self.allow(base & !self.deny) | self.allow
         base.difference(self.deny).union(self.allow)
     }
+
+    /// Sorts overwrites so that role overwrites come before user overwrites,
+    /// as expected by [`Permissions::compute_overwrites`].
+    ///
+    /// The `@everyone` overwrite, which shares its ID with the party, is always considered a role overwrite.
+    /// Any other overwrite with an ID not found in `roles` is considered a user overwrite.
+    pub fn sort_role_first(overwrites: &mut [Overwrite], roles: &[Role], everyone: RoleId) {
+        overwrites.sort_by_key(|o| !(everyone == o.id || roles.iter().any(|r| r.id == o.id)));
+    }
 }
 
 impl Permissions {
@@ -296,6 +307,23 @@ impl Permissions {
         self
     }
 
+    /// Computes the base permissions from a set of party roles, taking the union of the
+    /// `@everyone` role (which shares its ID with the party) and any roles in `user_roles`.
+    ///
+    /// This does not account for party ownership or normalization.
+    #[must_use]
+    pub fn from_roles(roles: &[Role], everyone: RoleId, user_roles: &[RoleId]) -> Permissions {
+        let mut base = Permissions::empty();
+
+        for role in roles {
+            if role.id == everyone || user_roles.contains(&role.id) {
+                base |= role.permissions;
+            }
+        }
+
+        base
+    }
+
     /// Computes the final permissions for a user in a room given the overwrites and roles.
     #[must_use]
     pub fn compute_overwrites(mut self, overwrites: &[Overwrite], user_roles: &[RoleId], user_id: UserId) -> Permissions {
@@ -314,7 +342,7 @@ impl Permissions {
 
         // overwrites are always sorted role-first
         for overwrite in overwrites {
-            if user_roles.contains(&overwrite.id) {
+            if user_roles.contains(&cast_id(overwrite.id)) {
                 deny |= overwrite.deny;
                 allow |= overwrite.allow;
             } else if overwrite.id == user_id {
@@ -335,6 +363,278 @@ impl Permissions {
     }
 }
 
+/// Computes the party-wide permissions of a member from the `@everyone` role and their assigned roles.
+///
+/// The party owner is always granted all permissions, and the result is [normalized](Permissions::normalize).
+#[must_use]
+pub fn resolve_party_permissions(party: &Party, member: &PartyMember) -> Permissions {
+    if party.owner == member.user.id {
+        return Permissions::all();
+    }
+
+    Permissions::from_roles(&party.roles, party.everyone_role_id(), &member.roles).normalize()
+}
+
+/// Computes the effective permissions of a party member within a room.
+///
+/// This combines the `@everyone` role, the member's roles, party ownership and
+/// the room's overwrites. Overwrites are sorted role-first internally,
+/// so the order they are given in by the room does not matter.
+#[must_use]
+pub fn resolve_permissions(party: &Party, room: &Room, member: &PartyMember) -> Permissions {
+    let base = resolve_party_permissions(party, member);
+
+    // owners and administrators bypass overwrites entirely
+    if base.is_admin() {
+        return base;
+    }
+
+    let mut overwrites = room.overwrites.to_vec();
+    Overwrite::sort_role_first(&mut overwrites, &party.roles, party.everyone_role_id());
+
+    let mut user_roles = Vec::with_capacity(member.roles.len() + 1);
+    user_roles.push(party.everyone_role_id());
+    user_roles.extend_from_slice(&member.roles);
+
+    base.compute_overwrites(&overwrites, &user_roles, member.user.id)
+}
+
+/// Source of a permission change, as traced by [`PermissionExplanation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermissionSource {
+    /// The member owns the party, which grants all permissions.
+    Owner,
+    /// Permissions granted by a party role, including `@everyone`.
+    Role { id: RoleId, name: SmolStr },
+    /// `DEFAULT_ONLY` resets the base permissions to [`Permissions::DEFAULT`].
+    DefaultOnly,
+    /// `ADMINISTRATOR` grants all permissions and bypasses any overwrites.
+    Administrator,
+    /// Room overwrite for one of the member's roles, named if the role is known to the party.
+    RoleOverwrite { id: RoleId, name: Option<SmolStr> },
+    /// Room overwrite for the member themselves.
+    UserOverwrite(UserId),
+}
+
+impl fmt::Display for PermissionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionSource::Owner => f.write_str("party owner"),
+            PermissionSource::Role { id, name } => write!(f, "role \"{name}\" ({id})"),
+            PermissionSource::DefaultOnly => f.write_str("DEFAULT_ONLY permission"),
+            PermissionSource::Administrator => f.write_str("ADMINISTRATOR permission"),
+            PermissionSource::RoleOverwrite { id, name: Some(name) } => write!(f, "room overwrite for role \"{name}\" ({id})"),
+            PermissionSource::RoleOverwrite { id, name: None } => write!(f, "room overwrite for unknown role ({id})"),
+            PermissionSource::UserOverwrite(id) => write!(f, "room overwrite for user {id}"),
+        }
+    }
+}
+
+/// Whether a [`PermissionStep`] allowed or denied a permission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PermissionEffect {
+    Allow,
+    Deny,
+}
+
+/// A single step in computing the effective permissions of a member.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionStep {
+    pub source: PermissionSource,
+    pub allow: Permissions,
+    pub deny: Permissions,
+}
+
+/// Ordered trace of every role and overwrite that contributed to a member's
+/// effective permissions, as returned by [`explain_permissions`].
+///
+/// Steps are listed in the order they are applied, so the last step affecting
+/// a permission is the one that decided it. Denies from role overwrites are
+/// listed before their allows, since role overwrite allows take precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionExplanation {
+    pub steps: Vec<PermissionStep>,
+
+    /// Final effective permissions, equivalent to [`resolve_permissions`].
+    pub result: Permissions,
+}
+
+impl PermissionExplanation {
+    fn push(&mut self, source: PermissionSource, allow: Permissions, deny: Permissions) {
+        if !(allow.is_empty() && deny.is_empty()) {
+            self.steps.push(PermissionStep { source, allow, deny });
+        }
+    }
+
+    /// Traces the permission computation for a set of roles and room overwrites,
+    /// not accounting for party ownership.
+    #[must_use]
+    pub fn compute(
+        roles: &[Role],
+        everyone: RoleId,
+        overwrites: &[Overwrite],
+        user_roles: &[RoleId],
+        user_id: UserId,
+    ) -> PermissionExplanation {
+        let mut explanation = PermissionExplanation {
+            steps: Vec::new(),
+            result: Permissions::empty(),
+        };
+
+        let mut base = Permissions::empty();
+
+        for role in roles.iter().filter(|role| role.id == everyone || user_roles.contains(&role.id)) {
+            base |= role.permissions;
+
+            let source = PermissionSource::Role {
+                id: role.id,
+                name: role.name.clone(),
+            };
+
+            explanation.push(source, role.permissions, Permissions::empty());
+        }
+
+        // same order as `Permissions::normalize`
+        if base.contains(Permissions::DEFAULT_ONLY) {
+            explanation.push(PermissionSource::DefaultOnly, Permissions::DEFAULT, !Permissions::DEFAULT);
+            base = Permissions::DEFAULT;
+        }
+
+        if base.is_admin() {
+            explanation.push(PermissionSource::Administrator, Permissions::all(), Permissions::empty());
+            explanation.result = Permissions::all();
+
+            return explanation;
+        }
+
+        let mut overwrites = overwrites.to_vec();
+        Overwrite::sort_role_first(&mut overwrites, roles, everyone);
+
+        let role_overwrite_source = |o: &Overwrite| PermissionSource::RoleOverwrite {
+            id: cast_id(o.id),
+            name: roles.iter().find(|r| r.id == o.id).map(|role| role.name.clone()),
+        };
+
+        let role_overwrites: Vec<&Overwrite> =
+            overwrites.iter().filter(|o| everyone == o.id || user_roles.contains(&cast_id(o.id))).collect();
+
+        for o in &role_overwrites {
+            explanation.push(role_overwrite_source(o), Permissions::empty(), o.deny);
+        }
+
+        for o in &role_overwrites {
+            explanation.push(role_overwrite_source(o), o.allow, Permissions::empty());
+        }
+
+        if let Some(o) = overwrites.iter().find(|o| o.id == user_id) {
+            explanation.push(PermissionSource::UserOverwrite(user_id), o.allow, o.deny);
+        }
+
+        let mut member_roles = Vec::with_capacity(user_roles.len() + 1);
+        member_roles.push(everyone);
+        member_roles.extend_from_slice(user_roles);
+
+        explanation.result = base.compute_overwrites(&overwrites, &member_roles, user_id);
+        explanation
+    }
+
+    /// Returns `true` if all of the given permissions are granted.
+    #[inline]
+    #[must_use]
+    pub fn is_granted(&self, perms: Permissions) -> bool {
+        self.result.contains(perms)
+    }
+
+    /// Ordered chain of sources that affected any of the given permissions.
+    ///
+    /// A step that both denies and allows a permission yields the deny first.
+    pub fn trace(&self, perms: Permissions) -> impl Iterator<Item = (&PermissionSource, PermissionEffect)> + '_ {
+        self.steps.iter().flat_map(move |step| {
+            let deny = step.deny.intersects(perms).then_some((&step.source, PermissionEffect::Deny));
+            let allow = step.allow.intersects(perms).then_some((&step.source, PermissionEffect::Allow));
+
+            deny.into_iter().chain(allow)
+        })
+    }
+
+    /// Renders a readable report for only the given permissions.
+    ///
+    /// The [`Display`](fmt::Display) implementation of `PermissionExplanation`
+    /// renders a report for all permissions.
+    #[must_use]
+    pub fn report(&self, perms: Permissions) -> PermissionReport<'_> {
+        PermissionReport {
+            explanation: self,
+            perms,
+        }
+    }
+}
+
+/// Readable per-permission report of a [`PermissionExplanation`], see [`PermissionExplanation::report`].
+#[derive(Debug, Clone, Copy)]
+pub struct PermissionReport<'a> {
+    explanation: &'a PermissionExplanation,
+    perms: Permissions,
+}
+
+impl fmt::Display for PermissionReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, flag) in self.perms.iter_names() {
+            let state = if self.explanation.is_granted(flag) { "allowed" } else { "denied" };
+
+            writeln!(f, "{name}: {state}")?;
+
+            let mut traced = false;
+            for (source, effect) in self.explanation.trace(flag) {
+                traced = true;
+
+                match effect {
+                    PermissionEffect::Allow => writeln!(f, "  + allowed by {source}")?,
+                    PermissionEffect::Deny => writeln!(f, "  - denied by {source}")?,
+                }
+            }
+
+            if !traced {
+                writeln!(f, "  (not granted by any role)")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for PermissionExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.report(Permissions::all()), f)
+    }
+}
+
+/// Explains the effective permissions of a party member within a room,
+/// tracing which roles and overwrites allowed or denied each permission.
+///
+/// The [`result`](PermissionExplanation::result) is always equal to [`resolve_permissions`].
+#[must_use]
+pub fn explain_permissions(party: &Party, room: &Room, member: &PartyMember) -> PermissionExplanation {
+    if party.owner == member.user.id {
+        return PermissionExplanation {
+            steps: vec![PermissionStep {
+                source: PermissionSource::Owner,
+                allow: Permissions::all(),
+                deny: Permissions::empty(),
+            }],
+            result: Permissions::all(),
+        };
+    }
+
+    PermissionExplanation::compute(
+        &party.roles,
+        party.everyone_role_id(),
+        &room.overwrites,
+        &member.roles,
+        member.user.id,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -350,4 +650,174 @@ mod tests {
         let [low, high] = Permissions::all().to_i64();
         assert_eq!(Permissions::from_i64(low, high), Permissions::all());
     }
+
+    fn sf<T: From<Snowflake>>(id: u64) -> T {
+        T::from(id.to_string().parse().unwrap())
+    }
+
+    fn role(id: u64, permissions: Permissions) -> Role {
+        Role {
+            id: sf(id),
+            party_id: sf(1),
+            avatar: None,
+            name: SmolStr::new_inline("role"),
+            desc: None,
+            permissions,
+            color: None,
+            position: 0,
+            flags: RoleFlags::empty(),
+        }
+    }
+
+    #[test]
+    fn test_from_roles() {
+        let roles = [
+            role(1, Permissions::DEFAULT),
+            role(2, Permissions::KICK_MEMBERS),
+            role(3, Permissions::BAN_MEMBERS),
+        ];
+
+        let base = Permissions::from_roles(&roles, sf(1), &[sf(3)]);
+
+        assert_eq!(base, Permissions::DEFAULT | Permissions::BAN_MEMBERS);
+    }
+
+    #[test]
+    fn test_sorted_overwrites() {
+        let roles = [role(1, Permissions::DEFAULT), role(2, Permissions::empty())];
+
+        let mut overwrites = [
+            Overwrite {
+                id: sf(10),
+                allow: Permissions::SEND_MESSAGES,
+                deny: Permissions::empty(),
+            },
+            Overwrite {
+                id: sf(2),
+                allow: Permissions::empty(),
+                deny: Permissions::SEND_MESSAGES,
+            },
+        ];
+
+        Overwrite::sort_role_first(&mut overwrites, &roles, sf(1));
+        assert_eq!(overwrites[0].id, sf::<Snowflake>(2));
+
+        // user overwrite takes precedence over the role overwrite
+        let perms = Permissions::DEFAULT.compute_overwrites(&overwrites, &[sf(1), sf(2)], sf(10));
+        assert!(perms.contains(Permissions::SEND_MESSAGES));
+
+        let perms = Permissions::DEFAULT.compute_overwrites(&overwrites, &[sf(1), sf(2)], sf(11));
+        assert!(!perms.contains(Permissions::SEND_MESSAGES));
+    }
+
+    #[test]
+    fn test_everyone_overwrite_without_role() {
+        // the @everyone role itself is missing, such as when only the member's roles are known
+        let roles = [role(2, Permissions::DEFAULT)];
+
+        let mut overwrites = [
+            Overwrite {
+                id: sf(10),
+                allow: Permissions::ADD_REACTIONS,
+                deny: Permissions::empty(),
+            },
+            Overwrite {
+                id: sf(1),
+                allow: Permissions::empty(),
+                deny: Permissions::SEND_MESSAGES,
+            },
+        ];
+
+        let explanation = PermissionExplanation::compute(&roles, sf(1), &overwrites, &[sf(2)], sf(10));
+
+        Overwrite::sort_role_first(&mut overwrites, &roles, sf(1));
+        assert_eq!(overwrites[0].id, sf::<Snowflake>(1));
+
+        let perms = Permissions::DEFAULT.compute_overwrites(&overwrites, &[sf(1), sf(2)], sf(10));
+        assert!(!perms.contains(Permissions::SEND_MESSAGES));
+        assert!(perms.contains(Permissions::ADD_REACTIONS));
+
+        assert!(!explanation.is_granted(Permissions::SEND_MESSAGES));
+    }
+
+    #[test]
+    fn test_explain_permissions() {
+        let roles = [role(1, Permissions::DEFAULT), role(2, Permissions::empty())];
+
+        let overwrites = [
+            Overwrite {
+                id: sf(10),
+                allow: Permissions::SEND_MESSAGES,
+                deny: Permissions::empty(),
+            },
+            Overwrite {
+                id: sf(2),
+                allow: Permissions::empty(),
+                deny: Permissions::SEND_MESSAGES | Permissions::ADD_REACTIONS,
+            },
+        ];
+
+        let explanation = PermissionExplanation::compute(&roles, sf(1), &overwrites, &[sf(2)], sf(10));
+
+        let trace: Vec<_> =
+            explanation.trace(Permissions::SEND_MESSAGES).map(|(source, effect)| (source.clone(), effect)).collect();
+
+        assert_eq!(trace.len(), 3);
+        assert!(matches!(trace[0], (PermissionSource::Role { .. }, PermissionEffect::Allow)));
+        assert!(matches!(
+            trace[1],
+            (PermissionSource::RoleOverwrite { .. }, PermissionEffect::Deny)
+        ));
+        assert!(matches!(
+            trace[2],
+            (PermissionSource::UserOverwrite(_), PermissionEffect::Allow)
+        ));
+
+        assert!(explanation.is_granted(Permissions::SEND_MESSAGES));
+        assert!(!explanation.is_granted(Permissions::ADD_REACTIONS));
+
+        let report = explanation.report(Permissions::SEND_MESSAGES | Permissions::ADD_REACTIONS).to_string();
+        assert!(report.contains("ADD_REACTIONS: denied"));
+
+        let admin = [role(1, Permissions::ADMINISTRATOR)];
+        let explanation = PermissionExplanation::compute(&admin, sf(1), &overwrites, &[], sf(10));
+        assert_eq!(explanation.result, Permissions::all());
+        assert_eq!(explanation.steps.last().unwrap().source, PermissionSource::Administrator);
+    }
+
+    #[test]
+    fn test_explain_unknown_role_overwrite() {
+        let roles = [role(1, Permissions::DEFAULT), role(2, Permissions::empty())];
+
+        let overwrites = [
+            Overwrite {
+                id: sf(2),
+                allow: Permissions::ADD_REACTIONS,
+                deny: Permissions::empty(),
+            },
+            Overwrite {
+                id: sf(3),
+                allow: Permissions::empty(),
+                deny: Permissions::SEND_MESSAGES,
+            },
+        ];
+
+        // the member has role 3, but it isn't among the party's known roles
+        let explanation = PermissionExplanation::compute(&roles, sf(1), &overwrites, &[sf(2), sf(3)], sf(10));
+
+        let known = PermissionSource::RoleOverwrite {
+            id: sf(2),
+            name: Some(SmolStr::new_inline("role")),
+        };
+
+        let unknown = PermissionSource::RoleOverwrite { id: sf(3), name: None };
+
+        let trace: Vec<_> = explanation.trace(Permissions::SEND_MESSAGES | Permissions::ADD_REACTIONS).collect();
+
+        assert_eq!(trace.last(), Some(&(&known, PermissionEffect::Allow)));
+        assert!(trace.contains(&(&unknown, PermissionEffect::Deny)));
+
+        assert_eq!(known.to_string(), "room overwrite for role \"role\" (2)");
+        assert_eq!(unknown.to_string(), "room overwrite for unknown role (3)");
+    }
 }
@@ -9,5 +9,5 @@ pub const LANTERN_EPOCH_PDT: PrimitiveDateTime = time::macros::datetime!(2019 -
 
 pub use snowflake::Snowflake;
 
-#[cfg(feature = "rkyv")]
+#[cfg(all(feature = "rkyv", not(feature = "strict_ids")))]
 pub use snowflake::NicheSnowflake;